@@ -1,27 +1,114 @@
 use dirs::runtime_dir;
-use std::io::{Error, ErrorKind};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
 use tokio::net::UnixStream;
 
-/// Connect to the first available stardust server, opening a Tokio UnixStream to its socket.
-pub async fn connect() -> Result<UnixStream, std::io::Error> {
-	// Is here so if you launch a stardust client from another stardust client, and somehow errored your way
-	// into an invalid value, it resolves it somehow
-	let stardust_instance: u8 = std::env::var("STARDUST_INSTANCE")
-		.ok()
-		.and_then(|s| s.parse::<u8>().ok())
-		.unwrap_or(0);
-	std::env::set_var("STARDUST_INSTANCE", stardust_instance.to_string());
+/// Why connecting to a stardust server failed.
+#[derive(Error, Debug)]
+pub enum ConnectError {
+	#[error("No stardust server socket at {path}, is the server running?")]
+	SocketNotFound { path: PathBuf },
+	#[error("The stardust server socket at {path} exists but no server is listening on it")]
+	ServerNotListening { path: PathBuf },
+	#[error("Not allowed to connect to the stardust server socket at {path}")]
+	PermissionDenied { path: PathBuf },
+	#[error("No runtime directory to find the stardust server socket in, set XDG_RUNTIME_DIR")]
+	NoRuntimeDir,
+	#[error("IO error connecting to the stardust server: {0}")]
+	Io(#[from] std::io::Error),
+}
+impl ConnectError {
+	fn from_io(path: &Path, e: std::io::Error) -> Self {
+		let path = path.to_path_buf();
+		match e.kind() {
+			ErrorKind::NotFound => ConnectError::SocketNotFound { path },
+			ErrorKind::ConnectionRefused => ConnectError::ServerNotListening { path },
+			ErrorKind::PermissionDenied => ConnectError::PermissionDenied { path },
+			_ => ConnectError::Io(e),
+		}
+	}
+}
+
+/// The parts of the environment a client uses to find its server, captured in one place instead of read from process globals while connecting.
+///
+/// Use `ClientEnv::from_process_env` for the usual behavior, or build one explicitly to make connecting deterministic,
+/// such as when embedding a client in a larger application or in tests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientEnv {
+	/// Directory the server sockets are in, `XDG_RUNTIME_DIR` in the process environment.
+	pub runtime_dir: Option<PathBuf>,
+	/// Which server instance to connect to, `STARDUST_INSTANCE` in the process environment.
+	pub instance: Option<u8>,
+	/// `STARDUST_STARTUP_TOKEN` in the process environment.
+	/// The server reads this from the client's process itself, so it's here to inspect or pass on to child processes rather than sent while connecting.
+	pub startup_token: Option<String>,
+}
+impl ClientEnv {
+	/// Capture the environment of the current process.
+	pub fn from_process_env() -> Self {
+		// An invalid value falls back to instance 0 instead of failing to connect at all.
+		// The process environment is left alone, pass `instance` on explicitly to launch child clients on the same server.
+		let stardust_instance: u8 = std::env::var("STARDUST_INSTANCE")
+			.ok()
+			.and_then(|s| s.parse::<u8>().ok())
+			.unwrap_or(0);
+		ClientEnv {
+			runtime_dir: runtime_dir(),
+			instance: Some(stardust_instance),
+			startup_token: std::env::var("STARDUST_STARTUP_TOKEN").ok(),
+		}
+	}
+}
+
+/// Options for connecting to a stardust server on top of what's in the `ClientEnv`.
+///
+/// Precedence for the socket is: `socket_path`, then `instance`, then the env's instance, then instance 0.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+	/// Exact path to the server's socket. Overrides everything else when set.
+	pub socket_path: Option<PathBuf>,
+	/// Instance number of the server, used as `{runtime_dir}/stardust-{instance}`. Overrides the env's instance when set.
+	pub instance: Option<u8>,
+}
+impl ConnectOptions {
+	/// Resolve the socket path these options point to in `env`.
+	pub fn socket_path(&self, env: &ClientEnv) -> Result<PathBuf, ConnectError> {
+		if let Some(socket_path) = &self.socket_path {
+			return Ok(socket_path.clone());
+		}
+		let instance = self.instance.or(env.instance).unwrap_or(0);
+		Ok(env
+			.runtime_dir
+			.as_ref()
+			.ok_or(ConnectError::NoRuntimeDir)?
+			.join(format!("stardust-{instance}")))
+	}
+}
 
+/// Connect to the first available stardust server in `env`, opening a Tokio UnixStream to its socket.
+pub async fn connect(env: &ClientEnv) -> Result<UnixStream, ConnectError> {
+	connect_with(&ConnectOptions::default(), env).await
+}
+/// Connect to the stardust server listening on the socket at `socket_path`, ignoring the environment.
+pub async fn connect_to(socket_path: impl AsRef<Path>) -> Result<UnixStream, ConnectError> {
+	let socket_path = socket_path.as_ref();
+	UnixStream::connect(socket_path)
+		.await
+		.map_err(|e| ConnectError::from_io(socket_path, e))
+}
+/// Connect to a stardust server using the given options, falling back to `env` for anything unset.
+pub async fn connect_with(
+	options: &ConnectOptions,
+	env: &ClientEnv,
+) -> Result<UnixStream, ConnectError> {
 	// Tries to connect the client to the server.
-	let socket_path = runtime_dir()
-		.ok_or_else(|| Error::from(ErrorKind::AddrNotAvailable))?
-		.join(format!("stardust-{stardust_instance}"));
-	UnixStream::connect(socket_path).await
+	connect_to(options.socket_path(env)?).await
 }
 
 #[tokio::test]
 async fn client_connect() {
-	let socket = super::client::connect()
+	let socket = super::client::connect(&ClientEnv::from_process_env())
 		.await
 		.expect("Socket not connected");
 	let peer_addr = socket.peer_addr().expect("Couldn't get peer address");
@@ -30,3 +117,77 @@ async fn client_connect() {
 		peer_addr.as_pathname().unwrap().to_str().unwrap()
 	);
 }
+
+#[tokio::test]
+async fn client_connect_to_path() {
+	let socket_path =
+		std::env::temp_dir().join(format!("stardust-test-{}.sock", std::process::id()));
+	let _ = std::fs::remove_file(&socket_path);
+	let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+
+	let options = ConnectOptions {
+		socket_path: Some(socket_path.clone()),
+		instance: Some(200),
+	};
+	let env = ClientEnv::default();
+	assert_eq!(options.socket_path(&env).unwrap(), socket_path);
+
+	let (socket, accepted) = tokio::join!(connect_with(&options, &env), listener.accept());
+	let socket = socket.expect("Socket not connected");
+	accepted.expect("Mock server did not accept");
+	assert_eq!(
+		socket.peer_addr().unwrap().as_pathname().unwrap(),
+		socket_path.as_path()
+	);
+	let _ = std::fs::remove_file(&socket_path);
+}
+
+#[tokio::test]
+async fn client_connect_missing_socket() {
+	let socket_path =
+		std::env::temp_dir().join(format!("stardust-missing-{}.sock", std::process::id()));
+	let _ = std::fs::remove_file(&socket_path);
+
+	let error = connect_to(&socket_path).await.unwrap_err();
+	assert!(
+		matches!(&error, ConnectError::SocketNotFound { path } if path == &socket_path),
+		"{error:?}"
+	);
+
+	// A leftover socket file with nothing listening on it is a different problem
+	drop(std::os::unix::net::UnixListener::bind(&socket_path).unwrap());
+	let error = connect_to(&socket_path).await.unwrap_err();
+	assert!(
+		matches!(error, ConnectError::ServerNotListening { .. }),
+		"{error:?}"
+	);
+	let _ = std::fs::remove_file(&socket_path);
+}
+
+#[tokio::test]
+async fn client_connect_explicit_env() {
+	let runtime_dir = std::env::temp_dir().join(format!("stardust-env-{}", std::process::id()));
+	std::fs::create_dir_all(&runtime_dir).unwrap();
+	let socket_path = runtime_dir.join("stardust-201");
+	let _ = std::fs::remove_file(&socket_path);
+	let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+
+	let env = ClientEnv {
+		runtime_dir: Some(runtime_dir.clone()),
+		instance: Some(201),
+		startup_token: None,
+	};
+	assert_eq!(
+		ConnectOptions::default().socket_path(&env).unwrap(),
+		socket_path
+	);
+	assert!(matches!(
+		ConnectOptions::default().socket_path(&ClientEnv::default()),
+		Err(ConnectError::NoRuntimeDir)
+	));
+
+	let (socket, accepted) = tokio::join!(connect(&env), listener.accept());
+	socket.expect("Socket not connected");
+	accepted.expect("Mock server did not accept");
+	let _ = std::fs::remove_dir_all(&runtime_dir);
+}
@@ -2,11 +2,16 @@
 
 /// Standard connection to servers as a client
 pub mod client;
-/// Standard creation of sockets for servers
+/// Standard creation of sockets and accepting of clients for servers
 pub mod server;
 
 /// Symmetrical messenger for client/server
 pub mod messenger;
+/// Mock server to test clients against without a compositor
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+/// Several logical clients over one socket
+pub mod multiplex;
 /// Scenegraph trait and error for messenger
 pub mod scenegraph;
 
@@ -9,10 +9,12 @@ use rustc_hash::FxHashMap;
 use stardust_xr_schemas::flat::flatbuffers::{self, InvalidFlatbuffer};
 use stardust_xr_schemas::flat::message::{root_as_message, Message as FlatMessage, MessageArgs};
 use stardust_xr_schemas::flex::flexbuffers;
-use std::future::Future;
+use std::future::{poll_fn, Future};
 use std::io::{IoSlice, IoSliceMut};
 use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::task::Poll;
 use thiserror::Error;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
 use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
@@ -95,6 +97,13 @@ pub enum MessengerError {
 	/// The message type u8 is greater than method return (3)
 	#[error("Message type is out of bounds")]
 	MessageTypeOutOfBounds,
+	/// The connection ended partway through a message, so only `received` of the `expected` bytes arrived,
+	/// or the header claimed a body longer than `MAX_FRAME_SIZE` so none of it was read
+	#[error("Corrupt frame: expected {expected} bytes but only got {received}")]
+	CorruptFrame { expected: usize, received: usize },
+	/// The server shut down with `Server::shutdown`, so it closed the connection
+	#[error("Server shut down")]
+	ServerShutdown,
 }
 impl From<std::io::Error> for MessengerError {
 	fn from(e: std::io::Error) -> Self {
@@ -121,7 +130,43 @@ impl Message {
 	}
 }
 
+/// Why a method called on the other side failed.
+///
+/// Typed errors (from `ScenegraphError::TypedMethodError`) send their code and data in the error message's data as a flex map,
+/// under the `stardust_error_code` and `stardust_error_data` keys.
+/// Older messengers ignore the data of errors and only see the message, and put the call's own data there instead, which is never such a map.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{message}")]
+pub struct RemoteMethodError {
+	pub message: String,
+	/// The error code and its data, if the other side returned one.
+	pub code: Option<(u32, Vec<u8>)>,
+}
+
+const ERROR_CODE_KEY: &str = "stardust_error_code";
+const ERROR_DATA_KEY: &str = "stardust_error_data";
+fn serialize_error_code(code: u32, data: &[u8]) -> Vec<u8> {
+	let mut fbb = flexbuffers::Builder::default();
+	let mut map = fbb.start_map();
+	map.push(ERROR_CODE_KEY, code);
+	map.push(ERROR_DATA_KEY, flexbuffers::Blob(data));
+	map.end_map();
+	fbb.view().to_vec()
+}
+fn deserialize_error_code(data: &[u8]) -> Option<(u32, Vec<u8>)> {
+	let map = flexbuffers::Reader::get_root(data).ok()?.get_map().ok()?;
+	let code = map.index(ERROR_CODE_KEY).ok()?.get_u64().ok()?;
+	let data = map.index(ERROR_DATA_KEY).ok()?.get_blob().ok()?.0;
+	Some((code.try_into().ok()?, data.to_vec()))
+}
+
+/// Longest frame body that will be read, anything claiming to be longer is treated as corrupt instead of allocating for it.
+pub const MAX_FRAME_SIZE: usize = 128 * 1024 * 1024;
+
 /// Header for sending messages over the socket.
+///
+/// Unlike the message bodies (flatbuffers and flexbuffers, which are always little-endian) this is in the host's byte order,
+/// which is fine as both ends of a unix socket are always on the same machine.
 #[derive(Clone, Copy)]
 pub struct Header {
 	pub body_length: u32,
@@ -137,7 +182,79 @@ impl Header {
 	}
 }
 
-type PendingFuture = oneshot::Sender<Result<Message, String>>;
+/// Anything a `MessageSenderHandle` can queue up for the `MessageSender`.
+enum QueuedMessage {
+	Message(Message),
+	/// Answered once every message queued before it has been written to the socket.
+	Flush(oneshot::Sender<()>),
+}
+
+/// How urgently a message should be written, when priority lanes are enabled with `MessengerOptions`.
+///
+/// Queued messages of a higher priority are always written before lower priority ones, and nothing orders the lanes relative to each other,
+/// not even messages to the same node. A `High` transform can be written before the `Normal` message creating its node, so the caller has to
+/// make sure a message only goes in another lane once nothing it depends on is still queued. Nothing picks a lane on its own, every message is `Normal`
+/// unless sent with `signal_with_priority`, so enabling lanes alone doesn't speed up transforms or input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+	/// Frame critical messages, jumping ahead of everything else queued.
+	High,
+	#[default]
+	Normal,
+	/// Bulk data such as model bytes or textures.
+	Low,
+}
+impl Priority {
+	const LANES: usize = 3;
+}
+
+/// Options for `create_with_options`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessengerOptions {
+	/// Give each `Priority` its own send queue, so queued high priority messages jump ahead of lower priority ones.
+	/// When disabled, every message is written in the order it was queued.
+	pub priority_lanes: bool,
+}
+
+/// Snapshot of a messenger's traffic, for profiling.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessengerStats {
+	pub messages_sent: u64,
+	pub messages_received: u64,
+	/// Includes message headers.
+	pub bytes_sent: u64,
+	/// Includes message headers.
+	pub bytes_received: u64,
+	/// Methods sent that the other side hasn't returned from yet.
+	pub methods_in_flight: u64,
+	pub fds_sent: u64,
+}
+
+/// Counters behind `MessengerStats`, shared between both halves and every handle.
+/// Only ever read for statistics, so relaxed ordering is enough.
+#[derive(Default)]
+struct StatCounters {
+	messages_sent: AtomicU64,
+	messages_received: AtomicU64,
+	bytes_sent: AtomicU64,
+	bytes_received: AtomicU64,
+	methods_in_flight: AtomicU64,
+	fds_sent: AtomicU64,
+}
+impl StatCounters {
+	fn snapshot(&self) -> MessengerStats {
+		MessengerStats {
+			messages_sent: self.messages_sent.load(Ordering::Relaxed),
+			messages_received: self.messages_received.load(Ordering::Relaxed),
+			bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+			bytes_received: self.bytes_received.load(Ordering::Relaxed),
+			methods_in_flight: self.methods_in_flight.load(Ordering::Relaxed),
+			fds_sent: self.fds_sent.load(Ordering::Relaxed),
+		}
+	}
+}
+
+type PendingFuture = oneshot::Sender<Result<Message, RemoteMethodError>>;
 type PendingFutureSender = mpsc::UnboundedSender<(u64, PendingFuture)>;
 type PendingFutureReceiver = mpsc::UnboundedReceiver<(u64, PendingFuture)>;
 
@@ -175,45 +292,26 @@ impl MessageReceiver {
 		let mut header_buffer = [0_u8; Header::SIZE];
 		self.read.read_exact(&mut header_buffer).await?;
 		let header = Header::from_bytes(header_buffer);
+		let stats = &self.send_handle.stats;
+		stats.messages_received.fetch_add(1, Ordering::Relaxed);
+		stats.bytes_received.fetch_add(
+			(Header::SIZE + header.body_length as usize) as u64,
+			Ordering::Relaxed,
+		);
 
-		let mut body: Vec<u8> = std::vec::from_elem(0_u8, header.body_length as usize);
-		
-		let iov = &mut [IoSliceMut::new(body.as_mut_slice())];
-
-		// 253 is the Linux value for SCM_MAX_FD (max FDs in a cmsg)
-		let mut cmsgs = cmsg_space!([RawFd; 253]);
-
-		let stream = self.read.as_ref();
-		let fds: Vec<OwnedFd> = stream
-			.async_io(Interest::READABLE, || {
-				match recvmsg::<()>(stream.as_raw_fd(), iov, Some(&mut cmsgs), MsgFlags::empty()) {
-					Ok(recv_msg) => {
-						let fds = recv_msg
-							.cmsgs()
-							.flat_map(|cmsg| {
-								if let ControlMessageOwned::ScmRights(fds) = cmsg {
-									fds
-								} else {
-									Vec::new()
-								}
-							})
-							.filter_map(|fd| match fcntl(fd, FcntlArg::F_GETFD) {
-								Err(nix::errno::Errno::EBADF) => None,
-								_ => unsafe { Some(OwnedFd::from_raw_fd(fd)) }, // Consider non-EBADF errors as valid
-							})
-							.collect();
-						Ok(fds)
-					}
-					Err(nix::Error::EWOULDBLOCK) => Err(std::io::ErrorKind::WouldBlock.into()),
-					Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
-				}
-			})
-			.await?;
+		let (body, fds) = read_body(&mut self.read, header.body_length as usize).await?;
 
 		self.update_pending_futures();
 		self.handle_message(body, scenegraph, fds)
 	}
 
+	fn method_returned(&self) {
+		self.send_handle
+			.stats
+			.methods_in_flight
+			.fetch_sub(1, Ordering::Relaxed);
+	}
+
 	#[instrument(level = "trace", skip_all)]
 	fn handle_message<S: scenegraph::Scenegraph>(
 		&mut self,
@@ -242,7 +340,11 @@ impl MessageReceiver {
 			0 => {
 				let future_opt = self.pending_futures.remove(&message.id());
 				if let Some(future) = future_opt {
-					let _ = future.send(Err(message.error().unwrap_or("unknown").to_string()));
+					self.method_returned();
+					let _ = future.send(Err(RemoteMethodError {
+						message: message.error().unwrap_or("unknown").to_string(),
+						code: deserialize_error_code(data),
+					}));
 				}
 			}
 			// Signals
@@ -266,15 +368,19 @@ impl MessageReceiver {
 					let data = message.data().unwrap_or_default().bytes();
 					if let Ok(result) = response_rx.await {
 						let _ = match result {
-							Ok((data, fds)) => send_handle.send(serialize_call(
-								3,
-								message.id(),
-								node,
-								method,
-								None,
-								&data,
-								fds,
-							)),
+							Ok((data, fds)) => send_handle.send(
+								serialize_call(3, message.id(), node, method, None, &data, fds),
+								Priority::Normal,
+							),
+							Err(ScenegraphError::TypedMethodError { error, code, data }) => {
+								send_handle.error(
+									message.id(),
+									node,
+									method,
+									error,
+									&serialize_error_code(code, &data),
+								)
+							}
 							Err(error) => {
 								send_handle.error(message.id(), node, method, error, data)
 							}
@@ -304,6 +410,7 @@ impl MessageReceiver {
 						)?;
 					}
 					Some(future) => {
+						self.method_returned();
 						let _ = future.send(Ok(Message {
 							data: data.to_vec(),
 							fds,
@@ -394,30 +501,65 @@ fn serialize_call(
 pub struct MessageSender {
 	write: OwnedWriteHalf,
 	handle: MessageSenderHandle,
-	message_rx: mpsc::UnboundedReceiver<Message>,
+	/// One queue per lane, highest priority first.
+	message_rx: Vec<mpsc::UnboundedReceiver<QueuedMessage>>,
 	pending_future_tx: PendingFutureSender,
 	message_counter: Arc<CounterU64>,
 }
 impl MessageSender {
-	fn new(write: OwnedWriteHalf, pending_future_tx: PendingFutureSender) -> Self {
-		let (message_tx, message_rx) = mpsc::unbounded_channel();
+	fn new(
+		write: OwnedWriteHalf,
+		pending_future_tx: PendingFutureSender,
+		options: MessengerOptions,
+	) -> Self {
+		let lanes = match options.priority_lanes {
+			true => Priority::LANES,
+			false => 1,
+		};
+		let (message_tx, message_rx) = (0..lanes).map(|_| mpsc::unbounded_channel()).unzip();
 		let max_message_id = Arc::new(CounterU64::new(0));
 		MessageSender {
 			write,
 			handle: MessageSenderHandle {
-				message_tx,
+				message_tx: Arc::new(message_tx),
 				pending_future_tx: pending_future_tx.clone(),
 				message_counter: max_message_id.clone(),
+				stats: Default::default(),
 			},
 			message_rx,
 			pending_future_tx,
 			message_counter: max_message_id,
 		}
 	}
+	/// Wait for the next queued message, taking from the highest priority lane that has one.
+	async fn next_queued(&mut self) -> Option<QueuedMessage> {
+		poll_fn(|cx| {
+			let mut closed = 0;
+			for lane in &mut self.message_rx {
+				match lane.poll_recv(cx) {
+					Poll::Ready(Some(queued)) => return Poll::Ready(Some(queued)),
+					Poll::Ready(None) => closed += 1,
+					Poll::Pending => (),
+				}
+			}
+			if closed == self.message_rx.len() {
+				Poll::Ready(None)
+			} else {
+				Poll::Pending
+			}
+		})
+		.await
+	}
 	/// Send all the queued messages from the handles
 	pub async fn flush(&mut self) -> Result<(), MessengerError> {
-		while let Some(message) = self.message_rx.recv().await {
-			self.send(message).await?;
+		while let Some(queued) = self.next_queued().await {
+			match queued {
+				QueuedMessage::Message(message) => self.send(message).await?,
+				QueuedMessage::Flush(flushed) => {
+					self.write.flush().await?;
+					let _ = flushed.send(());
+				}
+			}
 		}
 		Ok(())
 	}
@@ -427,31 +569,18 @@ impl MessageSender {
 		let header = Header {
 			body_length: body.len() as u32,
 		};
+		let stats = &self.handle.stats;
+		stats.messages_sent.fetch_add(1, Ordering::Relaxed);
+		stats
+			.bytes_sent
+			.fetch_add((Header::SIZE + body.len()) as u64, Ordering::Relaxed);
+		stats
+			.fds_sent
+			.fetch_add(message.fds.len() as u64, Ordering::Relaxed);
 
 		self.write.write_all(&header.into_bytes()).await?;
 
-		if message.fds.is_empty() {
-			self.write.write_all(body).await?;
-		} else {
-			let iov = &[IoSlice::new(body)];
-			let fds = message
-				.fds
-				.into_iter()
-				.map(IntoRawFd::into_raw_fd)
-				.collect::<Vec<_>>();
-			let cmsgs = &[ControlMessage::ScmRights(&fds)];
-
-			let stream = self.write.as_ref();
-			stream
-				.async_io(Interest::WRITABLE, || {
-					match sendmsg::<()>(stream.as_raw_fd(), iov, cmsgs, MsgFlags::empty(), None) {
-						Ok(_) => Ok(()),
-						Err(nix::Error::EWOULDBLOCK) => Err(std::io::ErrorKind::WouldBlock.into()),
-						Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
-					}
-				})
-				.await?;
-		}
+		write_body(&mut self.write, body, message.fds).await?;
 		Ok(())
 	}
 	/// Get a handle to send messages from anywhere.
@@ -479,11 +608,28 @@ impl MessageSender {
 		data: &[u8],
 		fds: Vec<OwnedFd>,
 	) -> Result<Result<Message, String>, MessengerError> {
+		Ok(self
+			.method_typed(node, method, data, fds)
+			.await?
+			.map_err(|e| e.message))
+	}
+	/// Like `method`, but keeps the error code and data if the other side returned a typed error.
+	pub async fn method_typed(
+		&mut self,
+		node: u64,
+		method: u64,
+		data: &[u8],
+		fds: Vec<OwnedFd>,
+	) -> Result<Result<Message, RemoteMethodError>, MessengerError> {
 		let (tx, rx) = oneshot::channel();
 		let id = self.message_counter.inc();
 		self.pending_future_tx
 			.send((id, tx))
 			.map_err(|_| MessengerError::ReceiverDropped)?;
+		self.handle
+			.stats
+			.methods_in_flight
+			.fetch_add(1, Ordering::Relaxed);
 		self.send(serialize_method_call(id, node, method, data, fds))
 			.await?;
 		rx.await.map_err(|_| MessengerError::ReceiverDropped)
@@ -493,9 +639,11 @@ impl MessageSender {
 /// Handle to the message sender, so you can synchronously send messages from anywhere without blocking.
 #[derive(Clone)]
 pub struct MessageSenderHandle {
-	message_tx: mpsc::UnboundedSender<Message>,
+	/// One queue per lane, highest priority first.
+	message_tx: Arc<Vec<mpsc::UnboundedSender<QueuedMessage>>>,
 	pending_future_tx: PendingFutureSender,
 	message_counter: Arc<CounterU64>,
+	stats: Arc<StatCounters>,
 }
 impl MessageSenderHandle {
 	/// Queue up an error to be sent.
@@ -507,7 +655,10 @@ impl MessageSenderHandle {
 		err: E,
 		data: &[u8],
 	) -> Result<(), MessengerError> {
-		self.send(serialize_error(id, node, method, err, data))
+		self.send(
+			serialize_error(id, node, method, err, data),
+			Priority::Normal,
+		)
 	}
 	/// Queue up a signal to be sent.
 	pub fn signal(
@@ -516,9 +667,20 @@ impl MessageSenderHandle {
 		signal: u64,
 		data: &[u8],
 		fds: Vec<OwnedFd>,
+	) -> Result<(), MessengerError> {
+		self.signal_with_priority(node, signal, data, fds, Priority::Normal)
+	}
+	/// Queue up a signal to be sent in the lane for `priority`.
+	pub fn signal_with_priority(
+		&self,
+		node: u64,
+		signal: u64,
+		data: &[u8],
+		fds: Vec<OwnedFd>,
+		priority: Priority,
 	) -> Result<(), MessengerError> {
 		let id = self.message_counter.inc();
-		self.send(serialize_signal_call(id, node, signal, data, fds))
+		self.send(serialize_signal_call(id, node, signal, data, fds), priority)
 	}
 	/// Queue up a method to be sent and get back a future for when a response is returned.
 	pub fn method(
@@ -528,28 +690,264 @@ impl MessageSenderHandle {
 		data: &[u8],
 		fds: Vec<OwnedFd>,
 	) -> Result<impl Future<Output = Result<Message, String>>, MessengerError> {
+		let future = self.method_typed(node, method, data, fds)?;
+		Ok(async move { future.await.map_err(|e| e.message) })
+	}
+	/// Like `method`, but the future keeps the error code and data if the other side returned a typed error.
+	pub fn method_typed(
+		&self,
+		node: u64,
+		method: u64,
+		data: &[u8],
+		fds: Vec<OwnedFd>,
+	) -> Result<impl Future<Output = Result<Message, RemoteMethodError>>, MessengerError> {
 		let (tx, rx) = oneshot::channel();
 		let id = self.message_counter.inc();
 		self.pending_future_tx
 			.send((id, tx))
 			.map_err(|_| MessengerError::ReceiverDropped)?;
-		self.send(serialize_method_call(id, node, method, data, fds))?;
-		Ok(async move { rx.await.map_err(|e| e.to_string())? })
+		self.stats.methods_in_flight.fetch_add(1, Ordering::Relaxed);
+		self.send(
+			serialize_method_call(id, node, method, data, fds),
+			Priority::Normal,
+		)?;
+		Ok(async move {
+			rx.await.map_err(|e| RemoteMethodError {
+				message: e.to_string(),
+				code: None,
+			})?
+		})
+	}
+	/// Get a future that resolves once every message queued before this call has been written to the socket.
+	pub fn flush(
+		&self,
+	) -> Result<impl Future<Output = Result<(), MessengerError>>, MessengerError> {
+		let (tx, rx) = oneshot::channel();
+		// The lowest priority lane is only read once every other lane is empty, so everything queued before is written by then
+		self.message_tx
+			.last()
+			.unwrap()
+			.send(QueuedMessage::Flush(tx))
+			.map_err(|_| MessengerError::ReceiverDropped)?;
+		Ok(async move { rx.await.map_err(|_| MessengerError::ReceiverDropped) })
+	}
+
+	/// Get the traffic counters of this messenger so far.
+	pub fn stats(&self) -> MessengerStats {
+		self.stats.snapshot()
 	}
 
 	#[instrument(level = "trace", skip_all)]
-	fn send(&self, message: Message) -> Result<(), MessengerError> {
-		self.message_tx
-			.send(message)
+	fn send(&self, message: Message, priority: Priority) -> Result<(), MessengerError> {
+		let lane = (priority as usize).min(self.message_tx.len() - 1);
+		self.message_tx[lane]
+			.send(QueuedMessage::Message(message))
 			.map_err(|_| MessengerError::ReceiverDropped)
 	}
 }
 
+/// Read the body of a frame, `length` bytes long, along with any fds sent with it.
+pub(crate) async fn read_body(
+	read: &mut OwnedReadHalf,
+	length: usize,
+) -> Result<(Vec<u8>, Vec<OwnedFd>), MessengerError> {
+	if length > MAX_FRAME_SIZE {
+		return Err(MessengerError::CorruptFrame {
+			expected: length,
+			received: 0,
+		});
+	}
+	let mut body: Vec<u8> = std::vec::from_elem(0_u8, length);
+	let iov = &mut [IoSliceMut::new(body.as_mut_slice())];
+
+	// 253 is the Linux value for SCM_MAX_FD (max FDs in a cmsg)
+	let mut cmsgs = cmsg_space!([RawFd; 253]);
+
+	let stream = read.as_ref();
+	let (fds, mut received): (Vec<OwnedFd>, usize) = stream
+		.async_io(Interest::READABLE, || {
+			match recvmsg::<()>(stream.as_raw_fd(), iov, Some(&mut cmsgs), MsgFlags::empty()) {
+				Ok(recv_msg) => {
+					let fds = recv_msg
+						.cmsgs()
+						.flat_map(|cmsg| {
+							if let ControlMessageOwned::ScmRights(fds) = cmsg {
+								fds
+							} else {
+								Vec::new()
+							}
+						})
+						.filter_map(|fd| match fcntl(fd, FcntlArg::F_GETFD) {
+							Err(nix::errno::Errno::EBADF) => None,
+							_ => unsafe { Some(OwnedFd::from_raw_fd(fd)) }, // Consider non-EBADF errors as valid
+						})
+						.collect();
+					Ok((fds, recv_msg.bytes))
+				}
+				Err(nix::Error::EWOULDBLOCK) => Err(std::io::ErrorKind::WouldBlock.into()),
+				Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
+			}
+		})
+		.await?;
+	// The body may arrive in several pieces, make sure all of it is here before parsing it
+	while received < body.len() {
+		match read.read(&mut body[received..]).await? {
+			0 => {
+				return Err(MessengerError::CorruptFrame {
+					expected: body.len(),
+					received,
+				})
+			}
+			bytes => received += bytes,
+		}
+	}
+	Ok((body, fds))
+}
+/// Write the body of a frame, sending `fds` along with it.
+pub(crate) async fn write_body(
+	write: &mut OwnedWriteHalf,
+	body: &[u8],
+	fds: Vec<OwnedFd>,
+) -> std::io::Result<()> {
+	if fds.is_empty() {
+		write.write_all(body).await?;
+	} else {
+		let iov = &[IoSlice::new(body)];
+		let fds = fds
+			.into_iter()
+			.map(IntoRawFd::into_raw_fd)
+			.collect::<Vec<_>>();
+		let cmsgs = &[ControlMessage::ScmRights(&fds)];
+
+		let stream = write.as_ref();
+		stream
+			.async_io(Interest::WRITABLE, || {
+				match sendmsg::<()>(stream.as_raw_fd(), iov, cmsgs, MsgFlags::empty(), None) {
+					Ok(_) => Ok(()),
+					Err(nix::Error::EWOULDBLOCK) => Err(std::io::ErrorKind::WouldBlock.into()),
+					Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
+				}
+			})
+			.await?;
+	}
+	Ok(())
+}
+
 /// Create 2 messenger halves from a connection to a stardust client or server.
 pub fn create(connection: UnixStream) -> (MessageSender, MessageReceiver) {
+	create_with_options(connection, MessengerOptions::default())
+}
+/// Create 2 messenger halves like `create`, with explicit options.
+pub fn create_with_options(
+	connection: UnixStream,
+	options: MessengerOptions,
+) -> (MessageSender, MessageReceiver) {
 	let (read, write) = connection.into_split();
 	let (pending_future_tx, pending_future_rx) = mpsc::unbounded_channel();
-	let sender = MessageSender::new(write, pending_future_tx);
+	let sender = MessageSender::new(write, pending_future_tx, options);
 	let receiver = MessageReceiver::new(read, pending_future_rx, sender.handle());
 	(sender, receiver)
 }
+
+#[tokio::test]
+async fn messenger_flush() {
+	let (local, remote) = UnixStream::pair().unwrap();
+	let (mut sender, _receiver) = create(local);
+	let handle = sender.handle();
+	handle.signal(1, 2, &[], Vec::new()).unwrap();
+
+	let flushed = handle.flush().unwrap();
+	tokio::task::spawn(async move { sender.flush().await });
+	flushed.await.unwrap();
+
+	let mut header_buffer = [0_u8; Header::SIZE];
+	remote.try_read(&mut header_buffer).unwrap();
+	let header = Header::from_bytes(header_buffer);
+	let mut body = vec![0_u8; header.body_length as usize];
+	remote.try_read(&mut body).unwrap();
+	let message = root_as_message(&body).unwrap();
+	assert_eq!(message.type_(), 1);
+	assert_eq!(message.node(), 1);
+	assert_eq!(message.method(), 2);
+}
+
+#[tokio::test]
+async fn messenger_priority_lanes() {
+	let (local, mut remote) = UnixStream::pair().unwrap();
+	let (mut sender, _receiver) = create_with_options(
+		local,
+		MessengerOptions {
+			priority_lanes: true,
+		},
+	);
+	let handle = sender.handle();
+	let bulk = vec![0_u8; 1024 * 1024];
+	handle
+		.signal_with_priority(1, 0, &bulk, Vec::new(), Priority::Low)
+		.unwrap();
+	handle
+		.signal_with_priority(2, 0, &[], Vec::new(), Priority::High)
+		.unwrap();
+	tokio::task::spawn(async move { sender.flush().await });
+
+	let mut header_buffer = [0_u8; Header::SIZE];
+	remote.read_exact(&mut header_buffer).await.unwrap();
+	let header = Header::from_bytes(header_buffer);
+	let mut body = vec![0_u8; header.body_length as usize];
+	remote.read_exact(&mut body).await.unwrap();
+	let message = root_as_message(&body).unwrap();
+	assert_eq!(message.node(), 2);
+}
+
+#[tokio::test]
+async fn messenger_stats_sent() {
+	let (local, _remote) = UnixStream::pair().unwrap();
+	let (mut sender, _receiver) = create(local);
+	let handle = sender.handle();
+	tokio::task::spawn(async move { sender.flush().await });
+
+	let before = handle.stats();
+	for i in 0..10 {
+		handle.signal(1, i, &[], Vec::new()).unwrap();
+	}
+	handle.flush().unwrap().await.unwrap();
+	let after = handle.stats();
+	assert_eq!(after.messages_sent - before.messages_sent, 10);
+	assert!(after.bytes_sent - before.bytes_sent >= 10 * Header::SIZE as u64);
+	assert_eq!(after.fds_sent, 0);
+	assert_eq!(after.methods_in_flight, 0);
+}
+
+#[tokio::test]
+async fn messenger_truncated_frame() {
+	use crate::mock::MockServer;
+
+	let (local, mut remote) = UnixStream::pair().unwrap();
+	let (_sender, mut receiver) = create(local);
+	let header = Header { body_length: 100 };
+	remote.write_all(&header.into_bytes()).await.unwrap();
+	remote.write_all(&[0xAB; 10]).await.unwrap();
+	drop(remote);
+
+	let scenegraph = MockServer::new();
+	assert!(matches!(
+		receiver.dispatch(&*scenegraph).await,
+		Err(MessengerError::CorruptFrame {
+			expected: 100,
+			received: 10
+		})
+	));
+	assert!(scenegraph.signals().is_empty() && scenegraph.method_calls().is_empty());
+}
+
+#[tokio::test]
+async fn messenger_oversized_frame() {
+	let (local, _remote) = UnixStream::pair().unwrap();
+	let (mut read, _write) = local.into_split();
+
+	// Fails right away instead of allocating and waiting for a body that'll never come
+	assert!(matches!(
+		read_body(&mut read, MAX_FRAME_SIZE + 1).await,
+		Err(MessengerError::CorruptFrame { received: 0, .. })
+	));
+}
@@ -0,0 +1,134 @@
+use crate::{
+	messenger,
+	scenegraph::{MethodResponse, Scenegraph, ScenegraphError},
+	schemas::flex::serialize,
+};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use std::{os::fd::OwnedFd, sync::Arc};
+use tokio::net::UnixStream;
+
+type MockMethod = dyn Fn(u64, &[u8]) -> Result<Vec<u8>, ScenegraphError> + Send + Sync + 'static;
+type MockSignal =
+	dyn Fn(u64, &[u8], Vec<OwnedFd>) -> Result<(), ScenegraphError> + Send + Sync + 'static;
+
+/// A message a `MockServer` received from its client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockMessage {
+	pub node: u64,
+	pub method: u64,
+	pub data: Vec<u8>,
+}
+
+/// In-process stand-in for a stardust server, so clients can be tested without a running compositor.
+///
+/// Methods are answered by opcode with whatever was set up with `respond`/`on_method`, any other method fails with `ScenegraphError::MethodNotFound`.
+/// Every signal and method call is recorded so tests can check what the client sent.
+/// It's a `Scenegraph` itself, so it can also be handed to `MessageReceiver::dispatch` directly to record whatever comes in on any messenger.
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use stardust_xr::mock::MockServer;
+///
+/// let server = MockServer::new();
+/// server.respond(42, &"hello");
+/// // Hand this to the client instead of connecting to a real server
+/// let socket = server.connect().unwrap();
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MockServer {
+	methods: Mutex<FxHashMap<u64, Arc<MockMethod>>>,
+	signal_handlers: Mutex<FxHashMap<u64, Arc<MockSignal>>>,
+	signals: Mutex<Vec<MockMessage>>,
+	method_calls: Mutex<Vec<MockMessage>>,
+}
+impl MockServer {
+	pub fn new() -> Arc<Self> {
+		Arc::new(Self::default())
+	}
+
+	/// Answer every call to `method` with `value`, regardless of the node or arguments.
+	pub fn respond<T: Serialize>(&self, method: u64, value: &T) {
+		let data = serialize(value).expect("Couldn't serialize mock response");
+		self.on_method(method, move |_node, _data| Ok(data.clone()));
+	}
+	/// Answer calls to `method` with the serialized response `handler` returns for the node and arguments.
+	pub fn on_method<F>(&self, method: u64, handler: F)
+	where
+		F: Fn(u64, &[u8]) -> Result<Vec<u8>, ScenegraphError> + Send + Sync + 'static,
+	{
+		self.methods.lock().insert(method, Arc::new(handler));
+	}
+	/// Call `handler` with the node, data and fds of every `signal` after recording it, failing the signal if it returns an error.
+	/// Other signals just succeed.
+	pub fn on_signal<F>(&self, signal: u64, handler: F)
+	where
+		F: Fn(u64, &[u8], Vec<OwnedFd>) -> Result<(), ScenegraphError> + Send + Sync + 'static,
+	{
+		self.signal_handlers
+			.lock()
+			.insert(signal, Arc::new(handler));
+	}
+
+	/// All signals the client has sent so far, in order.
+	pub fn signals(&self) -> Vec<MockMessage> {
+		self.signals.lock().clone()
+	}
+	/// All methods the client has called so far, in order.
+	pub fn method_calls(&self) -> Vec<MockMessage> {
+		self.method_calls.lock().clone()
+	}
+
+	/// Create a socket connected to this server, serving it on the current tokio runtime until the other end is dropped.
+	pub fn connect(self: &Arc<Self>) -> std::io::Result<UnixStream> {
+		let (client_socket, server_socket) = UnixStream::pair()?;
+		let (mut message_tx, mut message_rx) = messenger::create(server_socket);
+		tokio::task::spawn(async move { while message_tx.flush().await.is_ok() {} });
+		let server = self.clone();
+		tokio::task::spawn(async move { while message_rx.dispatch(&*server).await.is_ok() {} });
+		Ok(client_socket)
+	}
+}
+impl Scenegraph for MockServer {
+	fn send_signal(
+		&self,
+		node: u64,
+		method: u64,
+		data: &[u8],
+		fds: Vec<OwnedFd>,
+	) -> Result<(), ScenegraphError> {
+		self.signals.lock().push(MockMessage {
+			node,
+			method,
+			data: data.to_vec(),
+		});
+		let handler = self.signal_handlers.lock().get(&method).cloned();
+		match handler {
+			Some(handler) => handler(node, data, fds),
+			None => Ok(()),
+		}
+	}
+	fn execute_method(
+		&self,
+		node: u64,
+		method: u64,
+		data: &[u8],
+		_fds: Vec<OwnedFd>,
+		response: MethodResponse,
+	) {
+		self.method_calls.lock().push(MockMessage {
+			node,
+			method,
+			data: data.to_vec(),
+		});
+		let handler = self.methods.lock().get(&method).cloned();
+		let result = match handler {
+			Some(handler) => handler(node, data).map(|data| (data, Vec::new())),
+			None => Err(ScenegraphError::MethodNotFound),
+		};
+		let _ = response.send(result);
+	}
+}
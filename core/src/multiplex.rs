@@ -0,0 +1,343 @@
+//! Several logical clients sharing one socket, such as every plugin in a plugin host.
+//!
+//! # Framing
+//! Every messenger frame on a multiplexed socket is prefixed with the ID of the session it belongs to,
+//! so frames are `[session: u32][body_length: u32][body]` (native endian like the normal `Header`) with any fds attached to the body as usual.
+//! A frame with an empty body closes its session, messenger frames are never empty.
+//!
+//! # Session lifecycle
+//! - One side (normally the client) opens sessions with `Multiplexer::open_session`, which picks a new ID.
+//!   Only one side should open sessions, otherwise both sides could pick the same ID.
+//! - The other side gets the session from `Multiplexer::accept_session` once its first frame arrives, so the opening side has to send first (as clients always do).
+//! - Each session is a plain `UnixStream` on both sides to hand to `messenger::create`,
+//!   so every session has its own messenger, scenegraph and node IDs and can't see the others.
+//! - Dropping either end of a session's stream closes it on both sides. Dropping the `Multiplexer` or losing the socket closes every session.
+//! - Closed session IDs are remembered, so frames still arriving for a session after it closed are dropped instead of reopening it.
+
+use crate::messenger::{read_body, write_body, Header, MessengerError};
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{
+	os::fd::OwnedFd,
+	sync::{
+		atomic::{AtomicU32, Ordering},
+		Arc,
+	},
+};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::{
+		unix::{OwnedReadHalf, OwnedWriteHalf},
+		UnixStream,
+	},
+	sync::mpsc,
+	task::JoinHandle,
+};
+
+type Frame = (Vec<u8>, Vec<OwnedFd>);
+type Sessions = Arc<Mutex<SessionTable>>;
+#[derive(Default)]
+struct SessionTable {
+	/// Where to send the frames that arrive for each open session.
+	open: FxHashMap<u32, mpsc::UnboundedSender<Frame>>,
+	/// Sessions that were open and closed since, so late frames for them don't reopen them.
+	closed: FxHashSet<u32>,
+}
+impl SessionTable {
+	fn close(&mut self, id: u32) {
+		if self.open.remove(&id).is_some() {
+			self.closed.insert(id);
+		}
+	}
+}
+/// Frames to write to the socket, `None` closes the session.
+type Outgoing = mpsc::UnboundedSender<(u32, Option<Frame>)>;
+
+/// Carries many messenger sessions over one socket, see the module docs for how.
+pub struct Multiplexer {
+	sessions: Sessions,
+	outgoing: Outgoing,
+	accepted: mpsc::UnboundedReceiver<(u32, UnixStream)>,
+	session_counter: AtomicU32,
+	reader: JoinHandle<()>,
+	writer: JoinHandle<()>,
+}
+impl Multiplexer {
+	/// Start multiplexing sessions over `connection`, the other end has to be multiplexed too.
+	///
+	/// Must be called inside a tokio runtime, as the frames are moved around by tasks.
+	pub fn new(connection: UnixStream) -> Self {
+		let (read, write) = connection.into_split();
+		let sessions = Sessions::default();
+		let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+		let (accepted_tx, accepted) = mpsc::unbounded_channel();
+		let reader = tokio::spawn(read_frames(
+			read,
+			sessions.clone(),
+			outgoing.clone(),
+			accepted_tx,
+		));
+		let writer = tokio::spawn(write_frames(write, outgoing_rx));
+		Multiplexer {
+			sessions,
+			outgoing,
+			accepted,
+			session_counter: AtomicU32::new(0),
+			reader,
+			writer,
+		}
+	}
+
+	/// Open a new session, returning its ID and the stream to create its messenger from.
+	pub fn open_session(&self) -> std::io::Result<(u32, UnixStream)> {
+		let id = self.session_counter.fetch_add(1, Ordering::Relaxed);
+		let (stream, _) = add_session(id, &self.sessions, &self.outgoing)?;
+		Ok((id, stream))
+	}
+	/// Wait for the other side to open a session, returning its ID and the stream to create its messenger from.
+	/// Returns `None` once the socket has closed.
+	pub async fn accept_session(&mut self) -> Option<(u32, UnixStream)> {
+		self.accepted.recv().await
+	}
+}
+impl Drop for Multiplexer {
+	fn drop(&mut self) {
+		self.reader.abort();
+		self.writer.abort();
+		self.sessions.lock().open.clear();
+	}
+}
+
+/// Connect a session to the socket, returning the session's end of it.
+fn add_session(
+	id: u32,
+	sessions: &Sessions,
+	outgoing: &Outgoing,
+) -> std::io::Result<(UnixStream, mpsc::UnboundedSender<Frame>)> {
+	let (stream, pump) = UnixStream::pair()?;
+	let (mut pump_read, mut pump_write) = pump.into_split();
+	let (incoming_tx, mut incoming_rx) = mpsc::unbounded_channel::<Frame>();
+	sessions.lock().open.insert(id, incoming_tx.clone());
+
+	// From the other side into the session, until it's closed from either side
+	tokio::spawn(async move {
+		while let Some((body, fds)) = incoming_rx.recv().await {
+			if write_frame(&mut pump_write, None, &body, fds)
+				.await
+				.is_err()
+			{
+				break;
+			}
+		}
+	});
+	// From the session out to the other side, until the session's stream is dropped
+	let sessions = sessions.clone();
+	let outgoing = outgoing.clone();
+	tokio::spawn(async move {
+		while let Ok(frame) = read_frame(&mut pump_read).await {
+			if outgoing.send((id, Some(frame))).is_err() {
+				return;
+			}
+		}
+		sessions.lock().close(id);
+		let _ = outgoing.send((id, None));
+	});
+
+	Ok((stream, incoming_tx))
+}
+
+async fn read_frames(
+	mut read: OwnedReadHalf,
+	sessions: Sessions,
+	outgoing: Outgoing,
+	accepted: mpsc::UnboundedSender<(u32, UnixStream)>,
+) {
+	loop {
+		let mut session = [0_u8; 4];
+		if read.read_exact(&mut session).await.is_err() {
+			break;
+		}
+		let id = u32::from_ne_bytes(session);
+		let Ok((body, fds)) = read_frame(&mut read).await else {
+			break;
+		};
+		if body.is_empty() {
+			sessions.lock().close(id);
+			continue;
+		}
+
+		let (existing, closed) = {
+			let sessions = sessions.lock();
+			(
+				sessions.open.get(&id).cloned(),
+				sessions.closed.contains(&id),
+			)
+		};
+		let incoming = match existing {
+			Some(incoming) => incoming,
+			None if closed => {
+				tracing::debug!("Dropping a frame for closed multiplexed session {id}");
+				continue;
+			}
+			None => match add_session(id, &sessions, &outgoing) {
+				Ok((stream, incoming)) => {
+					let _ = accepted.send((id, stream));
+					incoming
+				}
+				Err(e) => {
+					tracing::warn!("Couldn't accept multiplexed session {id}: {e}");
+					continue;
+				}
+			},
+		};
+		let _ = incoming.send((body, fds));
+	}
+	sessions.lock().open.clear();
+}
+
+async fn write_frames(
+	mut write: OwnedWriteHalf,
+	mut outgoing: mpsc::UnboundedReceiver<(u32, Option<Frame>)>,
+) {
+	while let Some((id, frame)) = outgoing.recv().await {
+		let (body, fds) = frame.unwrap_or_default();
+		if let Err(e) = write_frame(&mut write, Some(id), &body, fds).await {
+			tracing::warn!("Multiplexed socket broke: {e}");
+			break;
+		}
+	}
+}
+
+async fn read_frame(read: &mut OwnedReadHalf) -> Result<Frame, MessengerError> {
+	let mut header_buffer = [0_u8; Header::SIZE];
+	read.read_exact(&mut header_buffer).await?;
+	let header = Header::from_bytes(header_buffer);
+	if header.body_length == 0 {
+		return Ok(Default::default());
+	}
+	read_body(read, header.body_length as usize).await
+}
+async fn write_frame(
+	write: &mut OwnedWriteHalf,
+	session: Option<u32>,
+	body: &[u8],
+	fds: Vec<OwnedFd>,
+) -> std::io::Result<()> {
+	let header = Header {
+		body_length: body.len() as u32,
+	};
+	let mut prefix = Vec::with_capacity(8);
+	if let Some(session) = session {
+		prefix.extend_from_slice(&session.to_ne_bytes());
+	}
+	prefix.extend_from_slice(&header.into_bytes());
+	write.write_all(&prefix).await?;
+	write_body(write, body, fds).await
+}
+
+#[tokio::test]
+async fn multiplex_isolated_sessions() {
+	use crate::{
+		messenger,
+		mock::{MockMessage, MockServer},
+	};
+
+	let (client, server) = UnixStream::pair().unwrap();
+	let client = Multiplexer::new(client);
+	let mut server = Multiplexer::new(server);
+
+	let (id_a, stream_a) = client.open_session().unwrap();
+	let (id_b, stream_b) = client.open_session().unwrap();
+	assert_ne!(id_a, id_b);
+	let (mut client_a, _client_a_rx) = messenger::create(stream_a);
+	let (mut client_b, _client_b_rx) = messenger::create(stream_b);
+	// Both use node 1, which only means something within their own session
+	client_a.signal(1, 10, b"a", Vec::new()).await.unwrap();
+	client_b.signal(1, 20, b"b", Vec::new()).await.unwrap();
+
+	let mut sessions = FxHashMap::default();
+	for _ in 0..2 {
+		let (id, stream) = server.accept_session().await.unwrap();
+		sessions.insert(id, messenger::create(stream));
+	}
+	for (id, expected) in [(id_a, (1, 10, b"a")), (id_b, (1, 20, b"b"))] {
+		let (_, server_rx) = sessions.get_mut(&id).unwrap();
+		let scenegraph = MockServer::new();
+		server_rx.dispatch(&*scenegraph).await.unwrap();
+		assert_eq!(
+			scenegraph.signals(),
+			vec![MockMessage {
+				node: expected.0,
+				method: expected.1,
+				data: expected.2.to_vec()
+			}]
+		);
+	}
+
+	// Closing one session leaves the other working
+	drop((client_a, _client_a_rx));
+	let (_, server_a_rx) = sessions.get_mut(&id_a).unwrap();
+	assert!(server_a_rx.dispatch(&*MockServer::new()).await.is_err());
+	client_b
+		.signal(2, 30, b"still here", Vec::new())
+		.await
+		.unwrap();
+	let (_, server_b_rx) = sessions.get_mut(&id_b).unwrap();
+	let scenegraph = MockServer::new();
+	server_b_rx.dispatch(&*scenegraph).await.unwrap();
+	assert_eq!(
+		scenegraph.signals(),
+		vec![MockMessage {
+			node: 2,
+			method: 30,
+			data: b"still here".to_vec()
+		}]
+	);
+}
+
+#[tokio::test]
+async fn multiplex_late_frame_for_closed_session() {
+	let (raw, multiplexed) = UnixStream::pair().unwrap();
+	let mut multiplexer = Multiplexer::new(multiplexed);
+	let (_raw_read, mut raw_write) = raw.into_split();
+
+	write_frame(&mut raw_write, Some(0), b"open", Vec::new())
+		.await
+		.unwrap();
+	let (id, _stream) = multiplexer.accept_session().await.unwrap();
+	assert_eq!(id, 0);
+	// Closed, and then a frame that was already on its way arrives
+	write_frame(&mut raw_write, Some(0), &[], Vec::new())
+		.await
+		.unwrap();
+	write_frame(&mut raw_write, Some(0), b"late", Vec::new())
+		.await
+		.unwrap();
+	write_frame(&mut raw_write, Some(1), b"next", Vec::new())
+		.await
+		.unwrap();
+
+	let (id, _stream) = multiplexer.accept_session().await.unwrap();
+	assert_eq!(id, 1);
+}
+
+#[tokio::test]
+async fn multiplex_sessions_first_sent_out_of_order() {
+	let (client, server) = UnixStream::pair().unwrap();
+	let client = Multiplexer::new(client);
+	let mut server = Multiplexer::new(server);
+
+	let (id_a, stream_a) = client.open_session().unwrap();
+	let (id_b, stream_b) = client.open_session().unwrap();
+	let (_, mut write_a) = stream_a.into_split();
+	let (_, mut write_b) = stream_b.into_split();
+	// B was opened later but sends first
+	write_frame(&mut write_b, None, b"b", Vec::new())
+		.await
+		.unwrap();
+	assert_eq!(server.accept_session().await.unwrap().0, id_b);
+	write_frame(&mut write_a, None, b"a", Vec::new())
+		.await
+		.unwrap();
+	assert_eq!(server.accept_session().await.unwrap().0, id_a);
+}
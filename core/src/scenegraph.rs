@@ -17,6 +17,14 @@ pub enum ScenegraphError {
 	SignalError { error: String },
 	#[error("Method error: {error}")]
 	MethodError { error: String },
+	/// A method error with a code the caller can match on (and data to go with it, serialized however the method documents),
+	/// instead of only a message. The caller gets it as a `RemoteMethodError` with `code` set.
+	#[error("Method error {code}: {error}")]
+	TypedMethodError {
+		error: String,
+		code: u32,
+		data: Vec<u8>,
+	},
 }
 
 /// Handles node signals and method calls for the messenger.
@@ -1,8 +1,23 @@
+use crate::{
+	messenger::{self, MessageReceiver, MessageSender, MessageSenderHandle, MessengerError},
+	scenegraph::Scenegraph,
+};
 use cluFlock::ExclusiveFlock;
 use dirs::runtime_dir;
+use global_counter::primitive::exact::CounterU64;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use stardust_xr_schemas::protocol::aspect_member_opcode;
 use std::{
 	fs::{self, File},
-	path::PathBuf,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+use tokio::{
+	net::{UnixListener, UnixStream},
+	sync::watch,
+	task::JoinSet,
 };
 
 /// Get the lowest numbered socket path not taken by another server, if available.
@@ -33,8 +48,346 @@ pub fn get_free_socket_path() -> Option<PathBuf> {
 	}
 }
 
+/// Which process and user is on the other end of a socket, as reported by the kernel so the client can't fake it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+	/// Only missing on platforms that don't report it.
+	pub pid: Option<i32>,
+	pub uid: u32,
+	pub gid: u32,
+}
+/// Get the credentials of the process on the other end of `stream` (`SO_PEERCRED`), useful for per-app permissions.
+pub fn peer_credentials(stream: &UnixStream) -> std::io::Result<PeerCredentials> {
+	let cred = stream.peer_cred()?;
+	Ok(PeerCredentials {
+		pid: cred.pid(),
+		uid: cred.uid(),
+		gid: cred.gid(),
+	})
+}
+
+/// A client connected to a `Server`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+	/// Unique among all clients that connected to this server.
+	pub id: u64,
+	/// The `STARDUST_STARTUP_TOKEN` environment variable of the client's process, if it had one.
+	pub startup_token: Option<String>,
+	/// The client's process and user, if the socket could report them.
+	pub credentials: Option<PeerCredentials>,
+}
+
+type ClientCallback = dyn Fn(&ClientInfo) + Send + Sync + 'static;
+#[derive(Default)]
+struct ClientCallbacks {
+	connected: Option<Arc<ClientCallback>>,
+	disconnected: Option<Arc<ClientCallback>>,
+}
+
+type Connections = Arc<Mutex<FxHashMap<u64, MessageSenderHandle>>>;
+
+/// Accepts clients on a socket, letting you react to clients connecting and disconnecting.
+pub struct Server {
+	listener: UnixListener,
+	id_counter: CounterU64,
+	callbacks: Arc<Mutex<ClientCallbacks>>,
+	connections: Connections,
+	shutdown: watch::Sender<bool>,
+}
+impl Server {
+	/// Listen for clients on the socket at `socket_path`, such as one from `get_free_socket_path`.
+	pub fn bind(socket_path: impl AsRef<Path>) -> std::io::Result<Self> {
+		Ok(Server {
+			listener: UnixListener::bind(socket_path)?,
+			id_counter: CounterU64::new(0),
+			callbacks: Default::default(),
+			connections: Default::default(),
+			shutdown: watch::Sender::new(false),
+		})
+	}
+	/// Call `callback` every time a client is accepted.
+	pub fn on_client_connected(&self, callback: impl Fn(&ClientInfo) + Send + Sync + 'static) {
+		self.callbacks.lock().connected.replace(Arc::new(callback));
+	}
+	/// Call `callback` every time a client's `ClientConnection::run` ends, such as when its socket closes.
+	pub fn on_client_disconnected(&self, callback: impl Fn(&ClientInfo) + Send + Sync + 'static) {
+		self.callbacks
+			.lock()
+			.disconnected
+			.replace(Arc::new(callback));
+	}
+
+	/// Wait for the next client to connect.
+	pub async fn accept(&self) -> std::io::Result<ClientConnection> {
+		let (stream, _) = self.listener.accept().await?;
+		let credentials = peer_credentials(&stream).ok();
+		let info = ClientInfo {
+			id: self.id_counter.inc(),
+			startup_token: credentials.and_then(|c| startup_token(c.pid?)),
+			credentials,
+		};
+		// cloned out so the callback can set callbacks itself without deadlocking
+		let connected = self.callbacks.lock().connected.clone();
+		if let Some(connected) = connected {
+			connected(&info);
+		}
+		let (message_tx, message_rx) = messenger::create(stream);
+		self.connections.lock().insert(info.id, message_tx.handle());
+		Ok(ClientConnection {
+			info,
+			message_tx,
+			message_rx,
+			callbacks: self.callbacks.clone(),
+			connections: self.connections.clone(),
+			shutdown: self.shutdown.subscribe(),
+		})
+	}
+
+	/// Send the same signal to `node_id` on every connected client, such as to tell them all about a theme change.
+	/// Returns how many clients it was queued for.
+	///
+	/// This doesn't go through any scenegraph, so the node ID must mean the same thing on every client (like the root, `0`).
+	/// Clients whose connection has already broken are skipped and forgotten about.
+	pub fn broadcast_signal(&self, node_id: u64, opcode: u64, data: &[u8]) -> usize {
+		let mut connections = self.connections.lock();
+		connections.retain(
+			|id, handle| match handle.signal(node_id, opcode, data, Vec::new()) {
+				Ok(()) => true,
+				Err(e) => {
+					tracing::warn!("Skipping client {id} in broadcast: {e}");
+					false
+				}
+			},
+		);
+		connections.len()
+	}
+
+	/// Ask every connected client to save its state, then disconnect them all.
+	///
+	/// Each client gets the root's `save_state` method called, the same as any other state save, and has `timeout` to answer before its socket is closed.
+	/// Every `ClientConnection::run` then ends with `MessengerError::ServerShutdown`, including those of clients accepted afterwards.
+	///
+	/// Returns each client's answer (a serialized `ClientState`) by client ID, leaving out clients that failed or didn't answer in time.
+	pub async fn shutdown(&self, timeout: Duration) -> FxHashMap<u64, Vec<u8>> {
+		let no_arguments = stardust_xr_schemas::flex::serialize(()).unwrap();
+		// All clients save at once, so one slow client doesn't eat into the others' time
+		let mut pending = JoinSet::new();
+		for (id, handle) in self.connections.lock().iter() {
+			match handle.method(0, SAVE_STATE_OPCODE, &no_arguments, Vec::new()) {
+				Ok(future) => {
+					let id = *id;
+					pending.spawn(async move { (id, future.await) });
+				}
+				Err(e) => tracing::warn!("Couldn't ask client {id} to save its state: {e}"),
+			}
+		}
+
+		let mut states = FxHashMap::default();
+		let _ = tokio::time::timeout(timeout, async {
+			while let Some(result) = pending.join_next().await {
+				match result {
+					Ok((id, Ok(state))) => {
+						states.insert(id, state.into_message());
+					}
+					Ok((id, Err(e))) => tracing::warn!("Client {id} failed to save its state: {e}"),
+					Err(e) => tracing::warn!("Saving a client's state panicked: {e}"),
+				}
+			}
+		})
+		.await;
+		// Whoever hasn't answered by now is left out
+		pending.abort_all();
+
+		self.shutdown.send_replace(true);
+		states
+	}
+}
+
+const SAVE_STATE_OPCODE: u64 = aspect_member_opcode("Root", "save_state");
+
+/// A client accepted by a `Server` along with the messenger halves to talk to it.
+pub struct ClientConnection {
+	pub info: ClientInfo,
+	pub message_tx: MessageSender,
+	pub message_rx: MessageReceiver,
+	callbacks: Arc<Mutex<ClientCallbacks>>,
+	connections: Connections,
+	shutdown: watch::Receiver<bool>,
+}
+impl ClientConnection {
+	/// Send and receive messages until the client disconnects, the connection breaks or the server shuts down, then notify the server's disconnect callback.
+	pub async fn run<S: Scenegraph>(mut self, scenegraph: &S) -> MessengerError {
+		let message_rx = &mut self.message_rx;
+		let message_tx = &mut self.message_tx;
+		let shutdown = &mut self.shutdown;
+		let error = tokio::select! {
+			_ = async {
+				// Dropping the server without shutting down leaves its clients connected
+				if shutdown.wait_for(|shut_down| *shut_down).await.is_err() {
+					std::future::pending::<()>().await;
+				}
+			} => MessengerError::ServerShutdown,
+			e = async {
+				loop {
+					if let Err(e) = message_rx.dispatch(scenegraph).await {
+						break e;
+					}
+				}
+			} => e,
+			e = async {
+				loop {
+					if let Err(e) = message_tx.flush().await {
+						break e;
+					}
+				}
+			} => e,
+		};
+		self.connections.lock().remove(&self.info.id);
+		let disconnected = self.callbacks.lock().disconnected.clone();
+		if let Some(disconnected) = disconnected {
+			disconnected(&self.info);
+		}
+		error
+	}
+}
+
+fn startup_token(pid: i32) -> Option<String> {
+	let environ = fs::read(format!("/proc/{pid}/environ")).ok()?;
+	environ
+		.split(|b| *b == 0)
+		.filter_map(|var| std::str::from_utf8(var).ok())
+		.find_map(|var| var.strip_prefix("STARDUST_STARTUP_TOKEN="))
+		.map(ToString::to_string)
+}
+
 #[test]
 fn server_get_free_socket_path() {
 	let socket_path = get_free_socket_path().expect("Unable to set up socket!");
 	println!("Socket is free up at {}", socket_path.display());
 }
+
+#[tokio::test]
+async fn server_client_connect_disconnect() {
+	use crate::mock::MockServer;
+
+	let socket_path =
+		std::env::temp_dir().join(format!("stardust-server-test-{}.sock", std::process::id()));
+	let _ = fs::remove_file(&socket_path);
+	let server = Server::bind(&socket_path).unwrap();
+
+	let connected = Arc::new(Mutex::new(Vec::new()));
+	let disconnected = Arc::new(Mutex::new(Vec::new()));
+	server.on_client_connected({
+		let connected = connected.clone();
+		move |info| connected.lock().push(info.id)
+	});
+	server.on_client_disconnected({
+		let disconnected = disconnected.clone();
+		move |info| disconnected.lock().push(info.id)
+	});
+
+	let (client, connection) =
+		tokio::join!(crate::client::connect_to(&socket_path), server.accept());
+	let client = client.unwrap();
+	let connection = connection.unwrap();
+	assert_eq!(*connected.lock(), vec![connection.info.id]);
+	assert_eq!(
+		connection.info.credentials.unwrap().pid,
+		Some(std::process::id() as i32)
+	);
+	assert!(disconnected.lock().is_empty());
+
+	drop(client);
+	connection.run(&*MockServer::new()).await;
+	assert_eq!(*disconnected.lock(), *connected.lock());
+	let _ = fs::remove_file(&socket_path);
+}
+
+#[tokio::test]
+async fn server_peer_credentials() {
+	let (stream, _peer) = UnixStream::pair().unwrap();
+	let credentials = peer_credentials(&stream).unwrap();
+	assert_eq!(credentials.pid, Some(std::process::id() as i32));
+}
+
+#[tokio::test]
+async fn server_broadcast_signal() {
+	use crate::mock::{MockMessage, MockServer};
+
+	let socket_path = std::env::temp_dir().join(format!(
+		"stardust-broadcast-test-{}.sock",
+		std::process::id()
+	));
+	let _ = fs::remove_file(&socket_path);
+	let server = Server::bind(&socket_path).unwrap();
+
+	let mut clients = Vec::new();
+	for i in 0..3 {
+		let (client, connection) =
+			tokio::join!(crate::client::connect_to(&socket_path), server.accept());
+		// A connection that's gone away by the time of the broadcast gets skipped
+		if i == 2 {
+			drop(connection);
+			continue;
+		}
+		let connection = connection.unwrap();
+		tokio::spawn(async move { connection.run(&*MockServer::new()).await });
+		clients.push(messenger::create(client.unwrap()));
+	}
+
+	assert_eq!(server.broadcast_signal(0, 42, b"theme"), 2);
+	for (_, client_rx) in clients.iter_mut() {
+		let scenegraph = MockServer::new();
+		client_rx.dispatch(&*scenegraph).await.unwrap();
+		assert_eq!(
+			scenegraph.signals(),
+			vec![MockMessage {
+				node: 0,
+				method: 42,
+				data: b"theme".to_vec()
+			}]
+		);
+	}
+	let _ = fs::remove_file(&socket_path);
+}
+
+#[tokio::test]
+async fn server_shutdown_saves_state() {
+	use crate::mock::MockServer;
+
+	// Stands in for the client's side, saving the same state whenever asked
+	let saving = MockServer::new();
+	saving.on_method(SAVE_STATE_OPCODE, |_node, _data| Ok(b"state".to_vec()));
+
+	let socket_path = std::env::temp_dir().join(format!(
+		"stardust-shutdown-test-{}.sock",
+		std::process::id()
+	));
+	let _ = fs::remove_file(&socket_path);
+	let server = Server::bind(&socket_path).unwrap();
+
+	let (client, connection) =
+		tokio::join!(crate::client::connect_to(&socket_path), server.accept());
+	let connection = connection.unwrap();
+	let id = connection.info.id;
+	let run = tokio::spawn(async move { connection.run(&*MockServer::new()).await });
+	let (mut client_tx, mut client_rx) = messenger::create(client.unwrap());
+	let client = tokio::spawn(async move {
+		loop {
+			tokio::select! {
+				e = client_rx.dispatch(&*saving) => if e.is_err() { break },
+				e = client_tx.flush() => if e.is_err() { break },
+			}
+		}
+	});
+
+	let states = server.shutdown(Duration::from_secs(1)).await;
+	assert_eq!(states.get(&id).map(Vec::as_slice), Some(&b"state"[..]));
+	assert!(matches!(run.await.unwrap(), MessengerError::ServerShutdown));
+	// The client sees its socket close once the state was saved
+	tokio::time::timeout(Duration::from_secs(1), client)
+		.await
+		.unwrap()
+		.unwrap();
+	let _ = fs::remove_file(&socket_path);
+}
@@ -4,7 +4,6 @@ use std::{
 	str::FromStr,
 };
 
-pub use color;
 pub use mint::{Vector2, Vector3};
 pub type Quaternion = mint::Quaternion<f32>;
 pub type Mat4 = mint::ColumnMatrix4<f32>;
@@ -12,6 +11,297 @@ pub use stardust_xr_schemas::flex::Datamap;
 pub type Color = color::Rgba<f32, color::color_space::LinearRgb>;
 pub use rustc_hash::FxHashMap as Map;
 
+/// Colors, re-exporting the `color` crate along with some common constants.
+///
+/// All constants are in linear space, so their channels are exactly what's sent over the wire as `[r, g, b, a]`.
+pub mod color {
+	pub use ::color::*;
+
+	pub const WHITE: super::Color = rgba_linear!(1.0, 1.0, 1.0, 1.0);
+	pub const BLACK: super::Color = rgba_linear!(0.0, 0.0, 0.0, 1.0);
+	pub const RED: super::Color = rgba_linear!(1.0, 0.0, 0.0, 1.0);
+	pub const GREEN: super::Color = rgba_linear!(0.0, 1.0, 0.0, 1.0);
+	pub const BLUE: super::Color = rgba_linear!(0.0, 0.0, 1.0, 1.0);
+	pub const TRANSPARENT: super::Color = rgba_linear!(0.0, 0.0, 0.0, 0.0);
+
+	/// Look up one of the basic CSS color names (case insensitive), converted from sRGB into linear space.
+	pub fn named(name: &str) -> Option<super::Color> {
+		let (r, g, b): (u8, u8, u8) = match name.to_ascii_lowercase().as_str() {
+			"transparent" => return Some(TRANSPARENT),
+			"black" => (0, 0, 0),
+			"silver" => (192, 192, 192),
+			"gray" | "grey" => (128, 128, 128),
+			"white" => (255, 255, 255),
+			"maroon" => (128, 0, 0),
+			"red" => (255, 0, 0),
+			"purple" => (128, 0, 128),
+			"fuchsia" | "magenta" => (255, 0, 255),
+			"green" => (0, 128, 0),
+			"lime" => (0, 255, 0),
+			"olive" => (128, 128, 0),
+			"yellow" => (255, 255, 0),
+			"navy" => (0, 0, 128),
+			"blue" => (0, 0, 255),
+			"teal" => (0, 128, 128),
+			"aqua" | "cyan" => (0, 255, 255),
+			"orange" => (255, 165, 0),
+			_ => return None,
+		};
+		let srgb = Rgb::<f32, color_space::Srgb>::new(
+			r as f32 / 255.0,
+			g as f32 / 255.0,
+			b as f32 / 255.0,
+		);
+		Some(AlphaColor::new(srgb.to_linear(), 1.0))
+	}
+
+	/// Blending helpers for tints and fades. Everything works in linear space with straight (not premultiplied) alpha, same as the wire format.
+	pub trait ColorBlend {
+		/// Blend each channel (alpha included) from `self` at `t = 0.0` to `other` at `t = 1.0`, the same as the color crate's `mix`.
+		fn lerp(self, other: Self, t: f32) -> Self;
+		/// This color with its alpha replaced.
+		fn with_alpha(self, alpha: f32) -> Self;
+		/// Composite this color on top of `background` ("source over").
+		fn over(self, background: Self) -> Self;
+		/// Multiply each channel (alpha included) with `other`'s, such as for tinting.
+		fn multiply(self, other: Self) -> Self;
+	}
+	impl ColorBlend for super::Color {
+		fn lerp(self, other: Self, t: f32) -> Self {
+			::color::Color::mix(self, other, t)
+		}
+		fn with_alpha(mut self, alpha: f32) -> Self {
+			self.a = alpha;
+			self
+		}
+		fn over(self, background: Self) -> Self {
+			let background_weight = background.a * (1.0 - self.a);
+			let alpha = self.a + background_weight;
+			if alpha <= 0.0 {
+				return TRANSPARENT;
+			}
+			let blend = |a: f32, b: f32| (a * self.a + b * background_weight) / alpha;
+			rgba_linear!(
+				blend(self.c.r, background.c.r),
+				blend(self.c.g, background.c.g),
+				blend(self.c.b, background.c.b),
+				alpha
+			)
+		}
+		fn multiply(self, other: Self) -> Self {
+			rgba_linear!(
+				self.c.r * other.c.r,
+				self.c.g * other.c.g,
+				self.c.b * other.c.b,
+				self.a * other.a
+			)
+		}
+	}
+}
+
+/// Make a `Vector2<f32>`, such as `vec2!(0.5, 1.0)`.
+#[macro_export]
+macro_rules! vec2 {
+	($x:expr, $y:expr $(,)?) => {
+		$crate::values::Vector2::<f32> { x: $x, y: $y }
+	};
+}
+/// Make a `Vector3<f32>`, such as `vec3!(0.0, 1.0, 0.0)`.
+#[macro_export]
+macro_rules! vec3 {
+	($x:expr, $y:expr, $z:expr $(,)?) => {
+		$crate::values::Vector3::<f32> {
+			x: $x,
+			y: $y,
+			z: $z,
+		}
+	};
+}
+/// Make a `Quaternion`, either:
+/// - `quat!(identity)` for no rotation
+/// - `quat!(from_euler x, y, z)` from euler angles in radians, see `quat_from_euler`
+/// - `quat!(x, y, z, w)` from its components
+#[macro_export]
+macro_rules! quat {
+	(identity) => {
+		$crate::values::Quaternion {
+			v: $crate::vec3!(0.0, 0.0, 0.0),
+			s: 1.0,
+		}
+	};
+	(from_euler $x:expr, $y:expr, $z:expr $(,)?) => {
+		$crate::values::quat_from_euler($x, $y, $z)
+	};
+	($x:expr, $y:expr, $z:expr, $w:expr $(,)?) => {
+		$crate::values::Quaternion {
+			v: $crate::vec3!($x, $y, $z),
+			s: $w,
+		}
+	};
+}
+pub use crate::{quat, vec2, vec3};
+
+/// Rotation from euler angles in radians, rotating around X, then Y, then Z relative to the rotated axes
+/// (the same as glam's `Quat::from_euler(EulerRot::XYZ, x, y, z)`).
+pub fn quat_from_euler(x: f32, y: f32, z: f32) -> Quaternion {
+	let (sx, cx) = (x * 0.5).sin_cos();
+	let (sy, cy) = (y * 0.5).sin_cos();
+	let (sz, cz) = (z * 0.5).sin_cos();
+	quat!(
+		sx * cy * cz + cx * sy * sz,
+		cx * sy * cz - sx * cy * sz,
+		cx * cy * sz + sx * sy * cz,
+		cx * cy * cz - sx * sy * sz,
+	)
+}
+
+/// Approximate equality for the math types, since values that went over the wire or through a few
+/// transforms rarely compare exactly equal.
+pub trait ApproxEq {
+	/// The epsilon used by [`ApproxEq::approx_eq`].
+	const DEFAULT_EPSILON: f32 = 1.0e-5;
+
+	/// Whether every component is within [`ApproxEq::DEFAULT_EPSILON`] of `other`.
+	fn approx_eq(&self, other: &Self) -> bool {
+		self.approx_eq_eps(other, Self::DEFAULT_EPSILON)
+	}
+	/// Whether every component is within `epsilon` of `other`.
+	fn approx_eq_eps(&self, other: &Self, epsilon: f32) -> bool;
+}
+fn components_approx_eq<const N: usize>(a: [f32; N], b: [f32; N], epsilon: f32) -> bool {
+	a.iter().zip(b).all(|(a, b)| (a - b).abs() <= epsilon)
+}
+impl ApproxEq for Vector2<f32> {
+	fn approx_eq_eps(&self, other: &Self, epsilon: f32) -> bool {
+		components_approx_eq((*self).into(), (*other).into(), epsilon)
+	}
+}
+impl ApproxEq for Vector3<f32> {
+	fn approx_eq_eps(&self, other: &Self, epsilon: f32) -> bool {
+		components_approx_eq((*self).into(), (*other).into(), epsilon)
+	}
+}
+/// `q` and `-q` represent the same rotation, so both are considered equal.
+impl ApproxEq for Quaternion {
+	fn approx_eq_eps(&self, other: &Self, epsilon: f32) -> bool {
+		let a: [f32; 4] = (*self).into();
+		let b: [f32; 4] = (*other).into();
+		components_approx_eq(a, b, epsilon) || components_approx_eq(a, b.map(|c| -c), epsilon)
+	}
+}
+
+/// A rigid transform: a translation and a rotation, without any scale.
+///
+/// Unlike a spatial's `Transform`, every component is always set, so poses can be composed and inverted.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pose {
+	pub translation: Vector3<f32>,
+	pub rotation: Quaternion,
+}
+impl Pose {
+	pub const fn new(translation: Vector3<f32>, rotation: Quaternion) -> Self {
+		Pose {
+			translation,
+			rotation,
+		}
+	}
+	/// No translation and no rotation.
+	pub const fn identity() -> Self {
+		Pose::new(
+			Vector3 {
+				x: 0.0,
+				y: 0.0,
+				z: 0.0,
+			},
+			Quaternion {
+				v: Vector3 {
+					x: 0.0,
+					y: 0.0,
+					z: 0.0,
+				},
+				s: 1.0,
+			},
+		)
+	}
+
+	/// Compose `other`, given relative to this pose, into the space this pose is relative to.
+	///
+	/// For a parent's pose `a` and its child's local pose `b`, `a.then(b)` is the child's pose in the parent's space.
+	pub fn then(&self, other: &Pose) -> Pose {
+		let rotated = quat_rotate(self.rotation, other.translation);
+		Pose {
+			translation: vec3!(
+				self.translation.x + rotated.x,
+				self.translation.y + rotated.y,
+				self.translation.z + rotated.z,
+			),
+			rotation: quat_mul(self.rotation, other.rotation),
+		}
+	}
+	/// The pose that undoes this one, so `pose.then(&pose.inverse())` is the identity.
+	///
+	/// The rotation is assumed to be normalized.
+	pub fn inverse(&self) -> Pose {
+		let rotation = quat_conjugate(self.rotation);
+		let translation = quat_rotate(rotation, self.translation);
+		Pose {
+			translation: vec3!(-translation.x, -translation.y, -translation.z),
+			rotation,
+		}
+	}
+	/// Transform a point from this pose's space into the space it's relative to.
+	pub fn transform_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+		let rotated = quat_rotate(self.rotation, point);
+		vec3!(
+			self.translation.x + rotated.x,
+			self.translation.y + rotated.y,
+			self.translation.z + rotated.z,
+		)
+	}
+}
+impl Default for Pose {
+	fn default() -> Self {
+		Pose::identity()
+	}
+}
+impl ApproxEq for Pose {
+	fn approx_eq_eps(&self, other: &Self, epsilon: f32) -> bool {
+		self.translation.approx_eq_eps(&other.translation, epsilon)
+			&& self.rotation.approx_eq_eps(&other.rotation, epsilon)
+	}
+}
+
+fn quat_mul(a: Quaternion, b: Quaternion) -> Quaternion {
+	quat!(
+		a.s * b.v.x + a.v.x * b.s + a.v.y * b.v.z - a.v.z * b.v.y,
+		a.s * b.v.y - a.v.x * b.v.z + a.v.y * b.s + a.v.z * b.v.x,
+		a.s * b.v.z + a.v.x * b.v.y - a.v.y * b.v.x + a.v.z * b.s,
+		a.s * b.s - a.v.x * b.v.x - a.v.y * b.v.y - a.v.z * b.v.z,
+	)
+}
+fn quat_conjugate(q: Quaternion) -> Quaternion {
+	quat!(-q.v.x, -q.v.y, -q.v.z, q.s)
+}
+fn quat_rotate(q: Quaternion, v: Vector3<f32>) -> Vector3<f32> {
+	// v + 2w(u × v) + 2u × (u × v), where u is the vector part
+	let u = q.v;
+	let cross = |a: Vector3<f32>, b: Vector3<f32>| {
+		vec3!(
+			a.y * b.z - a.z * b.y,
+			a.z * b.x - a.x * b.z,
+			a.x * b.y - a.y * b.x,
+		)
+	};
+	let t = cross(u, v);
+	let t = vec3!(t.x * 2.0, t.y * 2.0, t.z * 2.0);
+	let ut = cross(u, t);
+	vec3!(
+		v.x + q.s * t.x + ut.x,
+		v.y + q.s * t.y + ut.y,
+		v.z + q.s * t.z + ut.z,
+	)
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 /// An identifier to a resource, such as a sound or
 pub enum ResourceID {
@@ -64,6 +354,7 @@ impl ResourceID {
 		}
 	}
 }
+impl stardust_xr_schemas::Schema for ResourceID {}
 impl Serialize for ResourceID {
 	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
 		serializer.serialize_str(&self.parse())
@@ -86,3 +377,102 @@ impl<'de> Deserialize<'de> for ResourceID {
 		})
 	}
 }
+
+#[test]
+fn color_constants_linear() {
+	use color::{named, RED, WHITE};
+
+	assert_eq!([RED.c.r, RED.c.g, RED.c.b, RED.a], [1.0, 0.0, 0.0, 1.0]);
+	assert_eq!(named("Red"), Some(RED));
+	assert_eq!(named("white"), Some(WHITE));
+	assert_eq!(named("not a color"), None);
+	// CSS gray is 128/255 in sRGB, which is much darker in linear space
+	let gray = named("gray").unwrap();
+	assert!((gray.c.r - 0.2158).abs() < 0.001, "gray was {}", gray.c.r);
+}
+
+#[test]
+fn approx_eq_math_types() {
+	let q: Quaternion = [0.0, 0.70710677, 0.0, 0.70710677].into();
+	let near: Quaternion = [0.000001, 0.7071063, 0.0, 0.7071072].into();
+	let negated: Quaternion = [-0.0, -0.70710677, -0.0, -0.70710677].into();
+	let other: Quaternion = [0.0, 0.0, 0.0, 1.0].into();
+	assert!(q.approx_eq(&near));
+	assert!(q.approx_eq(&negated));
+	assert!(!q.approx_eq(&other));
+	assert!(!q.approx_eq_eps(&near, 1.0e-8));
+
+	let v: Vector3<f32> = [1.0, 2.0, 3.0].into();
+	assert!(v.approx_eq(&[1.000001, 2.0, 2.999999].into()));
+	assert!(!v.approx_eq(&[1.0, 2.0, 3.01].into()));
+	assert!(v.approx_eq_eps(&[1.0, 2.0, 3.01].into(), 0.1));
+	let w: Vector2<f32> = [0.5, -0.5].into();
+	assert!(w.approx_eq(&[0.5, -0.500001].into()));
+	assert!(!w.approx_eq(&[-0.5, 0.5].into()));
+}
+
+#[test]
+fn math_literal_macros() {
+	assert_eq!(vec2!(0.5, 1.0), Vector2 { x: 0.5, y: 1.0 });
+	assert_eq!(vec3!(0.0, 1.0, 0.0), Vector3::from([0.0, 1.0, 0.0]));
+	assert_eq!(quat!(identity), Quaternion::from([0.0, 0.0, 0.0, 1.0]));
+	assert_eq!(
+		quat!(0.0, 1.0, 0.0, 0.0),
+		Quaternion::from([0.0, 1.0, 0.0, 0.0])
+	);
+
+	assert!(quat!(from_euler 0.0, 0.0, 0.0).approx_eq(&quat!(identity)));
+	let (s, c) = (1.57_f32 * 0.5).sin_cos();
+	assert!(quat!(from_euler 0.0, 1.57, 0.0).approx_eq(&quat!(0.0, s, 0.0, c)));
+	// X then Y, each around the already rotated axes
+	let half = std::f32::consts::FRAC_1_SQRT_2;
+	let xy = quat!(from_euler std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2, 0.0);
+	assert!(xy.approx_eq(&quat!(0.5, 0.5, 0.5, 0.5)), "{xy:?}");
+	assert!(quat!(from_euler 0.0, 0.0, std::f32::consts::FRAC_PI_2)
+		.approx_eq(&quat!(0.0, 0.0, half, half)));
+}
+
+#[test]
+fn pose_compose_and_invert() {
+	let quarter = std::f32::consts::FRAC_PI_2;
+	let parent = Pose::new(vec3!(1.0, 0.0, 0.0), quat!(from_euler 0.0, quarter, 0.0));
+	let child = Pose::new(vec3!(0.0, 0.0, 1.0), quat!(from_euler 0.0, quarter, 0.0));
+
+	// a quarter turn around Y takes +Z to +X
+	let combined = parent.then(&child);
+	assert!(
+		combined.translation.approx_eq(&vec3!(2.0, 0.0, 0.0)),
+		"{combined:?}"
+	);
+	assert!(combined
+		.rotation
+		.approx_eq(&quat!(from_euler 0.0, quarter * 2.0, 0.0)));
+	assert!(combined
+		.transform_point(vec3!(0.0, 0.0, 0.0))
+		.approx_eq(&combined.translation));
+
+	assert!(parent.then(&parent.inverse()).approx_eq(&Pose::identity()));
+	assert!(parent.inverse().then(&parent).approx_eq(&Pose::identity()));
+	assert!(parent.inverse().then(&combined).approx_eq(&child));
+	assert!(Pose::identity().then(&child).approx_eq(&child));
+}
+
+#[test]
+fn color_blending() {
+	use color::{rgba_linear, ColorBlend, BLUE, RED, TRANSPARENT, WHITE};
+
+	assert_eq!(RED.lerp(BLUE, 0.0), RED);
+	assert_eq!(RED.lerp(BLUE, 1.0), BLUE);
+	assert_eq!(RED.lerp(BLUE, 0.5), rgba_linear!(0.5, 0.0, 0.5, 1.0));
+	assert_eq!(RED.with_alpha(0.25).a, 0.25);
+	assert_eq!(RED.multiply(WHITE.with_alpha(0.5)), RED.with_alpha(0.5));
+
+	// a 50% red overlay on opaque blue
+	assert_eq!(
+		RED.with_alpha(0.5).over(BLUE),
+		rgba_linear!(0.5, 0.0, 0.5, 1.0)
+	);
+	// nothing underneath keeps the overlay as is
+	assert_eq!(RED.with_alpha(0.5).over(TRANSPARENT), RED.with_alpha(0.5));
+	assert_eq!(TRANSPARENT.over(TRANSPARENT), TRANSPARENT);
+}
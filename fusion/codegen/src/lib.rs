@@ -52,9 +52,53 @@ pub fn codegen_item_camera_protocol(_input: proc_macro::TokenStream) -> proc_mac
 pub fn codegen_item_panel_protocol(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 	codegen_client_protocol(ITEM_PANEL_PROTOCOL, true)
 }
+/// Generate the client side of a protocol given as a KDL string literal, for protocols that aren't built into fusion.
+#[proc_macro]
+pub fn codegen_protocol(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let protocol = syn::parse_macro_input!(input as syn::LitStr).value();
+	codegen_client_protocol(&protocol, true)
+}
 
-fn codegen_client_protocol(protocol: &'static str, generate_node: bool) -> proc_macro::TokenStream {
+const ALL_PROTOCOLS: &[&str] = &[
+	ROOT_PROTOCOL,
+	NODE_PROTOCOL,
+	SPATIAL_PROTOCOL,
+	FIELD_PROTOCOL,
+	DATA_PROTOCOL,
+	AUDIO_PROTOCOL,
+	DRAWABLE_PROTOCOL,
+	INPUT_PROTOCOL,
+	ITEM_PROTOCOL,
+	ITEM_CAMERA_PROTOCOL,
+	ITEM_PANEL_PROTOCOL,
+];
+fn all_aspects() -> Vec<Aspect> {
+	ALL_PROTOCOLS
+		.iter()
+		.flat_map(|p| Protocol::parse(p).unwrap().aspects)
+		.collect()
+}
+/// Every aspect `aspect_name` inherits, directly or through other aspects, across all protocols.
+fn resolve_inherits(all_aspects: &[Aspect], aspect_name: &str) -> Vec<String> {
+	let mut inherits: Vec<String> = Vec::new();
+	let mut to_resolve = vec![aspect_name.to_string()];
+	while let Some(name) = to_resolve.pop() {
+		let Some(aspect) = all_aspects.iter().find(|a| a.name == name) else {
+			continue;
+		};
+		for inherited in &aspect.inherits {
+			if !inherits.contains(inherited) {
+				inherits.push(inherited.clone());
+				to_resolve.push(inherited.clone());
+			}
+		}
+	}
+	inherits
+}
+
+fn codegen_client_protocol(protocol: &str, generate_node: bool) -> proc_macro::TokenStream {
 	let protocol = Protocol::parse(protocol).unwrap();
+	let all_aspects = all_aspects();
 	let protocol_version = protocol.version;
 	let protocol_version = quote!(pub(crate) const INTERFACE_VERSION: u32 = #protocol_version;);
 	let interface_node_id = protocol
@@ -86,7 +130,7 @@ fn codegen_client_protocol(protocol: &'static str, generate_node: bool) -> proc_
 	let aspects = protocol
 		.aspects
 		.iter()
-		.map(|a| generate_aspect(a, generate_node))
+		.map(|a| generate_aspect(a, &all_aspects, generate_node))
 		.reduce(fold_tokens)
 		.unwrap_or_default();
 	let interface = protocol
@@ -107,10 +151,18 @@ fn codegen_client_protocol(protocol: &'static str, generate_node: bool) -> proc_
 					);
 					let opcode = m.opcode;
 
-					let member = generate_member(Some(p.node_id), m);
+					let member = generate_member(Some(p.node_id), m, false);
+					let builder = if m.side == Side::Server
+						&& m.arguments.len() >= BUILDER_ARGUMENT_THRESHOLD
+					{
+						generate_builder(m)
+					} else {
+						TokenStream::default()
+					};
 					quote! {
 						pub(crate) const #name: u64 = #opcode;
 						#member
+						#builder
 					}
 				})
 				.reduce(fold_tokens)
@@ -136,6 +188,7 @@ fn generate_custom_enum(custom_enum: &CustomEnum) -> TokenStream {
 		#[derive(Debug, Clone, Copy, Hash, PartialEq, serde_repr::Deserialize_repr, serde_repr::Serialize_repr)]
 		#[repr(u32)]
 		pub enum #name {#argument_decls}
+		impl stardust_xr::schemas::Schema for #name {}
 	}
 }
 fn generate_custom_union(custom_union: &CustomUnion) -> TokenStream {
@@ -154,6 +207,7 @@ fn generate_custom_union(custom_union: &CustomUnion) -> TokenStream {
 		#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 		#[serde(untagged)]
 		pub enum #name {#option_decls}
+		impl stardust_xr::schemas::Schema for #name {}
 	}
 }
 fn generate_union_option(union_option: &UnionOption) -> TokenStream {
@@ -185,6 +239,7 @@ fn argument_type_option_name(argument_type: &ArgumentType) -> String {
 		ArgumentType::Color => "Color".to_string(),
 		ArgumentType::String => "String".to_string(),
 		ArgumentType::Bytes => "Bytes".to_string(),
+		ArgumentType::Fd => "Fd".to_string(),
 		ArgumentType::Vec(v) => format!("{}Vector", argument_type_option_name(v)),
 		ArgumentType::Map(m) => format!("{}Map", argument_type_option_name(m)),
 		ArgumentType::NodeID => "Node ID".to_string(),
@@ -212,10 +267,11 @@ fn generate_custom_struct(custom_struct: &CustomStruct) -> TokenStream {
 		#[doc = #description]
 		#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 		pub struct #name {#argument_decls}
+		impl stardust_xr::schemas::Schema for #name {}
 	}
 }
 
-fn generate_aspect(aspect: &Aspect, generate_node: bool) -> TokenStream {
+fn generate_aspect(aspect: &Aspect, all_aspects: &[Aspect], generate_node: bool) -> TokenStream {
 	let node_name = Ident::new(&aspect.name, Span::call_site());
 	let description = &aspect.description;
 	let (client_members, server_members) = aspect.members.iter().split(|m| m.side == Side::Server);
@@ -250,15 +306,65 @@ fn generate_aspect(aspect: &Aspect, generate_node: bool) -> TokenStream {
 		})
 		.reduce(fold_tokens)
 		.unwrap_or_default();
+	let members_table_name = Ident::new(
+		&format!("{}_MEMBERS", aspect.name.to_case(Case::ScreamingSnake)),
+		Span::call_site(),
+	);
+	let members_table = aspect
+		.members
+		.iter()
+		.map(|m| {
+			let opcode = m.opcode;
+			let name = &m.name;
+			let side = match m.side {
+				Side::Client => quote!(Client),
+				Side::Server => quote!(Server),
+			};
+			let _type = match m._type {
+				MemberType::Signal => quote!(Signal),
+				MemberType::Method => quote!(Method),
+			};
+			quote! {
+				(
+					#opcode,
+					#name,
+					stardust_xr::schemas::protocol::Side::#side,
+					stardust_xr::schemas::protocol::MemberType::#_type,
+				),
+			}
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+	let aspect_id_name = Ident::new(
+		&format!("{}_ASPECT_ID", aspect.name.to_case(Case::ScreamingSnake)),
+		Span::call_site(),
+	);
+	let aspect_id = aspect.id;
+	let opcodes = quote! {
+		#opcodes
+		/// The ID of this aspect, as listed by `get_aspects`.
+		pub const #aspect_id_name: u64 = #aspect_id;
+		/// Every member of this aspect as `(opcode, name, side, type)`, for fuzzers and inspectors to enumerate valid messages.
+		#[allow(dead_code)]
+		pub(crate) const #members_table_name: &[(
+			u64,
+			&str,
+			stardust_xr::schemas::protocol::Side,
+			stardust_xr::schemas::protocol::MemberType,
+		)] = &[#members_table];
+	};
 
 	let client_side = client_members
-		.map(|m| generate_member(None, m))
+		.map(|m| generate_member(None, m, false))
 		.reduce(fold_tokens)
 		.map(|t| {
 			quote! {
 				#[doc = #description]
 				pub trait #aspect_handler_name: Send + Sync + 'static {
 					#t
+					/// Called for signals from the server this client doesn't know about, such as ones added in a newer protocol version.
+					/// Lets tooling pass through unrecognized signals instead of them erroring.
+					fn unknown_signal(&mut self, _opcode: u64, _data: Vec<u8>) {}
 				}
 			}
 		})
@@ -282,20 +388,87 @@ fn generate_aspect(aspect: &Aspect, generate_node: bool) -> TokenStream {
 				}
 				fn add_handlers<N: crate::node::NodeType, H: #aspect_handler_name>(handler_wrapper: &crate::HandlerWrapper<N, H>) -> NodeResult<()> {
 					#handlers
+					handler_wrapper.add_unknown_signal_handler(|_handler, _opcode, _data| {
+						<H as #aspect_handler_name>::unknown_signal(&mut *_handler.lock(), _opcode, _data.to_vec());
+						Ok(())
+					})?;
 					Ok(())
 				}
 			}
 		}).unwrap_or_default();
 
+	let subscribe_method_name = Ident::new(
+		&format!("subscribe_{}_events", aspect.name.to_case(Case::Snake)),
+		Span::call_site(),
+	);
+	let aspect_subscribe = aspect
+		.members
+		.iter()
+		.filter(|m| m.side == Side::Client && m._type == MemberType::Signal)
+		.map(generate_subscriber)
+		.reduce(fold_tokens)
+		.map(|subscribers| {
+			quote! {
+				/// Also send this aspect's signals to `handler`, alongside the handler given to `wrap`.
+				/// Methods are only ever answered by the wrapped handler. Stops once `handler` is dropped.
+				fn #subscribe_method_name<H: #aspect_handler_name>(&self, handler: &std::sync::Arc<parking_lot::Mutex<H>>) -> NodeResult<()> where Self: Sized {
+					#subscribers
+					Ok(())
+				}
+			}
+		})
+		.unwrap_or_default();
+
 	let inherit_types = aspect
 		.inherits
 		.iter()
 		.map(|m| Ident::new(&format!("{m}Aspect"), Span::call_site()))
 		.fold(quote!(crate::node::NodeType), |a, b| quote!(#a + #b));
+	// Only the client that created a node may use these, so catch it here instead of the server rejecting it
+	let owner_only = aspect.name == "Owned";
 	let server_side_members = server_members
-		.map(|m| generate_member(None, m))
+		.map(|m| generate_member(None, m, owner_only))
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+	// Makes a missing aspect in the inheritance chain show up as an error naming that aspect,
+	// instead of a confusing unsatisfied trait bound on `impl #aspect_trait_name for #node_name`.
+	let inherit_assertions = resolve_inherits(all_aspects, &aspect.name)
+		.into_iter()
+		.map(|inherited| {
+			let inherited_trait = Ident::new(&format!("{inherited}Aspect"), Span::call_site());
+			let assertion = Ident::new(
+				&format!(
+					"{}_must_implement_{}_aspect",
+					aspect.name.to_case(Case::Snake),
+					inherited.to_case(Case::Snake)
+				),
+				Span::call_site(),
+			);
+			quote! {
+				fn #assertion<N: #inherited_trait>() {}
+				#assertion::<#node_name>();
+			}
+		})
 		.reduce(fold_tokens)
 		.unwrap_or_default();
+	// Every node with this aspect (through any inheritance chain) can be borrowed as the aspect's own node type
+	let aspect_conversion = if generate_node {
+		let conversion_name = Ident::new(
+			&format!("as_{}", aspect.name.to_case(Case::Snake)),
+			Span::call_site(),
+		);
+		let conversion_description = format!(
+			"Get this node as a [`{node_name}`] without consuming it. It's an alias, so it stops working once the original node is dropped."
+		);
+		quote! {
+			#[doc = #conversion_description]
+			fn #conversion_name(&self) -> #node_name {
+				#node_name(crate::node::NodeType::alias(self.node()))
+			}
+		}
+	} else {
+		TokenStream::default()
+	};
 	let node = generate_node
 		.then_some(quote! {
 			#[doc = #description]
@@ -318,7 +491,11 @@ fn generate_aspect(aspect: &Aspect, generate_node: bool) -> TokenStream {
 					serializer.serialize_u64(node_id)
 				}
 			}
+			impl stardust_xr::schemas::Schema for #node_name {}
 			impl #aspect_trait_name for #node_name {}
+			const _: fn() = || {
+				#inherit_assertions
+			};
 		})
 		.unwrap_or_default();
 	quote! {
@@ -328,18 +505,24 @@ fn generate_aspect(aspect: &Aspect, generate_node: bool) -> TokenStream {
 		#[doc = #description]
 		pub trait #aspect_trait_name: #inherit_types {
 			#aspect_wrap
+			#aspect_subscribe
+			#aspect_conversion
 			#server_side_members
 		}
 	}
 }
 
-fn generate_member(interface_node_id: Option<u64>, member: &Member) -> TokenStream {
+fn generate_member(
+	interface_node_id: Option<u64>,
+	member: &Member,
+	owner_only: bool,
+) -> TokenStream {
 	let opcode = member.opcode;
 	let name = Ident::new(&member.name.to_case(Case::Snake), Span::call_site());
 	let description = &member.description;
 
 	let side = member.side;
-	let _type = member._type;
+	let _type = member.wire_type();
 
 	let first_arg = if interface_node_id.is_some() {
 		quote!(_client: &std::sync::Arc<crate::client::Client>)
@@ -360,36 +543,85 @@ fn generate_member(interface_node_id: Option<u64>, member: &Member) -> TokenStre
 		.map(|a| generate_argument_serialize(&a.name, &a._type, a.optional))
 		.reduce(|a, b| quote!(#a, #b))
 		.unwrap_or_default();
-	let return_type = member
+	let has_fds = member.arguments.iter().any(|a| contains_fd(&a._type));
+	let fds = if has_fds {
+		quote!(_fds)
+	} else {
+		quote!(Vec::new())
+	};
+	let fds_decl = if has_fds {
+		quote!(let mut _fds = Vec::new();)
+	} else {
+		TokenStream::default()
+	};
+	let mut return_type = member
 		.return_type
 		.as_ref()
 		.map(|r| generate_argument_type(r, true))
 		.unwrap_or_else(|| quote!(()));
+	if member.return_optional {
+		return_type = quote!(Option<#return_type>);
+	}
+	let schema_types = member
+		.arguments
+		.iter()
+		.map(|a| generate_schema_type(&a._type, a.optional));
+	let mut schema_assertion =
+		quote!(stardust_xr::schemas::assert_schema::<(#(#schema_types,)*)>(););
+	if owner_only {
+		schema_assertion = quote! {
+			#schema_assertion
+			if !self.node().is_owned()? {
+				return Err(crate::node::NodeError::NotOwned);
+			}
+		};
+	}
 
 	match (side, _type) {
 		(Side::Server, MemberType::Method) => {
+			let argument_type = member.return_type.clone().unwrap_or(ArgumentType::Empty);
+			let mut deserializeable_type = generate_argument_type(
+				&convert_deserializeable_argument_type(&argument_type),
+				true,
+			);
+			if member.return_optional {
+				deserializeable_type = quote!(Option<#deserializeable_type>);
+			}
+			let deserialize =
+				generate_argument_deserialize("result", &argument_type, member.return_optional);
 			let body = if let Some(interface_node_id) = &interface_node_id {
-				let argument_type = member.return_type.clone().unwrap_or(ArgumentType::Empty);
-				let deserializeable_type = generate_argument_type(
-					&convert_deserializeable_argument_type(&argument_type),
-					true,
-				);
-				let deserialize = generate_argument_deserialize("result", &argument_type, false);
 				quote! {
+					#fds_decl
 					let data = stardust_xr::schemas::flex::serialize(&(#argument_uses))?;
-					let message = _client.message_sender_handle.method(#interface_node_id, #opcode, &data, Vec::new())?.await?.into_message();
+					let message = _client.message_sender_handle.method_typed(#interface_node_id, #opcode, &data, #fds)?.await?.into_message();
 					let result: #deserializeable_type = stardust_xr::schemas::flex::deserialize(&message)?;
+					let result = #deserialize;
+					Ok(result)
+				}
+			} else if has_fds {
+				quote! {
+					#fds_decl
+					let data = stardust_xr::schemas::flex::serialize(&(#argument_uses))?;
+					let message = self.node().execute_remote_method_raw(#opcode, &data, _fds)?.await?.into_message();
+					Ok(stardust_xr::schemas::flex::deserialize(&message)?)
+				}
+			} else if contains_node(&argument_type) {
+				// Returned nodes come as IDs and are turned into unowned nodes
+				quote! {
+					let result: #deserializeable_type = self.node().execute_remote_method_checked(#opcode, &(#argument_uses)).await?;
+					let _client = self.node().client()?;
 					Ok(#deserialize)
 				}
 			} else {
 				quote! {
-					self.node().execute_remote_method(#opcode, &(#argument_uses)).await
+					self.node().execute_remote_method_checked(#opcode, &(#argument_uses)).await
 				}
 			};
 			if interface_node_id.is_some() {
 				return quote! {
 					#[doc = #description]
 					pub async fn #name(#argument_decls) -> crate::node::NodeResult<#return_type> {
+						#schema_assertion
 						#body
 					}
 				};
@@ -397,6 +629,7 @@ fn generate_member(interface_node_id: Option<u64>, member: &Member) -> TokenStre
 			quote! {
 				#[doc = #description]
 				async fn #name(#argument_decls) -> crate::node::NodeResult<#return_type> {
+					#schema_assertion
 					#body
 				}
 			}
@@ -404,31 +637,72 @@ fn generate_member(interface_node_id: Option<u64>, member: &Member) -> TokenStre
 		(Side::Server, MemberType::Signal) => {
 			let mut body = if let Some(interface_node_id) = &interface_node_id {
 				quote! {
-					_client.message_sender_handle.signal(#interface_node_id, #opcode, &stardust_xr::schemas::flex::serialize(&(#argument_uses))?, Vec::new())
+					({
+						#fds_decl
+						_client.message_sender_handle.signal(#interface_node_id, #opcode, &stardust_xr::schemas::flex::serialize(&(#argument_uses))?, #fds)
+					})
+				}
+			} else if has_fds {
+				quote! {
+					({
+						#fds_decl
+						let data = stardust_xr::schemas::flex::serialize(&(#argument_uses))?;
+						self.node().send_remote_signal_raw(#opcode, &data, _fds)
+					})
 				}
 			} else {
 				quote! {
-					self.node().send_remote_signal(#opcode, &(#argument_uses))
+					self.node().send_remote_signal_checked(#opcode, &(#argument_uses))
 				}
 			};
-			body = if let Some(ArgumentType::Node {
-				_type: _,
-				return_id_parameter_name: Some(return_id_parameter_name),
-			}) = &member.return_type
-			{
-				let id_argument = Ident::new(return_id_parameter_name, Span::call_site());
-				let get_client = if interface_node_id.is_some() {
-					quote!(_client)
-				} else {
-					quote!(self.node().client()?)
-				};
+			let get_client = if interface_node_id.is_some() {
+				quote!(_client)
+			} else {
+				quote!(self.node().client()?)
+			};
+			let return_id_argument = match &member.return_type {
+				Some(ArgumentType::Node {
+					_type: _,
+					return_id_parameter_name: Some(return_id_parameter_name),
+				}) => Some(Ident::new(return_id_parameter_name, Span::call_site())),
+				_ => None,
+			};
+			// Spatial parents are tracked so reparenting under a descendant fails right away
+			let spatial_parent = member
+				.arguments
+				.iter()
+				.find(|a| a.spatial_parent)
+				.map(|a| Ident::new(&a.name.to_case(Case::Snake), Span::call_site()));
+			let spatial_child = match (&return_id_argument, &interface_node_id) {
+				(Some(id_argument), _) => Some(quote!(#id_argument)),
+				(None, None) => Some(quote!(self.node().get_id()?)),
+				(None, Some(_)) => None,
+			};
+			let (check_parent, record_parent) = match spatial_child.zip(spatial_parent) {
+				Some((spatial_child, spatial_parent)) => (
+					quote! {
+						let _spatial_child = #spatial_child;
+						let _spatial_parent = #spatial_parent.node().get_id()?;
+						let _scenegraph = #get_client.scenegraph.clone();
+						_scenegraph.check_spatial_parent(_spatial_child, _spatial_parent)?;
+					},
+					quote!(_scenegraph.set_spatial_parent(_spatial_child, _spatial_parent);),
+				),
+				None => Default::default(),
+			};
+			body = if let Some(id_argument) = return_id_argument {
 				quote! {
+					#check_parent
 					#body?;
+					#record_parent
 					Ok(<#return_type as crate::node::NodeType>::from_id(&#get_client, #id_argument, true))
 				}
 			} else {
 				quote! {
-					Ok(#body?)
+					#check_parent
+					#body?;
+					#record_parent
+					Ok(())
 				}
 			};
 			if interface_node_id.is_some() {
@@ -437,6 +711,7 @@ fn generate_member(interface_node_id: Option<u64>, member: &Member) -> TokenStre
 					return quote! {
 						#[doc = #description]
 						pub fn #name(#argument_decls) -> crate::node::NodeResult<#return_type> {
+							#schema_assertion
 							#body
 						}
 					};
@@ -445,6 +720,7 @@ fn generate_member(interface_node_id: Option<u64>, member: &Member) -> TokenStre
 			quote! {
 				#[doc = #description]
 				fn #name(#argument_decls) -> crate::node::NodeResult<#return_type> {
+					#schema_assertion
 					#body
 				}
 			}
@@ -463,11 +739,239 @@ fn generate_member(interface_node_id: Option<u64>, member: &Member) -> TokenStre
 		}
 	}
 }
+/// Interface functions with at least this many arguments also get a builder, since long positional argument lists are easy to mix up.
+const BUILDER_ARGUMENT_THRESHOLD: usize = 5;
+fn generate_builder(member: &Member) -> TokenStream {
+	let function_name = Ident::new(&member.name.to_case(Case::Snake), Span::call_site());
+	let builder_name = Ident::new(
+		&format!("{}Builder", member.name.to_case(Case::Pascal)),
+		Span::call_site(),
+	);
+	let description = format!(
+		"Builder for [`{function_name}`], required arguments not passed to `new` are checked when building."
+	);
+	let return_id_argument = match &member.return_type {
+		Some(ArgumentType::Node {
+			return_id_parameter_name: Some(id_argument),
+			..
+		}) => Some(id_argument.as_str()),
+		_ => None,
+	};
+	let (node_arguments, value_arguments) = member
+		.arguments
+		.iter()
+		.filter(|a| Some(a.name.as_str()) != return_id_argument)
+		.split(|a| !matches!(a._type, ArgumentType::Node { .. }));
+	let node_arguments = node_arguments.collect::<Vec<_>>();
+	let value_arguments = value_arguments.collect::<Vec<_>>();
+	let lifetime = (!node_arguments.is_empty()).then(|| quote!('a,));
+
+	let node_generic = |argument: &Argument| {
+		Ident::new(
+			&format!("{}Node", argument.name.to_case(Case::Pascal)),
+			Span::call_site(),
+		)
+	};
+	let generic_decls = node_arguments
+		.iter()
+		.map(|a| {
+			let generic = node_generic(a);
+			let ArgumentType::Node { _type, .. } = &a._type else {
+				unreachable!()
+			};
+			let aspect = Ident::new(
+				&format!("{}Aspect", _type.to_case(Case::Pascal)),
+				Span::call_site(),
+			);
+			quote!(#generic: #aspect,)
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+	let generics = node_arguments
+		.iter()
+		.map(|a| {
+			let generic = node_generic(a);
+			quote!(#generic,)
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+
+	let node_fields = node_arguments
+		.iter()
+		.map(|a| {
+			let name = generate_argument_name(a);
+			let generic = node_generic(a);
+			if a.optional {
+				quote!(#name: Option<&'a #generic>,)
+			} else {
+				quote!(#name: &'a #generic,)
+			}
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+	let node_names = node_arguments
+		.iter()
+		.map(|a| generate_argument_name(a))
+		.fold(TokenStream::default(), |a, b| quote!(#a #b,));
+	let value_fields = value_arguments
+		.iter()
+		.map(|a| {
+			let name = generate_argument_name(a);
+			let _type = generate_argument_type(&a._type, true);
+			quote!(#name: Option<#_type>,)
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+	let value_inits = value_arguments
+		.iter()
+		.map(|a| {
+			let name = generate_argument_name(a);
+			quote!(#name: None,)
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+	let setters = value_arguments
+		.iter()
+		.map(|a| {
+			let name = generate_argument_name(a);
+			let _type = generate_argument_type(&a._type, true);
+			let description = a
+				.description
+				.as_ref()
+				.map(|d| quote!(#[doc = #d]))
+				.unwrap_or_default();
+			quote! {
+				#description
+				pub fn #name(mut self, #name: impl Into<#_type>) -> Self {
+					self.#name = Some(#name.into());
+					self
+				}
+			}
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+
+	let value_unwraps = value_arguments
+		.iter()
+		.map(|a| {
+			let name = generate_argument_name(a);
+			let name_string = a.name.to_case(Case::Snake);
+			if a.optional {
+				quote!(let #name = self.#name;)
+			} else {
+				quote! {
+					let #name = self.#name.ok_or(crate::node::NodeError::MissingArgument { name: #name_string })?;
+				}
+			}
+		})
+		.reduce(fold_tokens)
+		.unwrap_or_default();
+	let argument_uses = member
+		.arguments
+		.iter()
+		.map(|a| {
+			let name = generate_argument_name(a);
+			if Some(a.name.as_str()) == return_id_argument {
+				return quote!(_client.generate_id());
+			}
+			match (&a._type, a.optional) {
+				(ArgumentType::Node { .. }, _) => quote!(self.#name),
+				(t, _) if contains_fd(t) => quote!(#name),
+				(ArgumentType::String | ArgumentType::Bytes | ArgumentType::Vec(_), true) => {
+					quote!(#name.as_deref())
+				}
+				(ArgumentType::Map(_) | ArgumentType::Datamap | ArgumentType::ResourceID, true) => {
+					quote!(#name.as_ref())
+				}
+				(
+					ArgumentType::String
+					| ArgumentType::Bytes
+					| ArgumentType::Vec(_)
+					| ArgumentType::Map(_)
+					| ArgumentType::Datamap
+					| ArgumentType::ResourceID,
+					false,
+				) => quote!(&#name),
+				_ => quote!(#name),
+			}
+		})
+		.fold(quote!(_client), |a, b| quote!(#a, #b));
+
+	let mut return_type = member
+		.return_type
+		.as_ref()
+		.map(|r| generate_argument_type(r, true))
+		.unwrap_or_else(|| quote!(()));
+	if member.return_optional {
+		return_type = quote!(Option<#return_type>);
+	}
+	let build = match member.wire_type() {
+		MemberType::Signal => quote! {
+			pub fn build(self, _client: &std::sync::Arc<crate::client::Client>) -> crate::node::NodeResult<#return_type> {
+				#value_unwraps
+				#function_name(#argument_uses)
+			}
+		},
+		MemberType::Method => quote! {
+			pub async fn build(self, _client: &std::sync::Arc<crate::client::Client>) -> crate::node::NodeResult<#return_type> {
+				#value_unwraps
+				#function_name(#argument_uses).await
+			}
+		},
+	};
+
+	quote! {
+		#[doc = #description]
+		pub struct #builder_name<#lifetime #generic_decls> {
+			#node_fields
+			#value_fields
+		}
+		impl<#lifetime #generic_decls> #builder_name<#lifetime #generics> {
+			pub fn new(#node_fields) -> Self {
+				#builder_name {
+					#node_names
+					#value_inits
+				}
+			}
+			#setters
+			#build
+		}
+	}
+}
 fn generate_handler(member: &Member) -> TokenStream {
-	let name = &member.name;
 	let opcode = member.opcode;
-	let name_ident = Ident::new(name, Span::call_site());
-
+	let name_ident = Ident::new(&member.name, Span::call_site());
+	let (deserialize, argument_uses) = generate_handler_arguments(member);
+	match member._type {
+		MemberType::Signal => {
+			let parse = generate_signal_parse(member);
+			quote! {
+				handler_wrapper.add_handled_signal(#opcode, #parse)?;
+			}
+		}
+		MemberType::Method => {
+			let serialize = generate_argument_serialize(
+				"value",
+				member.return_type.as_ref().unwrap(),
+				member.return_optional,
+			);
+			let schema_type =
+				generate_schema_type(member.return_type.as_ref().unwrap(), member.return_optional);
+			quote! {
+				handler_wrapper.add_handled_method(#opcode, |_node, _handler, _data, _fds| {
+					#deserialize
+					let _client = _node.client()?;
+					let mut _handler_lock = _handler.lock();
+					let value = H::#name_ident(&mut *_handler_lock #argument_uses)?;
+					stardust_xr::schemas::assert_schema::<#schema_type>();
+					let data = stardust_xr::schemas::flex::serialize(&(#serialize))?;
+					Ok((data, vec![]))
+				})?;
+			}
+		}
+	}
+}
+fn generate_handler_arguments(member: &Member) -> (TokenStream, TokenStream) {
 	let argument_names = member
 		.arguments
 		.iter()
@@ -481,40 +985,43 @@ fn generate_handler(member: &Member) -> TokenStream {
 		.map(|a| generate_argument_type(&a, true))
 		.reduce(|a, b| quote!(#a, #b));
 	// dbg!(&argument_types);
-	let deserialize = argument_names
+	let mut deserialize = argument_names
 		.clone()
 		.zip(argument_types)
 		.map(|(argument_names, argument_types)| {
 			quote!(let (#argument_names): (#argument_types) = stardust_xr::schemas::flex::deserialize(_data)?;)
 		})
 		.unwrap_or_default();
+	if member.arguments.iter().any(|a| contains_fd(&a._type)) {
+		// Taken out by index as the arguments refer to them
+		deserialize = quote! {
+			#deserialize
+			let mut _fds = _fds.into_iter().map(Some).collect::<Vec<_>>();
+		};
+	}
 	let argument_uses = member
 		.arguments
 		.iter()
 		.map(|a| generate_argument_deserialize(&a.name, &a._type, a.optional))
 		.fold(TokenStream::default(), |a, b| quote!(#a, #b));
-	match member._type {
-		MemberType::Signal => quote! {
-			handler_wrapper.add_handled_signal(#opcode, |_node, _handler, _data, _fds| {
-				#deserialize
-				let _client = _node.client()?;
-				let mut _handler_lock = _handler.lock();
-				Ok(H::#name_ident(&mut *_handler_lock #argument_uses))
-			})?;
-		},
-		MemberType::Method => {
-			let serialize =
-				generate_argument_serialize("value", member.return_type.as_ref().unwrap(), false);
-			quote! {
-				handler_wrapper.add_handled_method(#opcode, |_node, _handler, _data, _fds| {
-					#deserialize
-					let _client = _node.client()?;
-					let mut _handler_lock = _handler.lock();
-					let value = H::#name_ident(&mut *_handler_lock #argument_uses)?;
-					let data = stardust_xr::schemas::flex::serialize(&(#serialize))?;
-					Ok((data, vec![]))
-				})?;
-			}
+	(deserialize, argument_uses)
+}
+fn generate_subscriber(member: &Member) -> TokenStream {
+	let opcode = member.opcode;
+	let parse = generate_signal_parse(member);
+	quote! {
+		crate::subscribe_handled_signal(self, handler, #opcode, #parse)?;
+	}
+}
+fn generate_signal_parse(member: &Member) -> TokenStream {
+	let name_ident = Ident::new(&member.name, Span::call_site());
+	let (deserialize, argument_uses) = generate_handler_arguments(member);
+	quote! {
+		|_node, _handler, _data, _fds| {
+			#deserialize
+			let _client = _node.client()?;
+			let mut _handler_lock = _handler.lock();
+			Ok(H::#name_ident(&mut *_handler_lock #argument_uses))
 		}
 	}
 }
@@ -538,7 +1045,16 @@ fn generate_argument_deserialize(
 				false => quote!(#node_type::from_id(&_client, #name, false)),
 			}
 		}
+		ArgumentType::Color | ArgumentType::Fd | ArgumentType::Vec(_) | ArgumentType::Map(_)
+			if optional =>
+		{
+			let mapping = generate_argument_deserialize("o", argument_type, false);
+			quote!(#name.map(|o| Ok::<_, crate::node::NodeError>(#mapping)).transpose()?)
+		}
 		ArgumentType::Color => quote!(color::rgba_linear!(#name[0], #name[1], #name[2], #name[3])),
+		ArgumentType::Fd => quote! {
+			_fds.get_mut(#name as usize).and_then(Option::take).ok_or(crate::node::NodeError::InvalidFd)?
+		},
 		ArgumentType::Vec(v) => {
 			let mapping = generate_argument_deserialize("a", v, false);
 			quote!(#name.into_iter().map(|a| Ok(#mapping)).collect::<Result<Vec<_>, crate::node::NodeError>>()?)
@@ -553,6 +1069,7 @@ fn generate_argument_deserialize(
 fn convert_deserializeable_argument_type(argument_type: &ArgumentType) -> ArgumentType {
 	match argument_type {
 		ArgumentType::Node { .. } => ArgumentType::NodeID,
+		ArgumentType::Fd => ArgumentType::UInt,
 		ArgumentType::Vec(v) => {
 			ArgumentType::Vec(Box::new(convert_deserializeable_argument_type(v)))
 		}
@@ -563,6 +1080,20 @@ fn convert_deserializeable_argument_type(argument_type: &ArgumentType) -> Argume
 	}
 }
 
+fn contains_node(argument_type: &ArgumentType) -> bool {
+	match argument_type {
+		ArgumentType::Node { .. } => true,
+		ArgumentType::Vec(v) | ArgumentType::Map(v) => contains_node(v),
+		_ => false,
+	}
+}
+fn contains_fd(argument_type: &ArgumentType) -> bool {
+	match argument_type {
+		ArgumentType::Fd => true,
+		ArgumentType::Vec(v) | ArgumentType::Map(v) => contains_fd(v),
+		_ => false,
+	}
+}
 fn generate_argument_serialize(
 	argument_name: &str,
 	argument_type: &ArgumentType,
@@ -594,6 +1125,18 @@ fn generate_argument_serialize(
 			quote!(#name.into())
 		}
 		ArgumentType::Color => quote!([#name.c.r, #name.c.g, #name.c.b, #name.a]),
+		ArgumentType::Fd => quote!({
+			_fds.push(#name);
+			(_fds.len() - 1) as u32
+		}),
+		ArgumentType::Vec(v) if contains_fd(v) => {
+			let mapping = generate_argument_serialize("a", v, false);
+			quote!(#name.into_iter().map(|a| Ok(#mapping)).collect::<crate::node::NodeResult<Vec<_>>>()?)
+		}
+		ArgumentType::Map(v) if contains_fd(v) => {
+			let mapping = generate_argument_serialize("a", v, false);
+			quote!(#name.into_iter().map(|(k, a)| Ok((k, #mapping))).collect::<crate::node::NodeResult<rustc_hash::FxHashMap<String, _>>>()?)
+		}
 		ArgumentType::Vec(v) => {
 			let mapping = generate_argument_serialize("a", v, false);
 			quote!(#name.iter().map(|a| Ok(#mapping)).collect::<crate::node::NodeResult<Vec<_>>>()?)
@@ -605,6 +1148,28 @@ fn generate_argument_serialize(
 		_ => quote!(#name),
 	}
 }
+/// The type an argument has on the wire, so it can be checked against `stardust_xr::schemas::Schema`.
+fn generate_schema_type(argument_type: &ArgumentType, optional: bool) -> TokenStream {
+	let _type = match argument_type {
+		ArgumentType::Node { .. } => quote!(u64),
+		ArgumentType::Fd => quote!(u32),
+		ArgumentType::Color => quote!([f32; 4]),
+		ArgumentType::Vec(v) => {
+			let t = generate_schema_type(v, false);
+			quote!(Vec<#t>)
+		}
+		ArgumentType::Map(v) => {
+			let t = generate_schema_type(v, false);
+			quote!(stardust_xr::values::Map<String, #t>)
+		}
+		_ => generate_argument_type(argument_type, true),
+	};
+	if optional {
+		quote!(Option<#_type>)
+	} else {
+		_type
+	}
+}
 fn generate_argument_decl(argument: &Argument, returned: bool) -> TokenStream {
 	let name = Ident::new(&argument.name.to_case(Case::Snake), Span::call_site());
 	let mut _type = generate_argument_type(&argument._type, returned);
@@ -665,18 +1230,20 @@ fn generate_argument_type(argument_type: &ArgumentType, owned: bool) -> TokenStr
 				quote!(String)
 			}
 		}
-		ArgumentType::Vec(t) => {
-			let t = generate_argument_type(t, true);
-			if !owned {
+		ArgumentType::Fd => quote!(std::os::fd::OwnedFd),
+		ArgumentType::Vec(v) => {
+			let t = generate_argument_type(v, true);
+			// File descriptors get moved into the message, so they can't be borrowed
+			if !owned && !contains_fd(v) {
 				quote!(&[#t])
 			} else {
 				quote!(Vec<#t>)
 			}
 		}
-		ArgumentType::Map(t) => {
-			let t = generate_argument_type(t, true);
+		ArgumentType::Map(v) => {
+			let t = generate_argument_type(v, true);
 
-			if !owned {
+			if !owned && !contains_fd(v) {
 				quote!(&stardust_xr::values::Map<String, #t>)
 			} else {
 				quote!(stardust_xr::values::Map<String, #t>)
@@ -726,3 +1293,21 @@ fn generate_argument_type(argument_type: &ArgumentType, owned: bool) -> TokenStr
 		}
 	}
 }
+
+#[test]
+fn resolve_inherits_chain() {
+	let all_aspects = all_aspects();
+	let field_inherits = resolve_inherits(&all_aspects, "Field");
+	for aspect in ["Spatial", "FieldRef", "Owned", "SpatialRef"] {
+		assert!(
+			field_inherits.contains(&aspect.to_string()),
+			"Field should inherit {aspect}"
+		);
+	}
+	assert_eq!(
+		field_inherits.len(),
+		4,
+		"duplicate inherits in {field_inherits:?}"
+	);
+	assert!(resolve_inherits(&all_aspects, "PanelItemUi").is_empty());
+}
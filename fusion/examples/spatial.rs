@@ -28,7 +28,7 @@ async fn main() {
 	tokio::select! {
 		biased;
 		_ = tokio::signal::ctrl_c() => (),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
 	};
 }
 
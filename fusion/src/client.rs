@@ -2,16 +2,21 @@
 
 use crate::node::{NodeResult, NodeType};
 use crate::root::{ClientState, Root, RootAspect};
-use crate::{node::NodeError, scenegraph::Scenegraph};
+use crate::{node::NodeError, scenegraph::Scenegraph, spatial::TransformCache};
 use color_eyre::eyre::Result;
 use global_counter::primitive::exact::CounterU64;
 use stardust_xr::schemas::flex::flexbuffers::DeserializationError;
 use stardust_xr::{
-	client,
+	client::{self, ClientEnv, ConnectError, ConnectOptions},
 	messenger::{self, MessengerError},
-	messenger::{MessageReceiver, MessageSender, MessageSenderHandle},
+	messenger::{Message, MessageReceiver, MessageSender, MessageSenderHandle, MessengerStats},
 };
+use std::future::Future;
+use std::os::fd::OwnedFd;
+use std::path::Path;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use thiserror::Error;
 use tokio::net::UnixStream;
 use tokio::sync::{Notify, OnceCell};
@@ -19,10 +24,12 @@ use tokio::task::JoinHandle;
 
 #[derive(Error, Debug)]
 pub enum ClientError {
-	#[error("Could not connect to the stardust server")]
-	ConnectionFailure,
+	#[error("Could not connect to the stardust server: {0}")]
+	ConnectionFailure(#[from] ConnectError),
 	#[error("Node error: {0}")]
 	NodeError(NodeError),
+	#[error("The async loop can only be started from inside a tokio runtime")]
+	NoRuntime,
 }
 impl From<NodeError> for ClientError {
 	fn from(e: NodeError) -> Self {
@@ -45,6 +52,46 @@ impl From<DeserializationError> for ClientError {
 	}
 }
 
+/// Why an `EventLoop` stopped other than `Client::stop_loop`.
+#[derive(Error, Debug)]
+pub enum EventLoopError {
+	/// The connection to the server broke.
+	#[error("Messenger error: {0}")]
+	Messenger(#[from] MessengerError),
+	/// Stopped with `EventLoop::abort`, so messages may have been left unsent.
+	#[error("Event loop was aborted")]
+	Aborted,
+}
+
+/// The task running a client's event loop, from `Client::connect_with_async_loop` and the other async loop constructors.
+///
+/// Await it to find out why the loop stopped: `Ok(())` if it was stopped with `Client::stop_loop`,
+/// `EventLoopError::Aborted` if it was stopped with `abort`, otherwise the messenger error that broke the connection.
+/// Dropping it leaves the loop running in the background.
+#[derive(Debug)]
+pub struct EventLoop(JoinHandle<Result<(), MessengerError>>);
+impl EventLoop {
+	/// Stop the event loop right away, without telling the server. Nothing is sent or received for the client afterwards.
+	pub fn abort(&self) {
+		self.0.abort();
+	}
+	/// If the event loop has stopped, for any reason.
+	pub fn is_finished(&self) -> bool {
+		self.0.is_finished()
+	}
+}
+impl Future for EventLoop {
+	type Output = Result<(), EventLoopError>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		Pin::new(&mut self.0).poll(cx).map(|result| match result {
+			Ok(result) => Ok(result?),
+			Err(e) if e.is_cancelled() => Err(EventLoopError::Aborted),
+			Err(e) => std::panic::resume_unwind(e.into_panic()),
+		})
+	}
+}
+
 /// Your connection to the Stardust server.
 pub struct Client {
 	pub message_sender_handle: MessageSenderHandle,
@@ -52,17 +99,33 @@ pub struct Client {
 
 	id_counter: CounterU64,
 	stop_notifier: Notify,
+	pub(crate) transform_cache: TransformCache,
 
 	root: OnceCell<Root>,
 	state: OnceCell<ClientState>,
+	id: OnceCell<u64>,
 }
 
 impl Client {
-	/// Try to connect to the server, return messenger halves for manually setting up the event loop.
+	/// Try to connect to the server found through the process environment, return messenger halves for manually setting up the event loop.
 	pub async fn connect() -> Result<(Arc<Self>, MessageSender, MessageReceiver), ClientError> {
-		let connection = client::connect()
-			.await
-			.map_err(|_| ClientError::ConnectionFailure)?;
+		let connection = client::connect(&ClientEnv::from_process_env()).await?;
+		Client::from_connection(connection).await
+	}
+	/// Try to connect to the server listening at `socket_path`, ignoring `STARDUST_INSTANCE` and other environment variables.
+	pub async fn connect_to(
+		socket_path: impl AsRef<Path>,
+	) -> Result<(Arc<Self>, MessageSender, MessageReceiver), ClientError> {
+		let connection = client::connect_to(socket_path).await?;
+		Client::from_connection(connection).await
+	}
+	/// Try to connect to the server with explicit options and environment, without reading any process environment variables.
+	/// Anything left unset in `options` falls back to `env`, see `ConnectOptions` for precedence.
+	pub async fn connect_with(
+		options: &ConnectOptions,
+		env: &ClientEnv,
+	) -> Result<(Arc<Self>, MessageSender, MessageReceiver), ClientError> {
+		let connection = client::connect_with(options, env).await?;
 		Client::from_connection(connection).await
 	}
 
@@ -77,9 +140,11 @@ impl Client {
 
 			id_counter: CounterU64::new(u64::MAX / 2),
 			stop_notifier: Default::default(),
+			transform_cache: Default::default(),
 
 			root: OnceCell::new(),
 			state: OnceCell::new(),
+			id: OnceCell::new(),
 		});
 		let _ = client.root.set(Root::from_id(&client, 0, true));
 
@@ -87,11 +152,28 @@ impl Client {
 	}
 
 	/// Automatically set up the client with an async loop. This option is generally what you'll want to use.
-	pub async fn connect_with_async_loop(
-	) -> Result<(Arc<Self>, JoinHandle<Result<(), MessengerError>>), ClientError> {
-		let (client, mut message_tx, mut message_rx) = Client::connect().await?;
-
-		let event_loop = tokio::task::spawn({
+	///
+	/// The loop is spawned as a tokio task, so this has to be awaited inside a tokio runtime or it fails with `ClientError::NoRuntime`.
+	/// To drive the client from another executor, use `connect` and run the messenger halves yourself.
+	pub async fn connect_with_async_loop() -> Result<(Arc<Self>, EventLoop), ClientError> {
+		let (client, message_tx, message_rx) = Client::connect().await?;
+		Client::start_async_loop(client, message_tx, message_rx).await
+	}
+	/// Set up the client with an async loop over an established connection, such as one from `stardust_xr::client::connect_to`.
+	/// Like `connect_with_async_loop`, this needs a tokio runtime.
+	pub async fn from_connection_with_async_loop(
+		connection: UnixStream,
+	) -> Result<(Arc<Self>, EventLoop), ClientError> {
+		let (client, message_tx, message_rx) = Client::from_connection(connection).await?;
+		Client::start_async_loop(client, message_tx, message_rx).await
+	}
+	async fn start_async_loop(
+		client: Arc<Self>,
+		mut message_tx: MessageSender,
+		mut message_rx: MessageReceiver,
+	) -> Result<(Arc<Self>, EventLoop), ClientError> {
+		let runtime = tokio::runtime::Handle::try_current().map_err(|_| ClientError::NoRuntime)?;
+		let event_loop = runtime.spawn({
 			let client = client.clone();
 			let scenegraph = client.scenegraph.clone();
 			async move {
@@ -122,8 +204,11 @@ impl Client {
 		let _ = client
 			.state
 			.set(client.get_root().get_state().await.unwrap_or_default());
+		if let Ok(id) = client.get_root().get_client_id().await {
+			let _ = client.id.set(id);
+		}
 
-		Ok((client, event_loop))
+		Ok((client, EventLoop(event_loop)))
 	}
 
 	/// Get a reference to the client's root node, a spatial that exists where the client was spawned.
@@ -133,6 +218,11 @@ impl Client {
 	pub fn get_state(&self) -> &ClientState {
 		self.state.get().unwrap()
 	}
+	/// The ID the server assigned to this client, unique among the clients connected to it.
+	/// Only available when set up with an async loop, and `None` if the server doesn't support it.
+	pub fn id(&self) -> Option<u64> {
+		self.id.get().copied()
+	}
 
 	/// Set the prefixes for any `NamespacedResource`s.
 	pub fn set_base_prefixes(&self, prefixes: &[&str]) -> NodeResult<()> {
@@ -151,15 +241,74 @@ impl Client {
 		self.get_root().set_base_prefixes(&prefixes)
 	}
 
+	/// Wait until every message sent so far (such as creating nodes) has been written to the socket.
+	/// Useful to make sure the server will see them before doing something outside of Stardust, like spawning a child process.
+	pub async fn flush(&self) -> Result<(), MessengerError> {
+		self.message_sender_handle.flush()?.await
+	}
+
+	/// Counters of the messages sent and received over this connection so far, for profiling.
+	pub fn stats(&self) -> MessengerStats {
+		self.message_sender_handle.stats()
+	}
+
+	/// Send a signal to the node with `node_id` by opcode, skipping the generated protocol entirely.
+	/// Meant for tooling and for speaking protocols this version of fusion wasn't generated against.
+	///
+	/// Nothing is checked: the node may not exist, the opcode may not be a signal on any of its aspects, and `data` may not be what the server expects.
+	/// None of fusion's local bookkeeping (such as cached transforms or spatial parents) is updated either, so prefer the typed calls whenever they exist.
+	pub fn send_raw_signal(
+		&self,
+		node_id: u64,
+		opcode: u64,
+		data: &[u8],
+		fds: Vec<OwnedFd>,
+	) -> NodeResult<()> {
+		self.message_sender_handle
+			.signal(node_id, opcode, data, fds)
+			.map_err(|e| NodeError::MessengerError { e })
+	}
+	/// Call a method on the node with `node_id` by opcode and get back the raw response, skipping the generated protocol entirely.
+	/// The same caveats as `send_raw_signal` apply.
+	pub async fn call_raw_method(
+		&self,
+		node_id: u64,
+		opcode: u64,
+		data: &[u8],
+		fds: Vec<OwnedFd>,
+	) -> NodeResult<Message> {
+		let future = self
+			.message_sender_handle
+			.method_typed(node_id, opcode, data, fds)
+			.map_err(|e| NodeError::MessengerError { e })?;
+		future.await.map_err(NodeError::from)
+	}
+
+	/// Generate an id for a new node, unique among the nodes this client creates.
+	///
+	/// Generated ids start at `u64::MAX / 2`, so ids below that never collide with them and are free for `create_with_id` (such as to refer to a node by a known id in tests).
+	/// An id must not belong to any other live node of this client, reusing one is a protocol error (and panics in debug builds).
 	pub fn generate_id(&self) -> u64 {
 		self.id_counter.inc()
 	}
 
-	/// Stop the event loop if created with async loop. Equivalent to a graceful disconnect.
+	/// Stop the event loop if created with async loop.
+	/// This doesn't tell the server anything, use `shutdown` to disconnect cleanly.
 	pub fn stop_loop(&self) {
 		self.stop_notifier.notify_one();
 	}
+
+	/// Cleanly disconnect from the server: send `disconnect`, wait for it to be written to the socket, then stop the event loop.
+	/// The server can then tell this apart from the client crashing.
+	pub async fn shutdown(&self) -> Result<(), ClientError> {
+		self.get_root().disconnect()?;
+		let flushed = self.flush().await;
+		self.stop_loop();
+		Ok(flushed?)
+	}
 }
+/// Dropping the client is an abrupt close: `disconnect` is queued but the event loop is stopped without waiting for it to be sent.
+/// Call `Client::shutdown` first to make sure the server sees a clean disconnect.
 impl Drop for Client {
 	fn drop(&mut self) {
 		let _ = self.get_root().disconnect();
@@ -174,7 +323,7 @@ async fn fusion_client_connect() {
 	tokio::select! {
 		biased;
 		_ = tokio::time::sleep(core::time::Duration::from_secs(1)) => (),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
 	}
 }
 
@@ -202,6 +351,176 @@ async fn fusion_client_life_cycle() {
 	tokio::select! {
 		biased;
 		_ = tokio::time::sleep(core::time::Duration::from_secs(5)) => panic!("Timed Out"),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
+	};
+}
+
+#[tokio::test]
+async fn fusion_client_id() {
+	use crate::root::ROOT_GET_CLIENT_ID_SERVER_OPCODE;
+	use stardust_xr::{mock::MockServer, server::Server};
+
+	let socket_path = std::env::temp_dir().join(format!(
+		"stardust-client-id-test-{}.sock",
+		std::process::id()
+	));
+	let _ = std::fs::remove_file(&socket_path);
+	let server = Server::bind(&socket_path).unwrap();
+
+	let mut clients = Vec::new();
+	for _ in 0..2 {
+		let (stream, connection) = tokio::join!(
+			stardust_xr::client::connect_to(&socket_path),
+			server.accept()
+		);
+		let connection = connection.unwrap();
+		let server_id = connection.info.id;
+		// Answer with the id the server assigned this connection, like the server's root does
+		let scenegraph = MockServer::new();
+		scenegraph.respond(ROOT_GET_CLIENT_ID_SERVER_OPCODE, &server_id);
+		tokio::spawn(async move { connection.run(&*scenegraph).await });
+		let (client, event_loop) = Client::from_connection_with_async_loop(stream.unwrap())
+			.await
+			.unwrap();
+		clients.push((server_id, client, event_loop));
+	}
+	for (server_id, client, _) in &clients {
+		assert_eq!(client.id(), Some(*server_id));
+	}
+	assert_ne!(clients[0].0, clients[1].0);
+
+	let ids = (0..64)
+		.map(|_| clients[0].1.generate_id())
+		.collect::<rustc_hash::FxHashSet<_>>();
+	assert_eq!(ids.len(), 64);
+	let _ = std::fs::remove_file(&socket_path);
+}
+
+#[tokio::test]
+async fn fusion_client_shutdown() {
+	use crate::root::ROOT_DISCONNECT_SERVER_OPCODE;
+	use stardust_xr::mock::MockServer;
+
+	let server = MockServer::new();
+	let (client, event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	client.shutdown().await.unwrap();
+	event_loop.await.unwrap();
+
+	let disconnected = || {
+		server
+			.signals()
+			.iter()
+			.any(|s| s.node == 0 && s.method == ROOT_DISCONNECT_SERVER_OPCODE)
 	};
+	tokio::time::timeout(std::time::Duration::from_secs(1), async {
+		while !disconnected() {
+			tokio::task::yield_now().await;
+		}
+	})
+	.await
+	.expect("Server never saw the client disconnect");
+}
+
+#[tokio::test]
+async fn fusion_client_abort_event_loop() {
+	use stardust_xr::mock::MockServer;
+
+	let server = MockServer::new();
+	let (client, event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	assert!(!event_loop.is_finished());
+
+	event_loop.abort();
+	tokio::time::timeout(std::time::Duration::from_secs(1), async {
+		while !event_loop.is_finished() {
+			tokio::task::yield_now().await;
+		}
+	})
+	.await
+	.unwrap();
+	assert!(matches!(event_loop.await, Err(EventLoopError::Aborted)));
+
+	// Nothing is left to write messages out or read responses back
+	assert!(client.flush().await.is_err());
+	assert!(client.get_root().get_client_id().await.is_err());
+}
+
+#[tokio::test]
+async fn fusion_client_raw_messages() {
+	use crate::root::{ROOT_GET_BASE_PREFIXES_SERVER_OPCODE, ROOT_SET_BASE_PREFIXES_SERVER_OPCODE};
+	use stardust_xr::{
+		mock::MockServer,
+		schemas::flex::{deserialize, serialize},
+	};
+
+	let server = MockServer::new();
+	server.respond(ROOT_GET_BASE_PREFIXES_SERVER_OPCODE, &vec!["/res"]);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let prefixes = vec!["/res".to_string()];
+	client.get_root().set_base_prefixes(&prefixes).unwrap();
+	client
+		.send_raw_signal(
+			0,
+			ROOT_SET_BASE_PREFIXES_SERVER_OPCODE,
+			&serialize(&prefixes).unwrap(),
+			Vec::new(),
+		)
+		.unwrap();
+	client.flush().await.unwrap();
+	let set_prefixes = || {
+		server
+			.signals()
+			.into_iter()
+			.filter(|s| s.method == ROOT_SET_BASE_PREFIXES_SERVER_OPCODE)
+			.collect::<Vec<_>>()
+	};
+	tokio::time::timeout(std::time::Duration::from_secs(1), async {
+		while set_prefixes().len() < 2 {
+			tokio::task::yield_now().await;
+		}
+	})
+	.await
+	.unwrap();
+	let signals = set_prefixes();
+	assert_eq!(signals[0], signals[1]);
+
+	let response = client
+		.call_raw_method(
+			0,
+			ROOT_GET_BASE_PREFIXES_SERVER_OPCODE,
+			&serialize(()).unwrap(),
+			Vec::new(),
+		)
+		.await
+		.unwrap();
+	let raw: Vec<String> = deserialize(&response.into_message()).unwrap();
+	assert_eq!(raw, client.get_root().get_base_prefixes().await.unwrap());
+}
+
+#[test]
+fn fusion_client_async_loop_without_runtime() {
+	use std::task::{Wake, Waker};
+
+	struct NoopWaker;
+	impl Wake for NoopWaker {
+		fn wake(self: Arc<Self>) {}
+	}
+
+	// The socket needs a runtime to be created, but the runtime isn't entered while starting the loop
+	let runtime = tokio::runtime::Builder::new_current_thread()
+		.enable_all()
+		.build()
+		.unwrap();
+	let (client_socket, _server_socket) = runtime.block_on(async { UnixStream::pair() }).unwrap();
+
+	let mut start = std::pin::pin!(Client::from_connection_with_async_loop(client_socket));
+	let waker = Waker::from(Arc::new(NoopWaker));
+	let result = start.as_mut().poll(&mut Context::from_waker(&waker));
+	assert!(matches!(result, Poll::Ready(Err(ClientError::NoRuntime))));
 }
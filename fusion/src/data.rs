@@ -157,6 +157,77 @@ async fn fusion_pulses() {
 
 	tokio::select! {
 		_ = tokio::time::sleep(core::time::Duration::from_secs(1)) => panic!("Timed Out"),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
 	}
 }
+
+#[tokio::test]
+async fn fusion_pulse_receiver_fan_out() {
+	use super::client::Client;
+	use stardust_xr::{scenegraph::Scenegraph, schemas::flex::serialize};
+
+	struct CountingReceiver(usize);
+	impl PulseReceiverHandler for CountingReceiver {
+		fn data(&mut self, _sender: SpatialRef, _data: Datamap) {
+			self.0 += 1;
+		}
+	}
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _message_tx, _message_rx) = Client::from_connection(client_socket).await.unwrap();
+
+	let receiver = PulseReceiver::from_id(&client, 1, true);
+	let logger = std::sync::Arc::new(parking_lot::Mutex::new(CountingReceiver(0)));
+	receiver.subscribe_pulse_receiver_events(&logger).unwrap();
+	let receiver = receiver.wrap(CountingReceiver(0)).unwrap();
+
+	let data = Datamap::from_typed(()).unwrap();
+	let message = serialize((2_u64, &data)).unwrap();
+	let send = || {
+		client
+			.scenegraph
+			.send_signal(1, PULSE_RECEIVER_DATA_CLIENT_OPCODE, &message, Vec::new())
+	};
+	send().unwrap();
+	send().unwrap();
+	assert_eq!(receiver.lock_wrapped().0, 2);
+	assert_eq!(logger.lock().0, 2);
+
+	// Dropped subscribers get pruned without disturbing the wrapped handler.
+	drop(logger);
+	send().unwrap();
+	assert_eq!(receiver.lock_wrapped().0, 3);
+}
+
+#[tokio::test]
+async fn fusion_pulse_receiver_unknown_signal() {
+	use super::client::Client;
+	use stardust_xr::scenegraph::{Scenegraph, ScenegraphError};
+
+	#[derive(Default)]
+	struct UnknownReceiver(Vec<(u64, Vec<u8>)>);
+	impl PulseReceiverHandler for UnknownReceiver {
+		fn data(&mut self, _sender: SpatialRef, _data: Datamap) {}
+		fn unknown_signal(&mut self, opcode: u64, data: Vec<u8>) {
+			self.0.push((opcode, data));
+		}
+	}
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _message_tx, _message_rx) = Client::from_connection(client_socket).await.unwrap();
+
+	let receiver = PulseReceiver::from_id(&client, 1, true);
+	assert!(matches!(
+		client
+			.scenegraph
+			.send_signal(1, 999, &[1, 2, 3], Vec::new()),
+		Err(ScenegraphError::SignalNotFound)
+	));
+
+	let receiver = receiver.wrap(UnknownReceiver::default()).unwrap();
+	client
+		.scenegraph
+		.send_signal(1, 999, &[1, 2, 3], Vec::new())
+		.unwrap();
+	assert_eq!(receiver.lock_wrapped().0, vec![(999, vec![1, 2, 3])]);
+}
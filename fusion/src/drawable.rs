@@ -5,7 +5,7 @@ use std::hash::Hash;
 use crate::{
 	impl_aspects,
 	node::{NodeResult, NodeType, OwnedAspect},
-	spatial::{SpatialAspect, SpatialRefAspect, Transform},
+	spatial::{BoundingBox, SpatialAspect, SpatialRefAspect, Transform},
 };
 use stardust_xr::values::*;
 
@@ -33,7 +33,7 @@ impl Default for LinePoint {
 		Self {
 			point: [0.0; 3].into(),
 			thickness: 0.01,
-			color: color::rgba_linear!(1.0, 1.0, 1.0, 1.0),
+			color: color::WHITE,
 		}
 	}
 }
@@ -89,6 +89,21 @@ impl Model {
 		let client = self.client()?;
 		self.bind_model_part(client.generate_id(), relative_path)
 	}
+	/// Uniformly scale the model so it's as big as it can be while fitting inside a box of `target_size`, such as to place an arbitrary model in a fixed-size slot.
+	/// Only the scale is changed, and the scale that was applied is returned. Axes where the model is flat are ignored.
+	pub async fn fit_to(&self, target_size: impl Into<Vector3<f32>>) -> NodeResult<f32> {
+		let target_size: [f32; 3] = target_size.into().into();
+		let model_size: [f32; 3] = self.get_model_bounds().await?.size.into();
+		let scale = target_size
+			.into_iter()
+			.zip(model_size)
+			.filter(|(_, model)| *model > f32::EPSILON)
+			.map(|(target, model)| target / model)
+			.reduce(f32::min)
+			.unwrap_or(1.0);
+		self.set_local_transform(Transform::from_scale([scale; 3]))?;
+		Ok(scale)
+	}
 }
 impl_aspects!(Text: OwnedAspect, SpatialRefAspect, SpatialAspect);
 impl Text {
@@ -108,12 +123,31 @@ impl Text {
 			style,
 		)
 	}
+	/// Create a text node with `TextStyle::centered()`, handy for quick debug text.
+	pub fn create_default(
+		spatial_parent: &impl SpatialRefAspect,
+		transform: Transform,
+		text: &str,
+	) -> NodeResult<Self> {
+		Text::create(spatial_parent, transform, text, TextStyle::centered())
+	}
+}
+impl TextStyle {
+	/// Like `TextStyle::default()`, but centered on the node instead of hanging down and right from it.
+	pub fn centered() -> Self {
+		Self {
+			text_align_x: XAlign::Center,
+			text_align_y: YAlign::Center,
+			..Default::default()
+		}
+	}
 }
+/// White text 1cm tall in the server's default font, aligned to the top left of the node with no bounds.
 impl Default for TextStyle {
 	fn default() -> Self {
 		Self {
 			character_height: 0.01,
-			color: color::rgba_linear!(1.0, 1.0, 1.0, 1.0),
+			color: color::WHITE,
 			font: Default::default(),
 			text_align_x: XAlign::Left,
 			text_align_y: YAlign::Top,
@@ -186,6 +220,42 @@ async fn fusion_lines() {
 	tokio::time::sleep(core::time::Duration::from_secs(60)).await;
 }
 
+#[tokio::test]
+async fn fusion_lines_point_offset() {
+	use crate::spatial::Spatial;
+
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.unwrap();
+	let lines = Lines::create(client.get_root(), Transform::identity(), &[]).unwrap();
+	let child =
+		Spatial::create(&lines, Transform::from_translation([0.0, 0.5, 0.0]), false).unwrap();
+
+	lines.set_point_offset([1.0, 0.0, 0.0]).unwrap();
+	let child_transform = child.get_transform(client.get_root()).await.unwrap();
+	assert_eq!(child_transform.translation, Some([0.0, 0.5, 0.0].into()));
+	let lines_transform = lines.get_transform(client.get_root()).await.unwrap();
+	assert_eq!(lines_transform.translation, Some([0.0, 0.0, 0.0].into()));
+}
+
+#[tokio::test]
+async fn fusion_lines_thickness_space() {
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.unwrap();
+	let lines = Lines::create(client.get_root(), Transform::identity(), &[]).unwrap();
+	assert_eq!(
+		lines.get_thickness_space().await.unwrap(),
+		ThicknessSpace::World
+	);
+
+	lines.set_thickness_space(ThicknessSpace::Local).unwrap();
+	assert_eq!(
+		lines.get_thickness_space().await.unwrap(),
+		ThicknessSpace::Local
+	);
+}
+
 #[tokio::test]
 async fn fusion_model() {
 	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
@@ -221,6 +291,57 @@ async fn fusion_model() {
 
 	tokio::time::sleep(core::time::Duration::from_secs(60)).await;
 }
+#[tokio::test]
+async fn fusion_model_animation() {
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.unwrap();
+	client
+		.set_base_prefixes(&[manifest_dir_macros::directory_relative_path!("res")])
+		.unwrap();
+
+	let triangle_resource = ResourceID::new_namespaced("fusion", "spinning_triangle");
+	let triangle_model =
+		Model::create(client.get_root(), Transform::none(), &triangle_resource).unwrap();
+	assert_eq!(
+		triangle_model.list_animations().await.unwrap(),
+		vec!["Spin".to_string()]
+	);
+	triangle_model.set_animation_time("Spin", 0.5).unwrap();
+	triangle_model.play_animation("Spin", true).unwrap();
+	tokio::time::sleep(core::time::Duration::from_secs(2)).await;
+	triangle_model.stop_animation("Spin").unwrap();
+}
+
+#[tokio::test]
+async fn fusion_model_fit_to() {
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.unwrap();
+	client
+		.set_base_prefixes(&[manifest_dir_macros::directory_relative_path!("res")])
+		.unwrap();
+
+	let gyro_resource = ResourceID::new_namespaced("fusion", "gyro");
+	let gyro_model = Model::create(client.get_root(), Transform::none(), &gyro_resource).unwrap();
+	let scale = gyro_model.fit_to([0.1, 0.1, 0.1]).await.unwrap();
+	assert!(scale > 0.0);
+
+	let bounds = gyro_model
+		.get_relative_bounding_box(client.get_root())
+		.await
+		.unwrap();
+	let size: [f32; 3] = bounds.size.into();
+	assert!(
+		size.iter().all(|s| *s <= 0.1 + 0.001),
+		"fitted size was {size:?}"
+	);
+	assert!(
+		size.iter().any(|s| (*s - 0.1).abs() < 0.001),
+		"fitted size was {size:?}"
+	);
+}
+
 #[tokio::test]
 async fn fusion_text() {
 	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
@@ -244,6 +365,106 @@ async fn fusion_text() {
 	tokio::time::sleep(core::time::Duration::from_secs(60)).await;
 }
 
+#[test]
+fn text_style_defaults() {
+	let style = TextStyle::default();
+	assert_eq!(style.character_height, 0.01);
+	assert_eq!(style.color, color::rgba_linear!(1.0, 1.0, 1.0, 1.0));
+	assert_eq!(style.font, None);
+	assert_eq!(style.text_align_x, XAlign::Left);
+	assert_eq!(style.text_align_y, YAlign::Top);
+	assert_eq!(style.bounds, None);
+
+	let centered = TextStyle::centered();
+	assert_eq!(centered.text_align_x, XAlign::Center);
+	assert_eq!(centered.text_align_y, YAlign::Center);
+	assert_eq!(centered.character_height, style.character_height);
+	assert_eq!(centered.color, style.color);
+}
+
+#[test]
+fn text_style_json_round_trip() {
+	let style = TextStyle {
+		font: Some(ResourceID::new_namespaced("fusion", "common_case")),
+		bounds: Some(TextBounds {
+			bounds: [1.0, 0.5].into(),
+			fit: TextFit::Wrap,
+			anchor_align_x: XAlign::Left,
+			anchor_align_y: YAlign::Top,
+		}),
+		..Default::default()
+	};
+	let json = serde_json::to_value(&style).unwrap();
+	assert_eq!(json["font"], "fusion:common_case");
+	assert_eq!(json["bounds"]["bounds"], serde_json::json!([1.0, 0.5]));
+	assert_eq!(json["bounds"]["fit"], TextFit::Wrap as u32);
+	assert_eq!(serde_json::from_value::<TextStyle>(json).unwrap(), style);
+}
+
+#[tokio::test]
+async fn fusion_text_restyle() {
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.unwrap();
+
+	let text = Text::create(
+		client.get_root(),
+		Transform::none(),
+		"Test Text",
+		TextStyle::default(),
+	)
+	.unwrap();
+	text.set_color(color::RED).unwrap();
+	text.set_alignment(XAlign::Right, YAlign::Bottom).unwrap();
+	text.set_bounds(None).unwrap();
+
+	let style = text.get_style().await.unwrap();
+	assert_eq!(style.color, color::RED);
+	assert_eq!(style.text_align_x, XAlign::Right);
+	assert_eq!(style.text_align_y, YAlign::Bottom);
+	assert!(style.bounds.is_none());
+}
+
+#[tokio::test]
+async fn fusion_create_text_builder() {
+	use crate::{client::Client, node::NodeError};
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _message_tx, _message_rx) = Client::from_connection(client_socket).await.unwrap();
+
+	let text = CreateTextBuilder::new(client.get_root())
+		.transform(Transform::identity())
+		.text("Test Text")
+		.style(TextStyle::default())
+		.build(&client)
+		.unwrap();
+	assert!(text.node().get_id().is_ok());
+
+	let missing_style = CreateTextBuilder::new(client.get_root())
+		.transform(Transform::identity())
+		.text("Test Text")
+		.build(&client);
+	assert!(matches!(
+		missing_style,
+		Err(NodeError::MissingArgument { name: "style" })
+	));
+}
+
+#[tokio::test]
+async fn fusion_resource_exists() {
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	client
+		.set_base_prefixes(&[manifest_dir_macros::directory_relative_path!("res")])
+		.unwrap();
+
+	let gyro = ResourceID::new_namespaced("fusion", "gyro");
+	assert!(resource_exists(&client, &gyro).await.unwrap());
+	let missing = ResourceID::new_namespaced("fusion", "does_not_exist");
+	assert!(!resource_exists(&client, &missing).await.unwrap());
+}
+
 #[tokio::test]
 async fn fusion_sky() {
 	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
@@ -1,11 +1,11 @@
 //! Analog SDFs to define boundaries for input, interaction, and behavior.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::{
 	client::Client,
 	impl_aspects,
-	node::{NodeResult, NodeType, OwnedAspect},
+	node::{NodeError, NodeResult, NodeType, OwnedAspect},
 	spatial::{SpatialAspect, SpatialRefAspect, Transform},
 };
 
@@ -24,15 +24,365 @@ impl Field {
 		transform: Transform,
 		shape: Shape,
 	) -> NodeResult<Self> {
-		let client = spatial_parent.client()?;
+		let id = spatial_parent.client()?.generate_id();
+		Self::create_with_id(spatial_parent, id, transform, shape)
+	}
+	/// Like `create`, but with an explicit node id instead of a generated one, see `Client::generate_id` for which ids are safe to use.
+	pub fn create_with_id(
+		spatial_parent: &impl SpatialRefAspect,
+		id: u64,
+		transform: Transform,
+		shape: Shape,
+	) -> NodeResult<Self> {
 		create_field(
+			&spatial_parent.client()?,
+			id,
+			spatial_parent,
+			transform,
+			shape,
+		)
+	}
+	/// Create a field combining `a` and `b` with `op`, such as a box with a sphere subtracted from it.
+	pub fn create_combined(
+		spatial_parent: &impl SpatialRefAspect,
+		transform: Transform,
+		op: BoolOp,
+		a: &impl FieldRefAspect,
+		b: &impl FieldRefAspect,
+	) -> NodeResult<Self> {
+		let client = spatial_parent.client()?;
+		create_combined_field(
 			&client,
 			client.generate_id(),
 			spatial_parent,
 			transform,
-			shape,
+			op,
+			a,
+			b,
 		)
 	}
+	/// Smoothly change the shape from `from` to `to` over `duration`, with `easing` mapping linear progress from 0 to 1 onto the blend between them.
+	/// Call `ShapeAnimation::update` every frame to apply it.
+	/// Only shapes of the same kind can be animated between, otherwise this fails with `NodeError::IncompatibleInterpolation`.
+	pub fn animate_shape(
+		&self,
+		from: Shape,
+		to: Shape,
+		duration: Duration,
+		easing: fn(f32) -> f32,
+	) -> NodeResult<ShapeAnimation> {
+		lerp_shape(&from, &to, 0.0).ok_or(NodeError::IncompatibleInterpolation)?;
+		Ok(ShapeAnimation {
+			field: self.alias(),
+			from,
+			to,
+			duration: duration.as_secs_f32(),
+			elapsed: 0.0,
+			easing,
+		})
+	}
+}
+
+/// A field's shape animating between 2 shapes of the same kind, made with `Field::animate_shape`.
+pub struct ShapeAnimation {
+	field: Field,
+	from: Shape,
+	to: Shape,
+	duration: f32,
+	elapsed: f32,
+	easing: fn(f32) -> f32,
+}
+impl ShapeAnimation {
+	/// Advance the animation by `delta` seconds (such as `FrameInfo::delta`) and set the field's shape, returning if it's finished.
+	pub fn update(&mut self, delta: f32) -> NodeResult<bool> {
+		self.elapsed += delta;
+		let progress = if self.duration > 0.0 {
+			(self.elapsed / self.duration).clamp(0.0, 1.0)
+		} else {
+			1.0
+		};
+		self.field.set_shape(self.shape_at(progress))?;
+		Ok(progress >= 1.0)
+	}
+	/// The shape at `progress` from 0 (`from`) to 1 (`to`), with easing applied.
+	pub fn shape_at(&self, progress: f32) -> Shape {
+		lerp_shape(&self.from, &self.to, (self.easing)(progress)).unwrap()
+	}
+}
+
+fn lerp_shape(from: &Shape, to: &Shape, t: f32) -> Option<Shape> {
+	let lerp = |a: f32, b: f32| a + (b - a) * t;
+	Some(match (from, to) {
+		(Shape::Box(a), Shape::Box(b)) => {
+			Shape::Box([lerp(a.x, b.x), lerp(a.y, b.y), lerp(a.z, b.z)].into())
+		}
+		(Shape::Cylinder(a), Shape::Cylinder(b)) => Shape::Cylinder(CylinderShape {
+			length: lerp(a.length, b.length),
+			radius: lerp(a.radius, b.radius),
+		}),
+		(Shape::Sphere(a), Shape::Sphere(b)) => Shape::Sphere(lerp(*a, *b)),
+		(Shape::Torus(a), Shape::Torus(b)) => Shape::Torus(TorusShape {
+			radius_a: lerp(a.radius_a, b.radius_a),
+			radius_b: lerp(a.radius_b, b.radius_b),
+		}),
+		_ => return None,
+	})
+}
+
+#[test]
+fn shape_json_round_trip() {
+	let shapes = [
+		Shape::Box([1.0, 2.0, 3.0].into()),
+		Shape::Cylinder(CylinderShape {
+			length: 1.0,
+			radius: 0.5,
+		}),
+		Shape::Sphere(0.5),
+		Shape::Torus(TorusShape {
+			radius_a: 1.0,
+			radius_b: 0.25,
+		}),
+	];
+	let json = serde_json::to_value(&shapes).unwrap();
+	assert_eq!(
+		json,
+		serde_json::json!([
+			[1.0, 2.0, 3.0],
+			{"length": 1.0, "radius": 0.5},
+			0.5,
+			{"radius_a": 1.0, "radius_b": 0.25},
+		])
+	);
+	assert_eq!(serde_json::from_value::<[Shape; 4]>(json).unwrap(), shapes);
 }
 
 // TODO: write proper tests for each field shape and setting shape
+
+#[tokio::test]
+async fn fusion_field_intersects() {
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	let field_a =
+		Field::create(client.get_root(), Transform::identity(), Shape::Sphere(0.5)).unwrap();
+	let field_b = Field::create(
+		client.get_root(),
+		Transform::from_translation([0.75, 0.0, 0.0]),
+		Shape::Sphere(0.5),
+	)
+	.unwrap();
+	let field_c = Field::create(
+		client.get_root(),
+		Transform::from_translation([5.0, 0.0, 0.0]),
+		Shape::Sphere(0.5),
+	)
+	.unwrap();
+
+	assert!(field_a.intersects(&field_b).await.unwrap());
+	assert!(!field_a.intersects(&field_c).await.unwrap());
+
+	let depth = field_a.penetration_depth(&field_b).await.unwrap();
+	assert!((depth - 0.25).abs() < 0.01, "penetration depth was {depth}");
+	assert!(field_a.penetration_depth(&field_c).await.unwrap() < 0.0);
+}
+
+#[tokio::test]
+async fn fusion_field_combined_subtraction() {
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	let cube = Field::create(
+		client.get_root(),
+		Transform::identity(),
+		Shape::Box([0.5; 3].into()),
+	)
+	.unwrap();
+	let hole = Field::create(client.get_root(), Transform::identity(), Shape::Sphere(0.2)).unwrap();
+	let combined = Field::create_combined(
+		client.get_root(),
+		Transform::identity(),
+		BoolOp::Subtraction,
+		&cube,
+		&hole,
+	)
+	.unwrap();
+
+	// the center is carved out, 0.2m from the sphere's surface
+	let center = combined
+		.distance(client.get_root(), [0.0; 3])
+		.await
+		.unwrap();
+	assert!(
+		(center - 0.2).abs() < 0.01,
+		"distance at center was {center}"
+	);
+	// between the sphere and the box's faces is still solid
+	let solid = combined
+		.distance(client.get_root(), [0.23, 0.0, 0.0])
+		.await
+		.unwrap();
+	assert!(solid < 0.0, "distance in solid part was {solid}");
+	let outside = combined
+		.distance(client.get_root(), [1.0, 0.0, 0.0])
+		.await
+		.unwrap();
+	assert!(
+		(outside - 0.75).abs() < 0.01,
+		"distance outside was {outside}"
+	);
+}
+
+#[tokio::test]
+async fn fusion_field_ray_march_step_size() {
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	let field = Field::create(
+		client.get_root(),
+		Transform::identity(),
+		Shape::Box([0.5; 3].into()),
+	)
+	.unwrap();
+	let ray_march = |max_steps, min_step| {
+		field.ray_march(
+			client.get_root(),
+			[0.1, 0.1, -2.0],
+			[0.0, 0.0, 1.0],
+			Some(max_steps),
+			Some(min_step),
+		)
+	};
+
+	let coarse = ray_march(1000, 0.1).await.unwrap();
+	let fine = ray_march(1000, 0.0001).await.unwrap();
+	assert!(coarse.ray_steps <= 1000 && fine.ray_steps <= 1000);
+	assert!(fine.ray_steps >= coarse.ray_steps);
+	assert!(fine.min_distance <= coarse.min_distance);
+
+	let capped = ray_march(2, 0.0001).await.unwrap();
+	assert!(capped.ray_steps <= 2);
+}
+
+#[tokio::test]
+async fn fusion_field_animate_shape() {
+	use stardust_xr::{mock::MockServer, schemas::flex::deserialize};
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	let field = Field::from_id(&client, 3, true);
+
+	assert!(matches!(
+		field.animate_shape(
+			Shape::Box([0.1; 3].into()),
+			Shape::Sphere(0.1),
+			Duration::from_secs(1),
+			|t| t
+		),
+		Err(NodeError::IncompatibleInterpolation)
+	));
+
+	let mut animation = field
+		.animate_shape(
+			Shape::Sphere(0.1),
+			Shape::Sphere(0.3),
+			Duration::from_secs(1),
+			|t| t * t,
+		)
+		.unwrap();
+	assert!(!animation.update(0.5).unwrap());
+	assert!(!animation.update(0.25).unwrap());
+	assert!(animation.update(0.5).unwrap());
+
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let radii = server
+		.signals()
+		.into_iter()
+		.filter(|signal| signal.method == FIELD_SET_SHAPE_SERVER_OPCODE)
+		.map(|signal| match deserialize(&signal.data).unwrap() {
+			Shape::Sphere(radius) => radius,
+			shape => panic!("Animated into {shape:?}"),
+		})
+		.collect::<Vec<_>>();
+	let expected = [0.15, 0.2125, 0.3];
+	assert_eq!(radii.len(), expected.len());
+	for (radius, expected) in radii.into_iter().zip(expected) {
+		assert!((radius - expected).abs() < 0.0001, "{radius} != {expected}");
+	}
+}
+
+#[tokio::test]
+async fn fusion_field_as_base_aspects() {
+	use crate::spatial::SpatialRef;
+	use stardust_xr::mock::MockServer;
+
+	async fn transform_of(field: &Field, relative_to: &SpatialRef) -> NodeResult<Transform> {
+		field.get_transform(relative_to).await
+	}
+
+	let server = MockServer::new();
+	server.respond(
+		crate::spatial::SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE,
+		&Transform::from_translation([0.0, 0.0, 1.0]),
+	);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	let field = Field::from_id(&client, 4, true);
+	let root = client.get_root().as_spatial_ref();
+
+	assert_eq!(
+		transform_of(&field, &root).await.unwrap(),
+		Transform::from_translation([0.0, 0.0, 1.0])
+	);
+	// Borrowed up the whole inheritance chain without giving up the field
+	assert_eq!(field.as_spatial().node().get_id().unwrap(), 4);
+	assert_eq!(field.as_spatial_ref().node().get_id().unwrap(), 4);
+	assert_eq!(field.as_field_ref().node().get_id().unwrap(), 4);
+}
+
+#[tokio::test]
+async fn fusion_field_get_shape() {
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	let field =
+		Field::create(client.get_root(), Transform::identity(), Shape::Sphere(0.5)).unwrap();
+	assert_eq!(field.get_shape().await.unwrap(), Shape::Sphere(0.5));
+
+	let torus = Shape::Torus(TorusShape {
+		radius_a: 0.5,
+		radius_b: 0.1,
+	});
+	field.set_shape(torus.clone()).unwrap();
+	assert_eq!(field.get_shape().await.unwrap(), torus);
+}
+
+#[tokio::test]
+async fn fusion_field_get_aspects() {
+	use crate::{
+		node::NodeAspect,
+		spatial::{SPATIAL_ASPECT_ID, SPATIAL_REF_ASPECT_ID},
+	};
+
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	let field =
+		Field::create(client.get_root(), Transform::identity(), Shape::Sphere(0.5)).unwrap();
+	let aspects = field.get_aspects().await.unwrap();
+	for aspect in [
+		FIELD_ASPECT_ID,
+		FIELD_REF_ASPECT_ID,
+		SPATIAL_ASPECT_ID,
+		SPATIAL_REF_ASPECT_ID,
+	] {
+		assert!(aspects.contains(&aspect));
+	}
+}
@@ -17,15 +17,21 @@
 //! - Send out input events (`InputHandlerHandler::input`) in order of distance until an input handler has captured the method.
 //! - The frame event is sent (`LifeCycle::frame`).
 //!
+//! Input methods and handlers can be switched off without destroying them using `NodeType::set_enabled`.
+//! An input method can also be tied to a field with `InputMethod::create_with_lifetime_field`, so the server destroys it along with the field.
+//! A disabled input method sends no input to any handler, and a disabled input handler is skipped as if it didn't exist, so input carries on to the next handler in order.
+//!
 //! You may want to use the `InputAction`-based structs in molecules for an easy way to parse and react to the raw input.
+//! For simply grabbing and moving something, `GrabGesture` turns the raw input into start/move/end events.
 
 use crate::{
-	fields::{Field, FieldAspect},
+	fields::{Field, FieldAspect, FieldRefAspect, Shape},
 	impl_aspects,
 	node::{NodeResult, NodeType, OwnedAspect},
 	spatial::{SpatialAspect, SpatialRefAspect, Transform},
 };
-use glam::{vec3a, Quat};
+use glam::{vec3a, Quat, Vec3};
+use stardust_xr::schemas::flex::flexbuffers::FlexBufferType;
 use stardust_xr::values::*;
 use std::hash::Hash;
 
@@ -48,6 +54,26 @@ impl InputMethod {
 			transform,
 			input_type,
 			datamap,
+			None::<&Field>,
+		)
+	}
+	/// Create an input method that the server destroys along with `lifetime_field`, such as a hand's field, so it can't outlive what it represents.
+	pub fn create_with_lifetime_field(
+		spatial_parent: &impl SpatialRefAspect,
+		transform: Transform,
+		input_type: InputDataType,
+		datamap: &Datamap,
+		lifetime_field: &impl FieldRefAspect,
+	) -> NodeResult<Self> {
+		let client = spatial_parent.client()?;
+		create_input_method(
+			&client,
+			client.generate_id(),
+			spatial_parent,
+			transform,
+			input_type,
+			datamap,
+			Some(lifetime_field),
 		)
 	}
 }
@@ -138,6 +164,15 @@ impl Pointer {
 	}
 }
 
+impl InputData {
+	/// If no other handler got this input method before this one this frame, see `order`.
+	///
+	/// Input stops propagating at the handler that captured it, so there's no need to check for input captured by other handlers:
+	/// it simply won't arrive. `captured` is only ever about this handler.
+	pub fn is_first(&self) -> bool {
+		self.order == 0
+	}
+}
 impl Hash for InputData {
 	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
 		self.id.hash(state)
@@ -145,6 +180,101 @@ impl Hash for InputData {
 }
 impl Eq for InputData {}
 
+/// What a `GrabGesture` did this frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrabEvent {
+	/// The input method with this id started grabbing.
+	Started { method: u64 },
+	/// The grabbing input method moved by `delta` relative to the input handler,
+	/// apply it with `set_relative_transform(&handler, delta)` on the spatial the handler is attached to.
+	Moving { delta: Transform },
+	/// The grab was released, or the grabbing input method stopped sending input to the handler.
+	Ended,
+}
+
+/// Tracks grabbing and moving with a single input method, one of the most common interactions.
+///
+/// Call `update` with the input data from `InputHandlerHandler::input` every time it arrives.
+/// A method starts grabbing when the datamap key `grab_key` is pressed (a bool, or a float above 0.5),
+/// and keeps the grab until it's released or stops sending input, even if other methods grab meanwhile.
+/// Pointers grab from their origin, tips from their origin and hands from their palm.
+/// You'll usually want to capture the method returned by `grabbing` so other handlers don't get it mid-grab.
+///
+/// Input arrives relative to the handler, so by default this expects each delta to be applied to the spatial the handler is attached to,
+/// moving the handler with the grab. Use `handler_moves(false)` if the handler stays put instead.
+#[derive(Debug, Clone)]
+pub struct GrabGesture {
+	grab_key: String,
+	handler_moves: bool,
+	grab: Option<(u64, Vec3, Quat)>,
+}
+impl GrabGesture {
+	pub fn new(grab_key: impl ToString) -> Self {
+		GrabGesture {
+			grab_key: grab_key.to_string(),
+			handler_moves: true,
+			grab: None,
+		}
+	}
+	/// Whether every delta is applied to the spatial the handler is attached to, so the handler moves with the grab (the default).
+	///
+	/// If it does, the grab's starting pose stays put relative to the handler, so deltas are measured from that.
+	/// If it doesn't, deltas are measured from the previous frame's pose instead.
+	pub fn handler_moves(mut self, handler_moves: bool) -> Self {
+		self.handler_moves = handler_moves;
+		self
+	}
+	/// The id of the input method currently grabbing, if any.
+	pub fn grabbing(&self) -> Option<u64> {
+		self.grab.map(|(id, _, _)| id)
+	}
+	/// Update with this frame's input, returning what happened to the grab if anything.
+	pub fn update(&mut self, data: &[InputData]) -> Option<GrabEvent> {
+		let Some((id, position, rotation)) = self.grab else {
+			let data = data.iter().find(|d| self.pressed(d))?;
+			let (position, rotation) = grab_pose(&data.input);
+			self.grab = Some((data.id, position, rotation));
+			return Some(GrabEvent::Started { method: data.id });
+		};
+		let Some(data) = data.iter().find(|d| d.id == id && self.pressed(d)) else {
+			self.grab = None;
+			return Some(GrabEvent::Ended);
+		};
+		let (new_position, new_rotation) = grab_pose(&data.input);
+		if new_position == position && new_rotation == rotation {
+			return None;
+		}
+		if !self.handler_moves {
+			self.grab = Some((id, new_position, new_rotation));
+		}
+		// Moving the handler by this brings the old pose onto the new one
+		let delta_rotation = new_rotation * rotation.inverse();
+		Some(GrabEvent::Moving {
+			delta: Transform::from_translation_rotation(
+				new_position - delta_rotation * position,
+				delta_rotation,
+			),
+		})
+	}
+	fn pressed(&self, data: &InputData) -> bool {
+		data.datamap.with_data(|map| {
+			map.index(self.grab_key.as_str())
+				.map(|value| match value.flexbuffer_type() {
+					FlexBufferType::Bool => value.as_bool(),
+					_ => value.as_f32() > 0.5,
+				})
+				.unwrap_or(false)
+		})
+	}
+}
+fn grab_pose(input: &InputDataType) -> (Vec3, Quat) {
+	match input {
+		InputDataType::Pointer(pointer) => (pointer.origin.into(), pointer.orientation.into()),
+		InputDataType::Hand(hand) => (hand.palm.position.into(), hand.palm.rotation.into()),
+		InputDataType::Tip(tip) => (tip.origin.into(), tip.orientation.into()),
+	}
+}
+
 #[tokio::test]
 async fn fusion_input_handler() {
 	use super::client::Client;
@@ -196,7 +326,7 @@ async fn fusion_input_handler() {
 	tokio::select! {
 		biased;
 		_ = tokio::signal::ctrl_c() => (),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
 	}
 }
 
@@ -272,7 +402,7 @@ async fn fusion_pointer_input_method() {
 	tokio::select! {
 		biased;
 		_ = tokio::signal::ctrl_c() => (),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
 	}
 }
 
@@ -375,6 +505,553 @@ async fn fusion_tip_input_method() {
 	tokio::select! {
 		biased;
 		_ = tokio::signal::ctrl_c() => (),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
+	}
+}
+
+#[tokio::test]
+async fn fusion_input_handler_disable() {
+	use crate::client::Client;
+	use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	struct MethodTest {
+		method: InputMethod,
+		handlers: Vec<InputHandler>,
+	}
+	impl InputMethodHandler for MethodTest {
+		fn create_handler(&mut self, handler: InputHandler, _field: Field) {
+			self.handlers.push(handler);
+			self.method.set_handler_order(&self.handlers).unwrap();
+		}
+		fn request_capture_handler(&mut self, _id: u64) {}
+		fn destroy_handler(&mut self, id: u64) {
+			self.handlers
+				.retain(|h| h.node().get_id().is_ok_and(|h| h != id));
+		}
+	}
+	struct HandlerTest(&'static str, UnboundedSender<&'static str>);
+	impl InputHandlerHandler for HandlerTest {
+		fn input(&mut self, _methods: Vec<InputMethodRef>, _data: Vec<InputData>) {
+			let _ = self.1.send(self.0);
+		}
+	}
+	async fn received_for(
+		rx: &mut UnboundedReceiver<&'static str>,
+		duration: core::time::Duration,
+	) -> Vec<&'static str> {
+		let mut received = Vec::new();
+		let _ = tokio::time::timeout(duration, async {
+			while let Some(name) = rx.recv().await {
+				received.push(name);
+			}
+		})
+		.await;
+		received
+	}
+
+	let method = InputMethod::create(
+		client.get_root(),
+		Transform::none(),
+		InputDataType::Tip(Tip::default()),
+		&Datamap::from_typed(()).unwrap(),
+	)
+	.unwrap();
+	let _method = method
+		.alias()
+		.wrap(MethodTest {
+			method,
+			handlers: Vec::new(),
+		})
+		.unwrap();
+
+	let field = crate::fields::Field::create(
+		client.get_root(),
+		Transform::identity(),
+		crate::fields::Shape::Sphere(0.1),
+	)
+	.unwrap();
+	let (tx, mut rx) = unbounded_channel();
+	let handler_a = InputHandler::create(client.get_root(), Transform::none(), &field)
+		.unwrap()
+		.wrap(HandlerTest("a", tx.clone()))
+		.unwrap();
+	let _handler_b = InputHandler::create(client.get_root(), Transform::none(), &field)
+		.unwrap()
+		.wrap(HandlerTest("b", tx))
+		.unwrap();
+
+	let received = received_for(&mut rx, core::time::Duration::from_millis(500)).await;
+	assert!(received.contains(&"a") && received.contains(&"b"));
+
+	NodeType::set_enabled(handler_a.node().as_ref(), false).unwrap();
+	// Let any input already in flight arrive first
+	received_for(&mut rx, core::time::Duration::from_millis(100)).await;
+	let received = received_for(&mut rx, core::time::Duration::from_millis(500)).await;
+	assert!(!received.contains(&"a"));
+	assert!(received.contains(&"b"));
+}
+
+#[tokio::test]
+async fn fusion_input_order() {
+	use super::client::Client;
+	use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	struct OrderTest(&'static str, UnboundedSender<(&'static str, u32, bool)>);
+	impl InputHandlerHandler for OrderTest {
+		fn input(&mut self, _methods: Vec<InputMethodRef>, data: Vec<InputData>) {
+			for data in data {
+				let _ = self.1.send((self.0, data.order, data.is_first()));
+			}
+		}
+	}
+
+	let field = crate::fields::Field::create(
+		client.get_root(),
+		Transform::identity(),
+		crate::fields::Shape::Sphere(0.1),
+	)
+	.unwrap();
+	let (tx, mut rx) = unbounded_channel();
+	let handler_a = InputHandler::create(client.get_root(), Transform::none(), &field)
+		.unwrap()
+		.wrap(OrderTest("a", tx.clone()))
+		.unwrap();
+	let handler_b = InputHandler::create(client.get_root(), Transform::none(), &field)
+		.unwrap()
+		.wrap(OrderTest("b", tx))
+		.unwrap();
+	let method = InputMethod::create(
+		client.get_root(),
+		Transform::none(),
+		InputDataType::Tip(Tip::default()),
+		&Datamap::empty(),
+	)
+	.unwrap();
+	method
+		.set_handler_order(&[handler_a.node().alias(), handler_b.node().alias()])
+		.unwrap();
+
+	let mut received = Vec::new();
+	let _ = tokio::time::timeout(core::time::Duration::from_millis(500), async {
+		while let Some(input) = rx.recv().await {
+			received.push(input);
+		}
+	})
+	.await;
+	assert!(received.contains(&("a", 0, true)));
+	assert!(received.contains(&("b", 1, false)));
+}
+
+#[tokio::test]
+async fn fusion_input_handler_wrapped_receives_input() {
+	use crate::client::Client;
+	use stardust_xr::{scenegraph::Scenegraph, schemas::flex::serialize};
+
+	struct InputRecorder(Vec<(u64, InputData)>);
+	impl InputHandlerHandler for InputRecorder {
+		fn input(&mut self, methods: Vec<InputMethodRef>, data: Vec<InputData>) {
+			let method_ids = methods.iter().map(|m| m.node().get_id().unwrap());
+			self.0.extend(method_ids.zip(data));
+		}
+	}
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+	let handler = InputHandler::from_id(&client, 1, true)
+		.wrap(InputRecorder(Vec::new()))
+		.unwrap();
+
+	let data = InputData {
+		id: 7,
+		input: InputDataType::Tip(Tip::default()),
+		distance: -0.05,
+		datamap: Datamap::from_typed(()).unwrap(),
+		order: 0,
+		captured: false,
+		region: None,
+	};
+	client
+		.scenegraph
+		.send_signal(
+			1,
+			INPUT_HANDLER_INPUT_CLIENT_OPCODE,
+			&serialize((vec![7_u64], vec![data.clone()])).unwrap(),
+			Vec::new(),
+		)
+		.unwrap();
+
+	assert_eq!(handler.lock_wrapped().0, vec![(7, data)]);
+}
+
+#[tokio::test]
+async fn fusion_input_method_get_handler_order() {
+	use crate::client::Client;
+	use stardust_xr::{mock::MockServer, schemas::flex::deserialize};
+
+	let server = MockServer::new();
+	server.respond(
+		INPUT_METHOD_GET_HANDLER_ORDER_SERVER_OPCODE,
+		&vec![3_u64, 2],
+	);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let method = InputMethod::from_id(&client, 1, true);
+	let handlers = [
+		InputHandler::from_id(&client, 5, true),
+		InputHandler::from_id(&client, 4, true),
+	];
+	method.set_handler_order(&handlers).unwrap();
+
+	let order = method.get_handler_order().await.unwrap();
+	let ids = order
+		.iter()
+		.map(|handler| handler.node().get_id().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(ids, vec![3, 2]);
+	assert!(order
+		.iter()
+		.all(|handler| !handler.node().is_owned().unwrap()));
+
+	let set_order = server
+		.signals()
+		.into_iter()
+		.find(|s| s.method == INPUT_METHOD_SET_HANDLER_ORDER_SERVER_OPCODE)
+		.unwrap();
+	assert_eq!(set_order.node, 1);
+	assert_eq!(
+		deserialize::<Vec<u64>>(&set_order.data).unwrap(),
+		vec![5, 4]
+	);
+}
+
+#[tokio::test]
+async fn fusion_input_handler_regions() {
+	use crate::client::Client;
+	use stardust_xr::{
+		mock::MockServer,
+		scenegraph::Scenegraph,
+		schemas::flex::{deserialize, serialize},
+	};
+
+	struct RegionRecorder(Vec<Option<String>>);
+	impl InputHandlerHandler for RegionRecorder {
+		fn input(&mut self, _methods: Vec<InputMethodRef>, data: Vec<InputData>) {
+			self.0.extend(data.into_iter().map(|d| d.region));
+		}
+	}
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	let handler = InputHandler::from_id(&client, 1, true)
+		.wrap(RegionRecorder(Vec::new()))
+		.unwrap();
+
+	let regions = vec![
+		InputRegion {
+			name: "left".to_string(),
+			transform: Transform::from_translation([-0.05, 0.0, 0.0]),
+			shape: Shape::Sphere(0.025),
+		},
+		InputRegion {
+			name: "right".to_string(),
+			transform: Transform::from_translation([0.05, 0.0, 0.0]),
+			shape: Shape::Sphere(0.025),
+		},
+	];
+	handler.node().set_regions(&regions).unwrap();
+	client.flush().await.unwrap();
+	tokio::time::sleep(core::time::Duration::from_millis(50)).await;
+	let sent = server
+		.signals()
+		.into_iter()
+		.find(|signal| signal.method == INPUT_HANDLER_SET_REGIONS_SERVER_OPCODE)
+		.unwrap();
+	let sent_regions: Vec<InputRegion> = deserialize(&sent.data).unwrap();
+	assert_eq!(sent_regions.len(), 2);
+	assert_eq!(sent_regions[1].name, "right");
+
+	// Stand in for the server reporting a method near each region
+	let data = ["left", "right"].map(|region| InputData {
+		id: 7,
+		input: InputDataType::Tip(Tip::default()),
+		distance: -0.01,
+		datamap: Datamap::empty(),
+		order: 0,
+		captured: false,
+		region: Some(region.to_string()),
+	});
+	client
+		.scenegraph
+		.send_signal(
+			1,
+			INPUT_HANDLER_INPUT_CLIENT_OPCODE,
+			&serialize((vec![7_u64, 7_u64], data.to_vec())).unwrap(),
+			Vec::new(),
+		)
+		.unwrap();
+
+	assert_eq!(
+		handler.lock_wrapped().0,
+		vec![Some("left".to_string()), Some("right".to_string())]
+	);
+}
+
+#[tokio::test]
+async fn fusion_input_method_lifetime_field() {
+	use crate::client::Client;
+
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	let field =
+		Field::create(client.get_root(), Transform::identity(), Shape::Sphere(0.1)).unwrap();
+	let method = InputMethod::create_with_lifetime_field(
+		client.get_root(),
+		Transform::none(),
+		InputDataType::Pointer(Pointer::default()),
+		&Datamap::empty(),
+		&field,
+	)
+	.unwrap();
+	assert!(method.get_handler_order().await.is_ok());
+
+	// Dropping the owned field destroys it on the server, which takes the method with it
+	drop(field);
+	assert!(method.get_handler_order().await.is_err());
+}
+
+#[test]
+fn grab_gesture_phases() {
+	let input = |origin: [f32; 3], grab: bool| InputData {
+		id: 7,
+		input: InputDataType::Tip(Tip {
+			origin: origin.into(),
+			..Default::default()
+		}),
+		distance: 0.0,
+		datamap: Datamap::from_typed(GrabData { grab }).unwrap(),
+		order: 0,
+		captured: false,
+		region: None,
+	};
+	#[derive(serde::Serialize)]
+	struct GrabData {
+		grab: bool,
+	}
+
+	let mut gesture = GrabGesture::new("grab");
+	assert_eq!(gesture.update(&[input([0.0; 3], false)]), None);
+	assert_eq!(
+		gesture.update(&[input([0.0; 3], true)]),
+		Some(GrabEvent::Started { method: 7 })
+	);
+	assert_eq!(gesture.grabbing(), Some(7));
+	assert_eq!(gesture.update(&[input([0.0; 3], true)]), None);
+
+	let Some(GrabEvent::Moving { delta }) = gesture.update(&[input([0.1, 0.0, -0.2], true)]) else {
+		panic!("Moving the grab should give a delta");
+	};
+	let translation = delta.translation.unwrap();
+	assert!((translation.x - 0.1).abs() < 1.0e-6 && (translation.z + 0.2).abs() < 1.0e-6);
+	assert!(Quat::from(delta.rotation.unwrap()).abs_diff_eq(Quat::IDENTITY, 1.0e-6));
+
+	assert_eq!(
+		gesture.update(&[input([0.1, 0.0, -0.2], false)]),
+		Some(GrabEvent::Ended)
+	);
+	assert_eq!(gesture.grabbing(), None);
+	// Input going away mid-grab ends it too
+	gesture.update(&[input([0.0; 3], true)]);
+	assert_eq!(gesture.update(&[]), Some(GrabEvent::Ended));
+}
+
+#[test]
+fn grab_gesture_consecutive_deltas() {
+	let input = |origin: Vec3| InputData {
+		id: 7,
+		input: InputDataType::Tip(Tip {
+			origin: origin.into(),
+			..Default::default()
+		}),
+		distance: 0.0,
+		datamap: Datamap::from_typed(GrabData { grab: true }).unwrap(),
+		order: 0,
+		captured: false,
+		region: None,
+	};
+	#[derive(serde::Serialize)]
+	struct GrabData {
+		grab: bool,
+	}
+	let translation = |event: Option<GrabEvent>| match event {
+		Some(GrabEvent::Moving { delta }) => Vec3::from(delta.translation.unwrap()),
+		event => panic!("Expected a delta, got {event:?}"),
+	};
+
+	// The tip moves 0.1 along x every frame, and each delta is applied to the handler like it should be
+	let mut gesture = GrabGesture::new("grab");
+	let mut handler = Vec3::ZERO;
+	gesture.update(&[input(Vec3::ZERO)]);
+	for frame in 1..=2 {
+		let tip = Vec3::X * 0.1 * frame as f32;
+		let delta = translation(gesture.update(&[input(tip - handler)]));
+		assert!(delta.abs_diff_eq(Vec3::X * 0.1, 1.0e-6), "{delta}");
+		handler += delta;
+	}
+	// Once the handler caught up, holding still is holding still
+	assert_eq!(gesture.update(&[input(Vec3::X * 0.2 - handler)]), None);
+
+	// A handler that stays put gets the movement since the last frame
+	let mut gesture = GrabGesture::new("grab").handler_moves(false);
+	gesture.update(&[input(Vec3::ZERO)]);
+	for frame in 1..=2 {
+		let delta = translation(gesture.update(&[input(Vec3::X * 0.1 * frame as f32)]));
+		assert!(delta.abs_diff_eq(Vec3::X * 0.1, 1.0e-6), "{delta}");
 	}
 }
+
+#[tokio::test]
+async fn fusion_input_method_merge_datamap() {
+	use super::client::Client;
+	use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+	struct Buttons {
+		grab: f32,
+		select: f32,
+	}
+	#[derive(serde::Serialize)]
+	struct Select {
+		select: f32,
+	}
+	struct DatamapTest(UnboundedSender<Datamap>);
+	impl InputHandlerHandler for DatamapTest {
+		fn input(&mut self, _methods: Vec<InputMethodRef>, data: Vec<InputData>) {
+			for data in data {
+				let _ = self.0.send(data.datamap);
+			}
+		}
+	}
+
+	let field =
+		Field::create(client.get_root(), Transform::identity(), Shape::Sphere(0.1)).unwrap();
+	let (tx, mut rx) = unbounded_channel();
+	let _handler = InputHandler::create(client.get_root(), Transform::none(), &field)
+		.unwrap()
+		.wrap(DatamapTest(tx))
+		.unwrap();
+	let method = InputMethod::create(
+		client.get_root(),
+		Transform::none(),
+		InputDataType::Tip(Tip::default()),
+		&Datamap::from_typed(Buttons {
+			grab: 1.0,
+			select: 0.0,
+		})
+		.unwrap(),
+	)
+	.unwrap();
+	method
+		.merge_datamap(&Datamap::from_typed(Select { select: 1.0 }).unwrap())
+		.unwrap();
+
+	let merged = tokio::time::timeout(core::time::Duration::from_secs(1), async {
+		while let Some(datamap) = rx.recv().await {
+			let buttons: Buttons = datamap.deserialize().unwrap();
+			if buttons.select == 1.0 {
+				return buttons;
+			}
+		}
+		panic!("Handler stopped getting input");
+	})
+	.await
+	.expect("Merged datamap never arrived");
+	assert_eq!(
+		merged,
+		Buttons {
+			grab: 1.0,
+			select: 1.0
+		}
+	);
+}
+
+#[tokio::test]
+async fn fusion_input_handler_capture_timeout() {
+	use crate::client::Client;
+	use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+	struct CaptureOnce {
+		handler: InputHandler,
+		requested: bool,
+		captured: UnboundedSender<bool>,
+	}
+	impl InputHandlerHandler for CaptureOnce {
+		fn input(&mut self, methods: Vec<InputMethodRef>, data: Vec<InputData>) {
+			for (method, data) in methods.iter().zip(data) {
+				let _ = self.captured.send(data.captured);
+				// Capture once and then never reaffirm it, like a client that hung
+				if !self.requested {
+					self.requested = true;
+					method.request_capture(&self.handler).unwrap();
+				}
+			}
+		}
+	}
+
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	let field =
+		Field::create(client.get_root(), Transform::identity(), Shape::Sphere(0.1)).unwrap();
+	let handler = InputHandler::create(client.get_root(), Transform::none(), &field).unwrap();
+	handler.set_capture_timeout(Some(0.2)).unwrap();
+	let (tx, mut rx) = unbounded_channel();
+	let _handler = handler
+		.alias()
+		.wrap(CaptureOnce {
+			handler,
+			requested: false,
+			captured: tx,
+		})
+		.unwrap();
+	let _method = InputMethod::create(
+		client.get_root(),
+		Transform::none(),
+		InputDataType::Tip(Tip::default()),
+		&Datamap::empty(),
+	)
+	.unwrap();
+
+	async fn wait_for(
+		rx: &mut tokio::sync::mpsc::UnboundedReceiver<bool>,
+		captured: bool,
+	) -> Result<(), tokio::time::error::Elapsed> {
+		tokio::time::timeout(core::time::Duration::from_secs(2), async {
+			while rx.recv().await != Some(captured) {}
+		})
+		.await
+	}
+	wait_for(&mut rx, true)
+		.await
+		.expect("Method was never captured");
+	wait_for(&mut rx, false)
+		.await
+		.expect("Capture was never released after the timeout");
+}
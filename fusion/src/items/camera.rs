@@ -40,6 +40,12 @@ impl CameraItemUi {
 
 impl_aspects!(CameraItemAcceptor: OwnedAspect, SpatialRefAspect, SpatialAspect, ItemAcceptorAspect);
 impl CameraItemAcceptor {
+	/// Get the camera items captured into this acceptor from now on, to await with `AcceptorCaptures::next_capture`.
+	pub fn captures(&self) -> NodeResult<AcceptorCaptures<CameraItem>> {
+		let (captures, forwarder) = AcceptorCaptures::new();
+		self.subscribe_camera_item_acceptor_events(&forwarder)?;
+		Ok(captures)
+	}
 	pub fn create(
 		spatial_parent: &impl SpatialRefAspect,
 		transform: Transform,
@@ -55,3 +61,104 @@ impl CameraItemAcceptor {
 		)
 	}
 }
+impl CameraItemAcceptorHandler for CaptureForwarder<CameraItem> {
+	fn capture_item(&mut self, item: CameraItem) {
+		let _ = self.0.send(item);
+	}
+}
+
+#[tokio::test]
+async fn fusion_camera_item_frame_fd() {
+	use stardust_xr::{scenegraph::Scenegraph, schemas::flex::serialize};
+	use std::{
+		io::{Read, Write},
+		os::{fd::OwnedFd, unix::net::UnixStream},
+	};
+
+	struct FrameReceiver(Option<(OwnedFd, Vector2<u32>)>);
+	impl CameraItemHandler for FrameReceiver {
+		fn frame(&mut self, dmabuf: OwnedFd, px_size: Vector2<u32>) {
+			self.0 = Some((dmabuf, px_size));
+		}
+	}
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+	let item = CameraItem::from_id(&client, 1, true)
+		.wrap(FrameReceiver(None))
+		.unwrap();
+
+	// Stands in for the dma-buf, anything readable through a file descriptor works
+	let (mut frame_writer, frame_reader) = UnixStream::pair().unwrap();
+	frame_writer.write_all(b"frame").unwrap();
+	drop(frame_writer);
+	let px_size: Vector2<u32> = [1920, 1080].into();
+	client
+		.scenegraph
+		.send_signal(
+			1,
+			CAMERA_ITEM_FRAME_CLIENT_OPCODE,
+			&serialize((0_u32, px_size)).unwrap(),
+			vec![frame_reader.into()],
+		)
+		.unwrap();
+
+	let (dmabuf, received_size) = item.lock_wrapped().0.take().expect("No frame received");
+	assert_eq!(received_size, px_size);
+	let mut contents = String::new();
+	UnixStream::from(dmabuf)
+		.read_to_string(&mut contents)
+		.unwrap();
+	assert_eq!(contents, "frame");
+}
+
+#[tokio::test]
+async fn fusion_camera_item_list_existing() {
+	use stardust_xr::mock::MockServer;
+
+	let server = MockServer::new();
+	server.respond(INTERFACE_LIST_CAMERA_ITEMS_SERVER_OPCODE, &vec![5_u64, 9]);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let items = list_camera_items(&client).await.unwrap();
+	let ids = items
+		.iter()
+		.map(|item| item.node().get_id().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(ids, vec![5, 9]);
+	// Someone else's items, so they can't be destroyed through these
+	assert!(items.iter().all(|item| !item.node().is_owned().unwrap()));
+	assert!(server
+		.method_calls()
+		.iter()
+		.any(|call| call.node == INTERFACE_NODE_ID
+			&& call.method == INTERFACE_LIST_CAMERA_ITEMS_SERVER_OPCODE));
+}
+
+#[tokio::test]
+async fn fusion_camera_item_acceptor_captures() {
+	use stardust_xr::{scenegraph::Scenegraph, schemas::flex::serialize};
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+	let acceptor = CameraItemAcceptor::from_id(&client, 1, true);
+	let mut captures = acceptor.captures().unwrap();
+
+	// The server telling the acceptor it captured item 5
+	client
+		.scenegraph
+		.send_signal(
+			1,
+			CAMERA_ITEM_ACCEPTOR_CAPTURE_ITEM_CLIENT_OPCODE,
+			&serialize(5_u64).unwrap(),
+			Vec::new(),
+		)
+		.unwrap();
+
+	let item = tokio::time::timeout(std::time::Duration::from_secs(1), captures.next_capture())
+		.await
+		.expect("Capture never arrived");
+	assert_eq!(item.node().get_id().unwrap(), 5);
+}
@@ -6,7 +6,45 @@ use crate::{
 	node::{NodeResult, OwnedAspect},
 	spatial::{SpatialAspect, SpatialRefAspect},
 };
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tokio::sync::mpsc;
 
 stardust_xr_fusion_codegen::codegen_item_protocol!();
 impl_aspects!(Item: OwnedAspect, SpatialRefAspect, SpatialAspect);
 impl_aspects!(ItemAcceptor: OwnedAspect, SpatialRefAspect, SpatialAspect);
+
+/// Items as they get captured into an acceptor, from `captures` on `CameraItemAcceptor` or `PanelItemAcceptor`.
+///
+/// Captures are received alongside any handler from `wrap`, not instead of it. Stops once this is dropped.
+pub struct AcceptorCaptures<C> {
+	receiver: mpsc::UnboundedReceiver<C>,
+	_forwarder: Arc<Mutex<CaptureForwarder<C>>>,
+}
+impl<C> AcceptorCaptures<C> {
+	/// Also returns the handler to subscribe to the acceptor's events, which sends the captures here.
+	pub(crate) fn new() -> (Self, Arc<Mutex<CaptureForwarder<C>>>) {
+		let (sender, receiver) = mpsc::unbounded_channel();
+		let forwarder = Arc::new(Mutex::new(CaptureForwarder(sender)));
+		(
+			AcceptorCaptures {
+				receiver,
+				_forwarder: forwarder.clone(),
+			},
+			forwarder,
+		)
+	}
+	/// Wait for the next item captured into the acceptor.
+	///
+	/// Never returns if nothing gets captured anymore.
+	///
+	/// # Cancel safety
+	/// This is cancel safe, so it can be used in `select!`. If the future is dropped before it completes, no capture is lost.
+	pub async fn next_capture(&mut self) -> C {
+		// we hold the forwarder and so the sender, so the channel can't close
+		self.receiver.recv().await.unwrap()
+	}
+}
+
+/// Handler subscribed to an acceptor's events for `AcceptorCaptures`.
+pub(crate) struct CaptureForwarder<C>(mpsc::UnboundedSender<C>);
@@ -7,6 +7,7 @@ use crate::{
 	node::{Node, NodeResult, NodeType, OwnedAspect},
 	spatial::{SpatialAspect, SpatialRefAspect, Transform},
 };
+use stardust_xr::values::Vector2;
 use std::sync::Arc;
 
 stardust_xr_fusion_codegen::codegen_item_panel_protocol!();
@@ -30,4 +31,187 @@ impl PanelItemAcceptor {
 	) -> NodeResult<Self> {
 		create_panel_item_acceptor(client, client.generate_id(), parent, transform, field)
 	}
+	/// Get the panel items captured into this acceptor from now on along with their initial data, to await with `AcceptorCaptures::next_capture`.
+	pub fn captures(&self) -> NodeResult<AcceptorCaptures<(PanelItem, PanelItemInitData)>> {
+		let (captures, forwarder) = AcceptorCaptures::new();
+		self.subscribe_panel_item_acceptor_events(&forwarder)?;
+		Ok(captures)
+	}
+}
+impl PanelItemAcceptorHandler for CaptureForwarder<(PanelItem, PanelItemInitData)> {
+	fn capture_item(&mut self, item: PanelItem, initial_data: PanelItemInitData) {
+		let _ = self.0.send((item, initial_data));
+	}
+}
+
+impl ToplevelInfo {
+	/// Clamp `requested` to the toplevel's recommended minimum and maximum size, such as to answer a `ToplevelResizeRequest`.
+	///
+	/// The minimum is rounded up and the maximum down to whole pixels, if the minimum is bigger than the maximum the minimum wins.
+	pub fn clamp_size(&self, requested: Vector2<u32>) -> Vector2<u32> {
+		let clamp_axis = |requested: u32, min: Option<f32>, max: Option<f32>| {
+			let requested = max.map_or(requested, |max| requested.min(max.floor() as u32));
+			min.map_or(requested, |min| requested.max(min.ceil() as u32))
+		};
+		Vector2 {
+			x: clamp_axis(
+				requested.x,
+				self.min_size.map(|s| s.x),
+				self.max_size.map(|s| s.x),
+			),
+			y: clamp_axis(
+				requested.y,
+				self.min_size.map(|s| s.y),
+				self.max_size.map(|s| s.y),
+			),
+		}
+	}
+	/// The part of the surface that's actually window content, excluding things like client side shadows.
+	pub fn content_geometry(&self) -> Geometry {
+		self.logical_rectangle.clone()
+	}
+}
+
+/// Common pointer buttons for `PanelItemAspect::pointer_button`, so you don't need the `input_event_codes` crate for them.
+///
+/// Convert with `.into()` to get the raw button code, any other code from `input_event_codes` can still be passed directly as a `u32`.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum PointerButton {
+	/// `BTN_LEFT`
+	Left,
+	/// `BTN_RIGHT`
+	Right,
+	/// `BTN_MIDDLE`
+	Middle,
+	/// `BTN_SIDE`, which is what mice send for the "back" thumb button.
+	Back,
+	/// `BTN_EXTRA`, which is what mice send for the "forward" thumb button.
+	Forward,
+}
+impl From<PointerButton> for u32 {
+	fn from(button: PointerButton) -> Self {
+		match button {
+			PointerButton::Left => 0x110,
+			PointerButton::Right => 0x111,
+			PointerButton::Middle => 0x112,
+			PointerButton::Back => 0x113,
+			PointerButton::Forward => 0x114,
+		}
+	}
+}
+
+#[test]
+fn pointer_button_codes() {
+	assert_eq!(
+		u32::from(PointerButton::Left),
+		input_event_codes::BTN_LEFT!()
+	);
+	assert_eq!(
+		u32::from(PointerButton::Right),
+		input_event_codes::BTN_RIGHT!()
+	);
+	assert_eq!(
+		u32::from(PointerButton::Middle),
+		input_event_codes::BTN_MIDDLE!()
+	);
+	assert_eq!(
+		u32::from(PointerButton::Back),
+		input_event_codes::BTN_SIDE!()
+	);
+	assert_eq!(
+		u32::from(PointerButton::Forward),
+		input_event_codes::BTN_EXTRA!()
+	);
+}
+
+#[test]
+fn toplevel_info_clamp_size() {
+	let toplevel = ToplevelInfo {
+		parent: None,
+		title: None,
+		app_id: None,
+		size: [800, 600].into(),
+		min_size: Some([200.5, 100.0].into()),
+		max_size: Some([1920.0, 1080.9].into()),
+		logical_rectangle: Geometry {
+			origin: [10, 20].into(),
+			size: [780, 560].into(),
+		},
+	};
+	assert_eq!(toplevel.clamp_size([1024, 768].into()), [1024, 768].into());
+	// below the minimum
+	assert_eq!(toplevel.clamp_size([0, 50].into()), [201, 100].into());
+	// above the maximum
+	assert_eq!(
+		toplevel.clamp_size([4000, 2000].into()),
+		[1920, 1080].into()
+	);
+	let unconstrained = ToplevelInfo {
+		min_size: None,
+		max_size: None,
+		..toplevel.clone()
+	};
+	assert_eq!(unconstrained.clamp_size([0, 5000].into()), [0, 5000].into());
+
+	assert_eq!(toplevel.content_geometry(), toplevel.logical_rectangle);
+}
+
+#[tokio::test]
+async fn fusion_panel_item_preferred_scale() {
+	use stardust_xr::{mock::MockServer, schemas::flex::deserialize};
+
+	let server = MockServer::new();
+	server.respond(PANEL_ITEM_GET_PREFERRED_SCALE_SERVER_OPCODE, &2.0_f32);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let panel_item = PanelItem::from_id(&client, 3, false);
+	panel_item.set_preferred_scale(0.5).unwrap();
+	assert_eq!(panel_item.get_preferred_scale().await.unwrap(), 2.0);
+	let set_scale = server
+		.signals()
+		.into_iter()
+		.find(|s| s.method == PANEL_ITEM_SET_PREFERRED_SCALE_SERVER_OPCODE)
+		.unwrap();
+	assert_eq!(set_scale.node, 3);
+	assert_eq!(deserialize::<f32>(&set_scale.data).unwrap(), 0.5);
+}
+
+#[tokio::test]
+async fn fusion_panel_item_get_toplevel_info() {
+	use stardust_xr::mock::MockServer;
+
+	let toplevel_info = ToplevelInfo {
+		parent: None,
+		title: Some("Terminal".to_string()),
+		app_id: Some("org.example.Terminal".to_string()),
+		size: [800, 600].into(),
+		min_size: None,
+		max_size: None,
+		logical_rectangle: Geometry {
+			origin: [0, 0].into(),
+			size: [800, 600].into(),
+		},
+	};
+	let child = ChildInfo {
+		id: 2,
+		parent: SurfaceId::Toplevel(()),
+		geometry: Geometry {
+			origin: [10, 20].into(),
+			size: [100, 50].into(),
+		},
+		z_order: 1,
+		receives_input: true,
+	};
+	let server = MockServer::new();
+	server.respond(PANEL_ITEM_GET_TOPLEVEL_INFO_SERVER_OPCODE, &toplevel_info);
+	server.respond(PANEL_ITEM_GET_CHILDREN_SERVER_OPCODE, &vec![child.clone()]);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let panel_item = PanelItem::from_id(&client, 3, false);
+	assert_eq!(panel_item.get_toplevel_info().await.unwrap(), toplevel_info);
+	assert_eq!(panel_item.get_children().await.unwrap(), vec![child]);
 }
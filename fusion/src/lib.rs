@@ -1,4 +1,14 @@
 //! A library for Stardust XR clients to use with abstractions over the client, nodes, and event loop.
+//!
+//! # Protocol types in other formats
+//! The structs, enums and unions generated from the protocol use plain serde derives, so besides the flex wire format they work with any serde format,
+//! such as `serde_json` for config files or debug exports. In self-describing formats like JSON:
+//! - Structs are objects with their field names, and optional fields are `null` when unset.
+//! - Vectors and quaternions are arrays (`[x, y, z]` and `[x, y, z, w]`).
+//! - Enums are the index of their variant.
+//! - Unions are untagged, so the option is picked from the shape of the value when deserializing.
+//!   Options whose values look the same (such as `int` and `uint`) come back as whichever is declared first.
+//! - Colors are objects of their components, and resources are `"namespace:path"` strings.
 
 #![allow(dead_code)]
 #![allow(clippy::derivable_impls)]
@@ -81,6 +91,19 @@ impl<N: NodeType, H: Send + Sync + 'static> HandlerWrapper<N, H> {
 		})
 	}
 	#[allow(clippy::type_complexity)]
+	pub(crate) fn add_unknown_signal_handler(
+		&self,
+		parse: fn(Arc<Mutex<H>>, u64, &[u8]) -> Result<()>,
+	) -> Result<(), NodeError> {
+		let handler = Arc::downgrade(&self.wrapped);
+		self.node.node().set_unknown_signal(move |id, data, _fds| {
+			let Some(handler) = handler.upgrade() else {
+				return Err(anyhow!("Handler broken"));
+			};
+			parse(handler, id, data)
+		})
+	}
+	#[allow(clippy::type_complexity)]
 	pub(crate) fn add_handled_method(
 		&self,
 		id: u64,
@@ -100,6 +123,86 @@ impl<N: NodeType, H: Send + Sync + 'static> HandlerWrapper<N, H> {
 	}
 }
 
+/// Register `handler` as an extra subscriber to a signal on `node`, next to any handler from `wrap`.
+#[allow(clippy::type_complexity)]
+pub(crate) fn subscribe_handled_signal<N: NodeType, H: Send + Sync + 'static>(
+	node: &N,
+	handler: &Arc<Mutex<H>>,
+	id: u64,
+	parse: fn(Arc<N>, Arc<Mutex<H>>, &[u8], Vec<OwnedFd>) -> Result<()>,
+) -> Result<(), NodeError> {
+	let alias = Arc::new(node.alias());
+	let handler = Arc::downgrade(handler);
+	node.node().add_signal_subscriber(id, move |data, fds| {
+		let handler = handler.upgrade()?;
+		Some(parse(alias.clone(), handler, data, fds))
+	})
+}
+
+/// A signal from the server to one of the nodes in a [`NodeEventSet`], still serialized since the set can hold any kind of node.
+#[derive(Debug)]
+pub struct AnyEvent {
+	/// The opcode of the signal, such as `TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE`.
+	pub opcode: u64,
+	pub data: Vec<u8>,
+	pub fds: Vec<OwnedFd>,
+}
+impl AnyEvent {
+	/// Deserialize the signal's arguments, a tuple if it has more than one.
+	pub fn deserialize<'a, T: serde::Deserialize<'a>>(
+		&'a self,
+	) -> Result<T, stardust_xr::schemas::flex::flexbuffers::DeserializationError> {
+		stardust_xr::schemas::flex::deserialize(&self.data)
+	}
+}
+
+/// Collects signals from many nodes into one stream, so they can all be awaited in a single loop instead of a `select!` over each one.
+///
+/// Signals are received alongside any handler from `wrap`, not instead of it.
+#[derive(Debug)]
+pub struct NodeEventSet {
+	sender: tokio::sync::mpsc::UnboundedSender<(u64, AnyEvent)>,
+	receiver: tokio::sync::mpsc::UnboundedReceiver<(u64, AnyEvent)>,
+}
+impl NodeEventSet {
+	pub fn new() -> Self {
+		let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+		NodeEventSet { sender, receiver }
+	}
+	/// Receive the signals with `opcodes` sent to `node`. Stops once this set is dropped.
+	pub fn insert(&self, node: &impl NodeType, opcodes: &[u64]) -> Result<(), NodeError> {
+		let node = node.node();
+		let node_id = node.get_id()?;
+		for &opcode in opcodes {
+			let sender = self.sender.clone();
+			node.add_signal_subscriber(opcode, move |data, fds| {
+				let event = AnyEvent {
+					opcode,
+					data: data.to_vec(),
+					fds,
+				};
+				sender.send((node_id, event)).ok().map(Ok)
+			})?;
+		}
+		Ok(())
+	}
+	/// Wait for the next signal from any node in the set, along with the ID of the node it was sent to.
+	///
+	/// Never returns if no nodes are inserted or none of them get any more signals.
+	///
+	/// # Cancel safety
+	/// This is cancel safe, so it can be used in `select!`. If the future is dropped before it completes, no signal is taken out of the set.
+	pub async fn next(&mut self) -> (u64, AnyEvent) {
+		// the set always holds a sender, so the channel can't close
+		self.receiver.recv().await.unwrap()
+	}
+}
+impl Default for NodeEventSet {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 #[macro_export]
 macro_rules! impl_aspects {
     ($node:ident: $( $aspect:ident ),+) => {
@@ -139,3 +242,132 @@ macro_rules! handle_action {
             .unwrap();
     };
 }
+
+#[tokio::test]
+async fn fusion_node_event_set() {
+	use crate::{
+		client::Client,
+		spatial::{
+			Spatial, Transform, TransformSubscription,
+			TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE,
+		},
+	};
+	use stardust_xr::{mock::MockServer, scenegraph::Scenegraph, schemas::flex::serialize};
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	let spatial = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	let a = TransformSubscription::create(&spatial, client.get_root()).unwrap();
+	let b = TransformSubscription::create(client.get_root(), &spatial).unwrap();
+
+	let mut set = NodeEventSet::new();
+	set.insert(
+		&a,
+		&[TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE],
+	)
+	.unwrap();
+	set.insert(
+		&b,
+		&[TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE],
+	)
+	.unwrap();
+
+	let ids = [a.node().get_id().unwrap(), b.node().get_id().unwrap()];
+	for (i, id) in ids.into_iter().enumerate() {
+		let transform = Transform::from_translation([i as f32, 0.0, 0.0]);
+		client
+			.scenegraph
+			.send_signal(
+				id,
+				TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE,
+				&serialize(transform).unwrap(),
+				Vec::new(),
+			)
+			.unwrap();
+	}
+
+	let mut received = Vec::new();
+	while received.len() < ids.len() {
+		let (id, event) = tokio::time::timeout(std::time::Duration::from_secs(1), set.next())
+			.await
+			.unwrap();
+		assert_eq!(
+			event.opcode,
+			TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE
+		);
+		let transform: Transform = event.deserialize().unwrap();
+		received.push((id, transform.translation.unwrap().x));
+	}
+	received.sort_by_key(|(id, _)| *id);
+	assert_eq!(received, vec![(ids[0], 0.0), (ids[1], 1.0)]);
+}
+
+#[tokio::test]
+async fn fusion_node_event_set_cancel_safe() {
+	use crate::{
+		client::Client,
+		spatial::{
+			Spatial, Transform, TransformSubscription,
+			TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE,
+		},
+	};
+	use stardust_xr::{mock::MockServer, scenegraph::Scenegraph, schemas::flex::serialize};
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	let spatial = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	let subscription = TransformSubscription::create(&spatial, client.get_root()).unwrap();
+	let id = subscription.node().get_id().unwrap();
+	let mut set = NodeEventSet::new();
+	set.insert(
+		&subscription,
+		&[TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE],
+	)
+	.unwrap();
+
+	let send = |x: f32| {
+		client
+			.scenegraph
+			.send_signal(
+				id,
+				TRANSFORM_SUBSCRIPTION_TRANSFORM_UPDATED_CLIENT_OPCODE,
+				&serialize(Transform::from_translation([x, 0.0, 0.0])).unwrap(),
+				Vec::new(),
+			)
+			.unwrap();
+	};
+	let received_x = |event: AnyEvent| {
+		let transform: Transform = event.deserialize().unwrap();
+		transform.translation.unwrap().x
+	};
+
+	// Polled while empty, then a signal arrives before it's polled again and it's dropped
+	{
+		let mut next = std::pin::pin!(set.next());
+		let poll = std::future::poll_fn(|cx| {
+			std::task::Poll::Ready(std::future::Future::poll(next.as_mut(), cx))
+		})
+		.await;
+		assert!(poll.is_pending());
+		send(1.0);
+	}
+	// Dropped by losing a select while the signal is ready
+	send(2.0);
+	tokio::select! {
+		biased;
+		_ = std::future::ready(()) => (),
+		_ = set.next() => panic!("The ready branch should win"),
+	}
+
+	for expected in [1.0, 2.0] {
+		let (event_id, event) = tokio::time::timeout(std::time::Duration::from_secs(1), set.next())
+			.await
+			.expect("A signal was lost when a receive was cancelled");
+		assert_eq!(event_id, id);
+		assert_eq!(received_x(event), expected);
+	}
+}
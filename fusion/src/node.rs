@@ -5,16 +5,21 @@ use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
 use serde::{de::DeserializeOwned, Serialize, Serializer};
 use stardust_xr::{
-	messenger::{Message, MessengerError},
-	schemas::flex::{
-		deserialize, flexbuffers::DeserializationError, serialize, FlexSerializeError,
+	messenger::{Message, MessengerError, RemoteMethodError},
+	schemas::{
+		flex::{deserialize, flexbuffers::DeserializationError, serialize, FlexSerializeError},
+		Schema,
 	},
 };
 use std::{
+	any::Any,
 	fmt::Debug,
 	future::Future,
 	os::fd::OwnedFd,
-	sync::{Arc, Weak},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Weak,
+	},
 	vec::Vec,
 };
 use thiserror::Error;
@@ -42,8 +47,27 @@ pub enum NodeError {
 	/// The server returned an error on a method return.
 	#[error("Server returned an error: {e}")]
 	ReturnedError { e: String },
+	/// The server returned an error with a code, for errors meant to be handled rather than just shown.
+	/// What the codes and `data` mean is up to the method, see `NodeError::error_code`.
+	#[error("Server returned error {code}: {e}")]
+	ReturnedErrorCode { e: String, code: u32, data: Vec<u8> },
 	#[error("Attempted to register to a singleton twice")]
 	OverrideSingleton,
+	/// A builder was built without setting one of its required arguments.
+	#[error("Required argument {name} was not set")]
+	MissingArgument { name: &'static str },
+	/// A file descriptor argument referred to one that wasn't sent with the message, or one already used by another argument.
+	#[error("File descriptor index is invalid")]
+	InvalidFd,
+	/// Only the client that created the node can do this, and this node was imported or received from the server.
+	#[error("Node is not owned by this client")]
+	NotOwned,
+	/// Two values of different kinds can't be blended between, like a box and a sphere shape.
+	#[error("Can't interpolate between different kinds of values")]
+	IncompatibleInterpolation,
+	/// Setting this spatial parent would parent a spatial under itself or one of its descendants.
+	#[error("Spatial parent would create a cycle")]
+	WouldCreateCycle,
 	/// The given data is not a valid flexbuffer map.
 	#[error("Map is not a valid flexbuffer map at the root")]
 	MapInvalid,
@@ -68,6 +92,29 @@ impl From<String> for NodeError {
 		NodeError::ReturnedError { e }
 	}
 }
+impl From<RemoteMethodError> for NodeError {
+	fn from(error: RemoteMethodError) -> Self {
+		match error.code {
+			Some((code, data)) => NodeError::ReturnedErrorCode {
+				e: error.message,
+				code,
+				data,
+			},
+			None => NodeError::ReturnedError { e: error.message },
+		}
+	}
+}
+impl NodeError {
+	/// If the server returned an error with a code, get the code along with its data deserialized as `T`, such as an enum of the ways the method can fail.
+	pub fn error_code<T: DeserializeOwned>(
+		&self,
+	) -> Option<(u32, Result<T, DeserializationError>)> {
+		match self {
+			NodeError::ReturnedErrorCode { code, data, .. } => Some((*code, deserialize(data))),
+			_ => None,
+		}
+	}
+}
 
 /// Common methods all nodes share, to make them easier to use.
 // #[enum_dispatch(FieldType)]
@@ -81,6 +128,9 @@ pub trait NodeType: Sized + Send + Sync + 'static {
 	}
 	/// Set whether the node is active or not. This has different effects depending on the node.
 	fn set_enabled(&self, enabled: bool) -> Result<(), NodeError> {
+		if !self.node().is_owned()? {
+			return Err(NodeError::NotOwned);
+		}
 		self.node()
 			.send_remote_signal(OWNED_SET_ENABLED_SERVER_OPCODE, &enabled)
 	}
@@ -91,13 +141,47 @@ pub trait NodeType: Sized + Send + Sync + 'static {
 	fn alias(&self) -> Self
 	where
 		Self: Sized;
+
+	/// Attach your own data to this node, such as a label or some state, replacing anything attached before.
+	/// It never leaves the client and is shared with every alias of the node.
+	///
+	/// The data lives as long as the node, so data holding an owned handle to the node itself makes a cycle that never gets dropped or destroyed.
+	/// Store an `alias` of the node instead, which only refers to it weakly.
+	fn set_user_data<T: Any + Send + Sync>(&self, data: T) -> NodeResult<()> {
+		self.node()
+			.internals()?
+			.user_data
+			.lock()
+			.replace(Arc::new(data));
+		Ok(())
+	}
+	/// Get the data attached with `set_user_data`, if there is any and it's a `T`.
+	fn user_data<T: Any + Send + Sync>(&self) -> NodeResult<Option<Arc<T>>> {
+		let user_data = self.node().internals()?.user_data.lock().clone();
+		Ok(user_data.and_then(|data| data.downcast().ok()))
+	}
+	/// Silently drop signals this node has no handler or subscriber for, instead of reporting each one back to the server as an error.
+	/// Meant for nodes that are created and never looked at again, like a fire-and-forget sound. The dropped signals are never queued, even with dispatch threads.
+	fn discard_unhandled_signals(&self, discard: bool) -> NodeResult<()> {
+		self.node()
+			.internals()?
+			.discard_unhandled_signals
+			.store(discard, Ordering::Relaxed);
+		Ok(())
+	}
 }
+impl<N: NodeType> NodeAspect for N {}
 
 type Signal = dyn Fn(&[u8], Vec<OwnedFd>) -> color_eyre::eyre::Result<()> + Send + Sync + 'static;
 type Method = dyn Fn(&[u8], Vec<OwnedFd>) -> color_eyre::eyre::Result<(Vec<u8>, Vec<OwnedFd>)>
 	+ Send
 	+ Sync
 	+ 'static;
+type UnknownSignal =
+	dyn Fn(u64, &[u8], Vec<OwnedFd>) -> color_eyre::eyre::Result<()> + Send + Sync + 'static;
+/// Extra receiver of a signal, returns `None` once whatever it forwards to is gone so it can be pruned.
+type SignalSubscriber =
+	dyn Fn(&[u8], Vec<OwnedFd>) -> Option<color_eyre::eyre::Result<()>> + Send + Sync + 'static;
 
 pub type NodeResult<O> = Result<O, NodeError>;
 
@@ -107,6 +191,12 @@ pub struct NodeInternals {
 	pub(crate) id: u64,
 	pub(crate) local_signals: Mutex<FxHashMap<u64, Arc<Signal>>>,
 	pub(crate) local_methods: Mutex<FxHashMap<u64, Arc<Method>>>,
+	pub(crate) signal_subscribers: Mutex<FxHashMap<u64, Vec<Arc<SignalSubscriber>>>>,
+	pub(crate) unknown_signal: Mutex<Option<Arc<UnknownSignal>>>,
+	/// Set with `NodeType::set_user_data`.
+	pub(crate) user_data: Mutex<Option<Arc<dyn Any + Send + Sync>>>,
+	/// Set with `NodeType::discard_unhandled_signals`.
+	pub(crate) discard_unhandled_signals: AtomicBool,
 	pub(crate) owned: bool,
 }
 impl Drop for NodeInternals {
@@ -121,6 +211,7 @@ impl Drop for NodeInternals {
 				);
 			}
 			client.scenegraph.remove_node(self.id);
+			client.transform_cache.remove_node(self.id);
 		}
 	}
 }
@@ -142,6 +233,32 @@ impl Node {
 			.insert(id, Arc::new(signal));
 		Ok(())
 	}
+	/// Add another receiver to a signal alongside the one from `add_local_signal`, so several consumers get the same signal.
+	/// The subscriber is removed once it returns `None`.
+	pub fn add_signal_subscriber<F>(&self, id: u64, subscriber: F) -> Result<(), NodeError>
+	where
+		F: Fn(&[u8], Vec<OwnedFd>) -> Option<color_eyre::eyre::Result<()>> + Send + Sync + 'static,
+	{
+		self.internals()?
+			.signal_subscribers
+			.lock()
+			.entry(id)
+			.or_default()
+			.push(Arc::new(subscriber));
+		Ok(())
+	}
+
+	/// Set what gets called when the server sends a signal with no local signal or subscriber for its id, instead of it failing with `ScenegraphError::SignalNotFound`.
+	pub fn set_unknown_signal<F>(&self, signal: F) -> Result<(), NodeError>
+	where
+		F: Fn(u64, &[u8], Vec<OwnedFd>) -> color_eyre::eyre::Result<()> + Send + Sync + 'static,
+	{
+		self.internals()?
+			.unknown_signal
+			.lock()
+			.replace(Arc::new(signal));
+		Ok(())
+	}
 
 	/// Add a signal to the node so that the server can send a message to it and get a response back. Not needed unless implementing functionality Fusion does not already have.
 	pub fn add_local_method<F>(&self, id: u64, method: F) -> Result<(), NodeError>
@@ -178,6 +295,10 @@ impl Node {
 		Ok(self.internals()?.id)
 	}
 
+	/// Check if this node was created by this client, as opposed to imported or received from the server.
+	pub fn is_owned(&self) -> Result<bool, NodeError> {
+		Ok(self.internals()?.owned)
+	}
 	/// Check if this node is still alive.
 	pub fn alive(&self) -> bool {
 		match self {
@@ -194,6 +315,14 @@ impl Node {
 			Vec::new(),
 		)
 	}
+	/// Like `send_remote_signal`, but only for data made of `Schema` types, so anything not meant for the wire fails to compile.
+	pub fn send_remote_signal_checked<S: Serialize + Schema>(
+		&self,
+		signal: u64,
+		data: &S,
+	) -> Result<(), NodeError> {
+		self.send_remote_signal(signal, data)
+	}
 	/// Send a signal to the node on the server with raw data (like when sending flatbuffers over). Not needed unless implementing functionality Fusion does not already have.
 	pub fn send_remote_signal_raw(
 		&self,
@@ -201,9 +330,12 @@ impl Node {
 		data: &[u8],
 		fds: Vec<OwnedFd>,
 	) -> Result<(), NodeError> {
-		self.client()?
+		let id = self.get_id()?;
+		let client = self.client()?;
+		client.transform_cache.invalidate(id, signal);
+		client
 			.message_sender_handle
-			.signal(self.get_id()?, signal, data, fds)
+			.signal(id, signal, data, fds)
 			.map_err(|e| NodeError::MessengerError { e })
 	}
 	/// Execute a method on the node on the server. Not needed unless implementing functionality Fusion does not already have.
@@ -217,6 +349,14 @@ impl Node {
 		let data = future.await?;
 		deserialize(&data.into_message()).map_err(|e| NodeError::Deserialization { e })
 	}
+	/// Like `execute_remote_method`, but only for data made of `Schema` types, so anything not meant for the wire fails to compile.
+	pub async fn execute_remote_method_checked<S: Serialize + Schema, D: DeserializeOwned>(
+		&self,
+		method: u64,
+		send_data: &S,
+	) -> Result<D, NodeError> {
+		self.execute_remote_method(method, send_data).await
+	}
 	/// Execute a method on the node on the server with raw data (like when sending over flatbuffers). Not needed unless implementing functionality Fusion does not already have.
 	pub fn execute_remote_method_raw(
 		&self,
@@ -227,10 +367,10 @@ impl Node {
 		let future = self
 			.client()?
 			.message_sender_handle
-			.method(self.get_id()?, method, data, fds)
+			.method_typed(self.get_id()?, method, data, fds)
 			.map_err(|e| NodeError::MessengerError { e })?;
 
-		Ok(async move { future.await.map_err(|e| NodeError::ReturnedError { e }) })
+		Ok(async move { future.await.map_err(NodeError::from) })
 	}
 }
 impl NodeType for Node {
@@ -252,6 +392,10 @@ impl NodeType for Node {
 			id,
 			local_signals: Mutex::new(FxHashMap::default()),
 			local_methods: Mutex::new(FxHashMap::default()),
+			signal_subscribers: Mutex::new(FxHashMap::default()),
+			unknown_signal: Mutex::new(None),
+			user_data: Mutex::new(None),
+			discard_unhandled_signals: AtomicBool::new(false),
 			owned,
 		});
 		if owned {
@@ -260,6 +404,7 @@ impl NodeType for Node {
 		Node::Owned(node)
 	}
 }
+impl Schema for Node {}
 impl serde::Serialize for Node {
 	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
 		let node_id = self.node().get_id().map_err(serde::ser::Error::custom)?;
@@ -297,3 +442,298 @@ impl Debug for Node {
 		dbg.finish()
 	}
 }
+
+#[tokio::test]
+async fn fusion_optional_method_return() {
+	use stardust_xr::mock::MockServer;
+
+	stardust_xr_fusion_codegen::codegen_protocol!(
+		r#"
+		version (u32)1
+		description ""
+
+		aspect "Lookup" {
+			description ""
+
+			method "find" side="server" {
+				description ""
+				argument "key" type="string"
+				return type="uint" optional=true
+			}
+		}
+	"#
+	);
+
+	let server = MockServer::new();
+	server.on_method(LOOKUP_FIND_SERVER_OPCODE, |_node, data| {
+		let found = (deserialize::<String>(data).unwrap() == "present").then_some(7_u32);
+		Ok(serialize(found).unwrap())
+	});
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let lookup = Lookup::from_id(&client, 1, false);
+	assert_eq!(lookup.find("present").await.unwrap(), Some(7));
+	assert_eq!(lookup.find("absent").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn fusion_acked_signal() {
+	use stardust_xr::mock::MockServer;
+
+	stardust_xr_fusion_codegen::codegen_protocol!(
+		r#"
+		version (u32)1
+		description ""
+
+		aspect "Sync" {
+			description ""
+
+			signal "mark" side="server" acked=true {
+				description ""
+				argument "label" type="string"
+			}
+		}
+	"#
+	);
+
+	let server = MockServer::new();
+	server.respond(SYNC_MARK_SERVER_OPCODE, &());
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	// Acked, so it's awaited and goes over the wire as a method call instead of a signal
+	Sync::from_id(&client, 1, false).mark("done").await.unwrap();
+	let marks: Vec<_> = server
+		.method_calls()
+		.into_iter()
+		.filter(|call| call.method == SYNC_MARK_SERVER_OPCODE)
+		.collect();
+	assert_eq!(marks.len(), 1);
+	assert_eq!(deserialize::<String>(&marks[0].data).unwrap(), "done");
+	assert!(!server
+		.signals()
+		.iter()
+		.any(|signal| signal.method == SYNC_MARK_SERVER_OPCODE));
+}
+
+#[tokio::test]
+async fn fusion_fd_vec_round_trip() {
+	use stardust_xr::{mock::MockServer, scenegraph::Scenegraph};
+	use std::io::{Read, Write};
+
+	stardust_xr_fusion_codegen::codegen_protocol!(
+		r#"
+		version (u32)1
+		description ""
+
+		aspect "FdBatch" {
+			description ""
+
+			signal "send" side="server" {
+				description ""
+				argument "fds" type="vec" member_type="fd"
+			}
+			signal "receive" side="client" {
+				description ""
+				argument "fds" type="vec" member_type="fd"
+			}
+		}
+	"#
+	);
+
+	struct FdBatchReceiver(Vec<OwnedFd>);
+	impl FdBatchHandler for FdBatchReceiver {
+		fn receive(&mut self, fds: Vec<OwnedFd>) {
+			self.0 = fds;
+		}
+	}
+
+	// Stands in for the server, handing back whatever fds it got
+	let server = MockServer::new();
+	let (echo_tx, mut echo_rx) = tokio::sync::mpsc::unbounded_channel();
+	server.on_signal(FD_BATCH_SEND_SERVER_OPCODE, move |_node, data, fds| {
+		let _ = echo_tx.send((data.to_vec(), fds));
+		Ok(())
+	});
+	let (client, mut client_tx, _client_rx) = Client::from_connection(server.connect().unwrap())
+		.await
+		.unwrap();
+	tokio::task::spawn(async move { client_tx.flush().await });
+
+	let fds = (0..3)
+		.map(|i| {
+			let path =
+				std::env::temp_dir().join(format!("stardust-fd-test-{}-{i}", std::process::id()));
+			let mut file = std::fs::File::create(&path).unwrap();
+			file.write_all(i.to_string().as_bytes()).unwrap();
+			let file = std::fs::File::open(&path).unwrap();
+			let _ = std::fs::remove_file(&path);
+			OwnedFd::from(file)
+		})
+		.collect::<Vec<_>>();
+
+	let node = FdBatch::from_id(&client, 1, true);
+	node.send(fds).unwrap();
+	let (data, fds) = echo_rx.recv().await.unwrap();
+	assert_eq!(deserialize::<Vec<u32>>(&data).unwrap(), vec![0, 1, 2]);
+
+	let node = node.wrap(FdBatchReceiver(Vec::new())).unwrap();
+	client
+		.scenegraph
+		.send_signal(1, FD_BATCH_RECEIVE_CLIENT_OPCODE, &data, fds)
+		.unwrap();
+	let contents = std::mem::take(&mut node.lock_wrapped().0)
+		.into_iter()
+		.map(|fd| {
+			let mut contents = String::new();
+			std::fs::File::from(fd)
+				.read_to_string(&mut contents)
+				.unwrap();
+			contents
+		})
+		.collect::<Vec<_>>();
+	assert_eq!(contents, vec!["0", "1", "2"]);
+}
+
+#[tokio::test]
+async fn fusion_destroy_unowned_node() {
+	use crate::spatial::Spatial;
+	use stardust_xr::mock::MockServer;
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	// Stands in for a spatial imported or received from another client
+	let imported = Spatial::from_id(&client, 5, false);
+	assert!(matches!(imported.destroy(), Err(NodeError::NotOwned)));
+	assert!(matches!(
+		NodeType::set_enabled(&imported, false),
+		Err(NodeError::NotOwned)
+	));
+
+	let owned = Spatial::from_id(&client, 6, true);
+	owned.destroy().unwrap();
+	tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+	let destroyed = server
+		.signals()
+		.into_iter()
+		.filter(|signal| signal.method == OWNED_DESTROY_SERVER_OPCODE)
+		.map(|signal| signal.node)
+		.collect::<Vec<_>>();
+	assert_eq!(destroyed, vec![6]);
+}
+
+#[tokio::test]
+async fn fusion_node_exists() {
+	use crate::spatial::Spatial;
+	use stardust_xr::mock::MockServer;
+
+	let server = MockServer::new();
+	// Only node 6 exists
+	server.on_method(NODE_EXISTS_SERVER_OPCODE, |node, _data| {
+		Ok(serialize(node == 6).unwrap())
+	});
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	assert!(Spatial::from_id(&client, 6, true)
+		.alias()
+		.exists()
+		.await
+		.unwrap());
+	assert!(!Spatial::from_id(&client, 7, false).exists().await.unwrap());
+	let asked = server
+		.method_calls()
+		.into_iter()
+		.filter(|call| call.method == NODE_EXISTS_SERVER_OPCODE)
+		.map(|call| call.node)
+		.collect::<Vec<_>>();
+	assert_eq!(asked, vec![6, 7]);
+}
+
+#[tokio::test]
+async fn fusion_node_user_data() {
+	use crate::spatial::{Spatial, Transform};
+	use stardust_xr::mock::MockServer;
+
+	#[derive(Debug, PartialEq)]
+	struct Label(&'static str);
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	let spatial = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	assert!(spatial.user_data::<Label>().unwrap().is_none());
+
+	spatial.set_user_data(Label("hand anchor")).unwrap();
+	assert_eq!(
+		spatial.user_data::<Label>().unwrap().as_deref(),
+		Some(&Label("hand anchor"))
+	);
+	// Shared with aliases, and only returned as the type it was set as
+	let alias = spatial.alias();
+	assert_eq!(
+		alias.user_data::<Label>().unwrap().as_deref(),
+		Some(&Label("hand anchor"))
+	);
+	assert!(alias.user_data::<String>().unwrap().is_none());
+
+	drop(spatial);
+	assert!(alias.user_data::<Label>().is_err());
+}
+
+#[tokio::test]
+async fn fusion_typed_method_error() {
+	use crate::root::{RootAspect, ROOT_GET_BASE_PREFIXES_SERVER_OPCODE};
+	use serde::Deserialize;
+	use stardust_xr::{mock::MockServer, scenegraph::ScenegraphError};
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	enum PrefixError {
+		NoPrefixes,
+		Unreadable,
+	}
+
+	let server = MockServer::new();
+	server.on_method(ROOT_GET_BASE_PREFIXES_SERVER_OPCODE, |_node, _data| {
+		Err(ScenegraphError::TypedMethodError {
+			error: "prefix directory unreadable".to_string(),
+			code: 7,
+			data: serialize(PrefixError::Unreadable).unwrap(),
+		})
+	});
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let error = client.get_root().get_base_prefixes().await.unwrap_err();
+	match &error {
+		NodeError::ReturnedErrorCode { e, code: 7, .. } => {
+			assert_eq!(e, "prefix directory unreadable")
+		}
+		e => panic!("expected a typed error, got {e:?}"),
+	}
+	let (code, data) = error.error_code::<PrefixError>().unwrap();
+	assert_eq!(code, 7);
+	assert_eq!(data.unwrap(), PrefixError::Unreadable);
+
+	// Plain errors still come back as a message only
+	server.on_method(ROOT_GET_BASE_PREFIXES_SERVER_OPCODE, |_node, _data| {
+		Err(ScenegraphError::MethodError {
+			error: "nope".to_string(),
+		})
+	});
+	let error = client.get_root().get_base_prefixes().await.unwrap_err();
+	assert!(
+		matches!(error, NodeError::ReturnedError { .. }),
+		"{error:?}"
+	);
+	assert!(error.error_code::<PrefixError>().is_none());
+}
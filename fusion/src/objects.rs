@@ -63,7 +63,11 @@ impl FieldObject {
 	}
 }
 
+/// Get a spatial at the user's head from `Root::hmd`, falling back to the `org.stardustxr.HMD` D-Bus object on servers without it.
 pub async fn hmd(client: &Arc<Client>) -> Option<SpatialRef> {
+	if let Ok(hmd) = client.get_root().hmd().await {
+		return Some(hmd);
+	}
 	SpatialRefProxy::new(
 		&Connection::session().await.ok()?,
 		"org.stardustxr.HMD",
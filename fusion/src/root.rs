@@ -2,16 +2,31 @@ use std::sync::Arc;
 
 use crate::client::Client;
 use crate::impl_aspects;
-use crate::node::{NodeResult, NodeType};
+use crate::node::{NodeResult, NodeType, OwnedAspect};
 use crate::spatial::{SpatialRef, SpatialRefAspect};
 use color_eyre::eyre::Result;
 use rustc_hash::FxHashMap;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use stardust_xr::schemas::flex::flexbuffers;
+use stardust_xr::values::ResourceID;
 
 stardust_xr_fusion_codegen::codegen_root_protocol!();
 impl_aspects!(Root: SpatialRefAspect);
+impl Root {
+	/// Get a spatial at the user's head, to position things relative to it.
+	/// It follows the head live, so query its transform whenever it's needed instead of caching it.
+	pub async fn hmd(&self) -> NodeResult<SpatialRef> {
+		self.get_hmd().await
+	}
+	/// Watch the file behind `resource` for changes while developing, wrap the returned watcher with a `ResourceWatcherHandler` to hear about them.
+	/// Watching stops once the watcher is dropped.
+	pub fn watch_resource(&self, resource: &ResourceID) -> NodeResult<ResourceWatcher> {
+		let client = self.client()?;
+		self.create_resource_watcher(client.generate_id(), resource)
+	}
+}
+impl_aspects!(ResourceWatcher: OwnedAspect);
 
 /// The persistent state of a Stardust client.
 impl Default for ClientState {
@@ -67,3 +82,96 @@ impl ClientState {
 			.collect()
 	}
 }
+
+#[tokio::test]
+async fn fusion_root_hmd() {
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	let hmd = client.get_root().hmd().await.unwrap();
+	let transform = hmd.get_transform(client.get_root()).await.unwrap();
+	assert!(transform.translation.is_some());
+	assert!(transform.rotation.is_some());
+}
+
+#[tokio::test]
+async fn fusion_root_add_base_prefix() {
+	use stardust_xr::{mock::MockServer, schemas::flex::deserialize};
+
+	let server = MockServer::new();
+	server.respond(
+		ROOT_GET_BASE_PREFIXES_SERVER_OPCODE,
+		&vec!["/usr/share/app".to_string()],
+	);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let root = client.get_root();
+	root.add_base_prefix("/usr/share/plugin").unwrap();
+	assert_eq!(
+		root.get_base_prefixes().await.unwrap(),
+		vec!["/usr/share/app".to_string()]
+	);
+	let added = server
+		.signals()
+		.into_iter()
+		.find(|s| s.method == ROOT_ADD_BASE_PREFIX_SERVER_OPCODE)
+		.unwrap();
+	assert_eq!(added.node, 0);
+	assert_eq!(
+		deserialize::<String>(&added.data).unwrap(),
+		"/usr/share/plugin"
+	);
+}
+
+#[tokio::test]
+async fn fusion_root_watch_resource() {
+	use stardust_xr::{mock::MockServer, scenegraph::Scenegraph};
+	use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+	struct ReloadCounter(UnboundedSender<()>);
+	impl ResourceWatcherHandler for ReloadCounter {
+		fn resource_changed(&mut self) {
+			let _ = self.0.send(());
+		}
+	}
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let resource = ResourceID::new_namespaced("fusion", "gyro");
+	let watcher = client.get_root().watch_resource(&resource).unwrap();
+	let watcher_id = watcher.node().get_id().unwrap();
+	let (tx, mut rx) = unbounded_channel();
+	let _watcher = watcher.wrap(ReloadCounter(tx)).unwrap();
+	let created = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+		loop {
+			let created = server
+				.signals()
+				.into_iter()
+				.find(|s| s.method == ROOT_CREATE_RESOURCE_WATCHER_SERVER_OPCODE);
+			if let Some(created) = created {
+				break created;
+			}
+			tokio::task::yield_now().await;
+		}
+	})
+	.await
+	.expect("Server never saw the watcher being created");
+	assert_eq!(created.node, 0);
+
+	// What the server sends once the file has been saved
+	client
+		.scenegraph
+		.send_signal(
+			watcher_id,
+			RESOURCE_WATCHER_RESOURCE_CHANGED_CLIENT_OPCODE,
+			&stardust_xr::schemas::flex::serialize(()).unwrap(),
+			Vec::new(),
+		)
+		.unwrap();
+	rx.recv().await.unwrap();
+}
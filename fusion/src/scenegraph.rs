@@ -1,17 +1,99 @@
-use crate::node::NodeInternals;
+use crate::node::{NodeError, NodeInternals, NodeResult};
 use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
 use stardust_xr::scenegraph::{self, ScenegraphError};
 use std::{
 	os::fd::OwnedFd,
-	sync::{Arc, Weak},
+	sync::{
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+		mpsc, Arc, Weak,
+	},
+	thread,
 };
-use tokio::sync::oneshot;
+use tokio::{runtime::Handle, sync::oneshot};
+
+type DispatchJob = Box<dyn FnOnce() + Send>;
+
+/// How many messages can wait on a dispatch thread before warning, unless changed with `Scenegraph::set_dispatch_high_water_mark`.
+pub const DEFAULT_DISPATCH_HIGH_WATER_MARK: usize = 1024;
+
+struct DispatchWorker {
+	tx: mpsc::Sender<DispatchJob>,
+	/// Messages queued or running on this thread, approximately.
+	pending: Arc<AtomicUsize>,
+	/// Set once the high-water mark has been warned about, until the thread catches up completely.
+	warned: Arc<AtomicBool>,
+}
+
+/// Threads that signals and methods are handed off to, each node always goes to the same thread so its messages stay in order.
+struct DispatchPool {
+	workers: Vec<DispatchWorker>,
+}
+impl DispatchPool {
+	fn new(threads: usize, runtime: Option<Handle>) -> Self {
+		let workers = (0..threads)
+			.map(|i| {
+				let (tx, rx) = mpsc::channel::<DispatchJob>();
+				let pending = Arc::new(AtomicUsize::new(0));
+				let warned = Arc::new(AtomicBool::new(false));
+				thread::Builder::new()
+					.name(format!("stardust-dispatch-{i}"))
+					.spawn({
+						let pending = pending.clone();
+						let warned = warned.clone();
+						let runtime = runtime.clone();
+						move || {
+							// So handlers can spawn tasks and use tokio's IO and timers like they could inline
+							let _runtime = runtime.as_ref().map(Handle::enter);
+							// Ends once the pool is dropped
+							while let Ok(job) = rx.recv() {
+								job();
+								if pending.fetch_sub(1, Ordering::Relaxed) == 1 {
+									warned.store(false, Ordering::Relaxed);
+								}
+							}
+						}
+					})
+					.expect("Couldn't spawn dispatch thread");
+				DispatchWorker {
+					tx,
+					pending,
+					warned,
+				}
+			})
+			.collect();
+		DispatchPool { workers }
+	}
+	fn dispatch(&self, id: u64, job: DispatchJob, high_water_mark: usize) {
+		let index = (id % self.workers.len() as u64) as usize;
+		let worker = &self.workers[index];
+		let pending = worker.pending.fetch_add(1, Ordering::Relaxed) + 1;
+		if pending > high_water_mark && !worker.warned.swap(true, Ordering::Relaxed) {
+			tracing::warn!(
+				"{pending} messages are waiting on dispatch thread {index} (last for node {id}), a handler is probably too slow to keep up"
+			);
+		}
+		let _ = worker.tx.send(job);
+	}
+}
 
 /// Scenegraph full of aliases to nodes, needed so the `Messenger` can send messages to nodes.
-#[derive(Default)]
 pub struct Scenegraph {
 	nodes: Mutex<FxHashMap<u64, Weak<NodeInternals>>>,
+	/// Spatial parent of each spatial by ID, as far as this client has set them.
+	spatial_parents: Mutex<FxHashMap<u64, u64>>,
+	dispatch_pool: Mutex<Option<Arc<DispatchPool>>>,
+	dispatch_high_water_mark: AtomicUsize,
+}
+impl Default for Scenegraph {
+	fn default() -> Self {
+		Scenegraph {
+			nodes: Default::default(),
+			spatial_parents: Default::default(),
+			dispatch_pool: Default::default(),
+			dispatch_high_water_mark: AtomicUsize::new(DEFAULT_DISPATCH_HIGH_WATER_MARK),
+		}
+	}
 }
 
 impl Scenegraph {
@@ -19,19 +101,164 @@ impl Scenegraph {
 		Default::default()
 	}
 
+	/// Register an owned node so messages can be routed to it.
+	///
+	/// In debug builds this panics if another live node already has the same ID, as only one of them would ever get its messages.
 	pub fn add_node(&self, node_internals: &Arc<NodeInternals>) {
-		self.nodes
+		let previous = self
+			.nodes
 			.lock()
 			.insert(node_internals.id, Arc::downgrade(node_internals));
+		debug_assert!(
+			previous.and_then(|n| n.upgrade()).is_none(),
+			"Node ID {} is already used by another live node",
+			node_internals.id
+		);
 	}
 
 	pub fn remove_node(&self, id: u64) {
 		self.nodes.lock().remove(&id);
+		self.spatial_parents.lock().remove(&id);
+	}
+
+	/// Check that making `parent` the spatial parent of `child` wouldn't parent `child` under itself.
+	///
+	/// Only links made by this client are known, so spatials imported or received from the server are treated as having no parent
+	/// and the server may still refuse the change.
+	pub(crate) fn check_spatial_parent(&self, child: u64, parent: u64) -> NodeResult<()> {
+		let spatial_parents = self.spatial_parents.lock();
+		let mut ancestor = Some(parent);
+		while let Some(id) = ancestor {
+			if id == child {
+				return Err(NodeError::WouldCreateCycle);
+			}
+			ancestor = spatial_parents.get(&id).copied();
+		}
+		Ok(())
+	}
+	/// Remember that `parent` is now the spatial parent of `child`.
+	pub(crate) fn set_spatial_parent(&self, child: u64, parent: u64) {
+		self.spatial_parents.lock().insert(child, parent);
+	}
+
+	/// Run signal and method handlers on a pool of `threads` threads instead of inside the message loop, or inline again if `threads` is 0.
+	///
+	/// Messages to the same node are always handled in the order they arrived, on the same thread.
+	/// Messages to different nodes can be handled in any order relative to each other, so a slow handler only holds up its own node (and any other node that shares its thread).
+	/// Errors from signals are logged instead of being sent back to the server, as the message loop has already moved on.
+	/// Messages already queued when switching are still handled, but may run out of order with ones after the switch.
+	/// When called inside a tokio runtime, the threads enter that runtime so handlers can spawn tasks on it.
+	pub fn set_dispatch_threads(&self, threads: usize) {
+		let runtime = Handle::try_current().ok();
+		*self.dispatch_pool.lock() =
+			(threads > 0).then(|| Arc::new(DispatchPool::new(threads, runtime)));
+	}
+
+	/// Warn once when more than `messages` signals and methods are waiting on one dispatch thread, which usually means a handler is too slow to keep up.
+	/// The warning comes back once that thread has caught up completely. Only applies with `set_dispatch_threads`, as otherwise messages are handled inline.
+	pub fn set_dispatch_high_water_mark(&self, messages: usize) {
+		self.dispatch_high_water_mark
+			.store(messages, Ordering::Relaxed);
 	}
 
-	// pub fn get_node(&self, path: &str) -> Option<Node> {
-	// 	self.nodes.lock().get(path).cloned().unwrap_or_default()
-	// }
+	fn get_node(&self, id: u64) -> Result<Arc<NodeInternals>, ScenegraphError> {
+		self.nodes
+			.lock()
+			.get(&id)
+			.and_then(Weak::upgrade)
+			.ok_or(ScenegraphError::NodeNotFound)
+	}
+}
+
+/// If the node asked for `method` to be thrown away without looking at it, see `NodeType::discard_unhandled_signals`.
+fn discards_signal(node: &NodeInternals, method: u64) -> bool {
+	node.discard_unhandled_signals.load(Ordering::Relaxed)
+		&& !node.local_signals.lock().contains_key(&method)
+		&& node
+			.signal_subscribers
+			.lock()
+			.get(&method)
+			.map_or(true, Vec::is_empty)
+		&& node.unknown_signal.lock().is_none()
+}
+fn dispatch_signal(
+	node: &NodeInternals,
+	method: u64,
+	data: &[u8],
+	fds: Vec<OwnedFd>,
+) -> Result<(), ScenegraphError> {
+	let signal = node.local_signals.lock().get(&method).cloned();
+	let subscribers = node
+		.signal_subscribers
+		.lock()
+		.get(&method)
+		.cloned()
+		.unwrap_or_default();
+	if signal.is_none() && subscribers.is_empty() {
+		if discards_signal(node, method) {
+			return Ok(());
+		}
+		let unknown_signal = node
+			.unknown_signal
+			.lock()
+			.clone()
+			.ok_or(ScenegraphError::SignalNotFound)?;
+		return unknown_signal(method, data, fds).map_err(|e| ScenegraphError::SignalError {
+			error: e.to_string(),
+		});
+	}
+
+	let mut result = Ok(());
+	let mut fd_error = None;
+	let mut dropped = Vec::new();
+	for subscriber in subscribers {
+		// every subscriber gets its own copies, one missing fd would shift the indices in the data
+		let fds = match fds.iter().map(OwnedFd::try_clone).collect() {
+			Ok(fds) => fds,
+			Err(e) => {
+				fd_error.get_or_insert(e);
+				continue;
+			}
+		};
+		match subscriber(data, fds) {
+			Some(Err(e)) if result.is_ok() => result = Err(e),
+			Some(_) => (),
+			None => dropped.push(subscriber),
+		}
+	}
+	if !dropped.is_empty() {
+		if let Some(subscribers) = node.signal_subscribers.lock().get_mut(&method) {
+			subscribers.retain(|s| !dropped.iter().any(|d| Arc::ptr_eq(s, d)));
+		}
+	}
+	if let Some(signal) = signal {
+		result = signal(data, fds).and(result);
+	}
+	result.map_err(|e| ScenegraphError::SignalError {
+		error: e.to_string(),
+	})?;
+	match fd_error {
+		Some(e) => Err(ScenegraphError::SignalError {
+			error: format!("Couldn't duplicate file descriptors for a signal subscriber: {e}"),
+		}),
+		None => Ok(()),
+	}
+}
+fn dispatch_method(
+	node: &NodeInternals,
+	method: u64,
+	data: &[u8],
+	fds: Vec<OwnedFd>,
+) -> Result<(Vec<u8>, Vec<OwnedFd>), ScenegraphError> {
+	let local_methods = node.local_methods.lock();
+	let method = local_methods
+		.get(&method)
+		.ok_or(ScenegraphError::MethodNotFound)?
+		.clone();
+	drop(local_methods);
+	method(data, fds).map_err(|e| ScenegraphError::MethodError {
+		error: e.to_string(),
+	})
 }
 
 impl scenegraph::Scenegraph for Scenegraph {
@@ -42,20 +269,29 @@ impl scenegraph::Scenegraph for Scenegraph {
 		data: &[u8],
 		fds: Vec<OwnedFd>,
 	) -> Result<(), ScenegraphError> {
-		let node = self
-			.nodes
-			.lock()
-			.get(&id)
-			.and_then(Weak::upgrade)
-			.ok_or(ScenegraphError::NodeNotFound)?;
-		let local_signals = node.local_signals.lock();
-		let signal = local_signals
-			.get(&method)
-			.ok_or(ScenegraphError::SignalNotFound)?
-			.clone();
-		signal(data, fds).map_err(|e| ScenegraphError::SignalError {
-			error: e.to_string(),
-		})
+		let node = self.get_node(id)?;
+		if discards_signal(&node, method) {
+			return Ok(());
+		}
+		let Some(dispatch_pool) = self.dispatch_pool.lock().clone() else {
+			return dispatch_signal(&node, method, data, fds);
+		};
+		let node = Arc::downgrade(&node);
+		let data = data.to_vec();
+		dispatch_pool.dispatch(
+			id,
+			Box::new(move || {
+				let result = node
+					.upgrade()
+					.ok_or(ScenegraphError::NodeNotFound)
+					.and_then(|node| dispatch_signal(&node, method, &data, fds));
+				if let Err(e) = result {
+					tracing::warn!("Signal {method} on node {id} failed: {e}");
+				}
+			}),
+			self.dispatch_high_water_mark.load(Ordering::Relaxed),
+		);
+		Ok(())
 	}
 	fn execute_method(
 		&self,
@@ -65,23 +301,239 @@ impl scenegraph::Scenegraph for Scenegraph {
 		fds: Vec<OwnedFd>,
 		response: oneshot::Sender<Result<(Vec<u8>, Vec<OwnedFd>), ScenegraphError>>,
 	) {
-		let method_method = || {
-			let node = self
-				.nodes
-				.lock()
-				.get(&id)
-				.and_then(Weak::upgrade)
-				.ok_or(ScenegraphError::NodeNotFound)?;
-			let local_methods = node.local_methods.lock();
-			let method = local_methods
-				.get(&method)
-				.ok_or(ScenegraphError::MethodNotFound)?
-				.clone();
-			method(data, fds).map_err(|e| ScenegraphError::MethodError {
-				error: e.to_string(),
-			})
+		let node = match self.get_node(id) {
+			Ok(node) => node,
+			Err(e) => {
+				let _ = response.send(Err(e));
+				return;
+			}
+		};
+		let Some(dispatch_pool) = self.dispatch_pool.lock().clone() else {
+			let _ = response.send(dispatch_method(&node, method, data, fds));
+			return;
 		};
+		let node = Arc::downgrade(&node);
+		let data = data.to_vec();
+		dispatch_pool.dispatch(
+			id,
+			Box::new(move || {
+				let result = node
+					.upgrade()
+					.ok_or(ScenegraphError::NodeNotFound)
+					.and_then(|node| dispatch_method(&node, method, &data, fds));
+				let _ = response.send(result);
+			}),
+			self.dispatch_high_water_mark.load(Ordering::Relaxed),
+		);
+	}
+}
+
+#[tokio::test]
+async fn fusion_dispatch_threads_slow_node() {
+	use crate::{
+		client::Client,
+		node::{Node, NodeType},
+	};
+	use scenegraph::Scenegraph;
+	use std::time::Duration;
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+	client.scenegraph.set_dispatch_threads(2);
+
+	let (tx, rx) = mpsc::channel();
+	// The slow node is stuck in its handler until released
+	let (release_tx, release_rx) = mpsc::channel::<()>();
+	let release_rx = Mutex::new(release_rx);
+	let slow = Node::from_id(&client, 1, true);
+	let slow_tx = tx.clone();
+	slow.add_local_signal(0, move |data, _fds| {
+		release_rx.lock().recv()?;
+		slow_tx.send(("slow", data[0]))?;
+		Ok(())
+	})
+	.unwrap();
+	let fast = Node::from_id(&client, 2, true);
+	fast.add_local_signal(0, move |data, _fds| {
+		tx.send(("fast", data[0]))?;
+		Ok(())
+	})
+	.unwrap();
+
+	for i in 0..3 {
+		client
+			.scenegraph
+			.send_signal(1, 0, &[i], Vec::new())
+			.unwrap();
+	}
+	client
+		.scenegraph
+		.send_signal(2, 0, &[0], Vec::new())
+		.unwrap();
+
+	// The fast node isn't stuck behind the slow one, which hasn't been released yet
+	assert_eq!(
+		rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+		("fast", 0)
+	);
+	// While the slow node still gets its signals in order
+	for _ in 0..3 {
+		release_tx.send(()).unwrap();
+	}
+	let slow_signals = (0..3)
+		.map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(slow_signals, vec![("slow", 0), ("slow", 1), ("slow", 2)]);
+}
+
+#[tokio::test]
+async fn fusion_dispatch_threads_runtime() {
+	use crate::{
+		client::Client,
+		node::{Node, NodeType},
+	};
+	use scenegraph::Scenegraph;
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+	client.scenegraph.set_dispatch_threads(1);
 
-		let _ = response.send(method_method());
+	let (tx, rx) = oneshot::channel();
+	let tx = Mutex::new(Some(tx));
+	let node = Node::from_id(&client, 1, true);
+	node.add_local_signal(0, move |_data, _fds| {
+		let tx = tx.lock().take();
+		tokio::task::spawn(async move {
+			let _ = tx.unwrap().send(thread::current().name().map(String::from));
+		});
+		Ok(())
+	})
+	.unwrap();
+	client
+		.scenegraph
+		.send_signal(1, 0, &[], Vec::new())
+		.unwrap();
+
+	// The task ran on the test's runtime rather than the dispatch thread
+	let spawned_on = tokio::time::timeout(std::time::Duration::from_secs(1), rx)
+		.await
+		.unwrap()
+		.unwrap();
+	assert_ne!(spawned_on.as_deref(), Some("stardust-dispatch-0"));
+}
+
+#[cfg(debug_assertions)]
+#[tokio::test]
+#[should_panic(expected = "already used by another live node")]
+async fn fusion_duplicate_node_id() {
+	use crate::{client::Client, node::Node, node::NodeType};
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+
+	let _first = Node::from_id(&client, 7, true);
+	// Reusing the ID of a node that's gone is fine
+	drop(Node::from_id(&client, 8, true));
+	let _reused = Node::from_id(&client, 8, true);
+	let _second = Node::from_id(&client, 7, true);
+}
+
+#[tokio::test]
+async fn fusion_dispatch_high_water_mark() {
+	use crate::{client::Client, node::Node, node::NodeType};
+	use scenegraph::Scenegraph;
+	use std::io::Write;
+
+	#[derive(Clone, Default)]
+	struct Logs(Arc<Mutex<Vec<u8>>>);
+	impl Write for Logs {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.0.lock().extend_from_slice(buf);
+			Ok(buf.len())
+		}
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
 	}
+	let logs = Logs::default();
+	let subscriber = tracing_subscriber::fmt()
+		.with_writer({
+			let logs = logs.clone();
+			move || logs.clone()
+		})
+		.finish();
+	let _guard = tracing::subscriber::set_default(subscriber);
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+	client.scenegraph.set_dispatch_threads(1);
+	client.scenegraph.set_dispatch_high_water_mark(8);
+
+	// Hold up the handler so nothing gets drained
+	let (release_tx, release_rx) = mpsc::channel::<()>();
+	let release_rx = Mutex::new(release_rx);
+	let node = Node::from_id(&client, 1, true);
+	node.add_local_signal(0, move |_data, _fds| {
+		let _ = release_rx.lock().recv();
+		Ok(())
+	})
+	.unwrap();
+
+	for _ in 0..32 {
+		client
+			.scenegraph
+			.send_signal(1, 0, &[], Vec::new())
+			.unwrap();
+	}
+	drop(release_tx);
+
+	let logs = String::from_utf8(logs.0.lock().clone()).unwrap();
+	assert_eq!(logs.matches("messages are waiting").count(), 1, "{logs}");
+}
+
+#[tokio::test]
+async fn fusion_discard_unhandled_signals() {
+	use crate::{
+		client::Client,
+		node::{Node, NodeType},
+	};
+	use scenegraph::Scenegraph;
+
+	let (client_socket, _server_socket) = tokio::net::UnixStream::pair().unwrap();
+	let (client, _client_tx, _client_rx) = Client::from_connection(client_socket).await.unwrap();
+
+	let node = Node::from_id(&client, 1, true);
+	assert!(matches!(
+		client.scenegraph.send_signal(1, 0, &[], Vec::new()),
+		Err(ScenegraphError::SignalNotFound)
+	));
+	node.discard_unhandled_signals(true).unwrap();
+	client
+		.scenegraph
+		.send_signal(1, 0, &[], Vec::new())
+		.unwrap();
+
+	// Discarded signals don't pile up on the dispatch threads either
+	client.scenegraph.set_dispatch_threads(1);
+	for _ in 0..100 {
+		client
+			.scenegraph
+			.send_signal(1, 0, &[], Vec::new())
+			.unwrap();
+	}
+	let dispatch_pool = client.scenegraph.dispatch_pool.lock().clone().unwrap();
+	assert_eq!(dispatch_pool.workers[0].pending.load(Ordering::Relaxed), 0);
+
+	// Signals with a handler still go through
+	let (tx, rx) = mpsc::channel();
+	node.add_local_signal(2, move |_data, _fds| {
+		tx.send(())?;
+		Ok(())
+	})
+	.unwrap();
+	client
+		.scenegraph
+		.send_signal(1, 2, &[], Vec::new())
+		.unwrap();
+	rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap();
 }
@@ -14,18 +14,59 @@
 //! Zones can capture spatials, temporarily parenting them to the zone until they are released.
 //! Zones can see zoneable spatials if they're closer to the surface of the field than any zone that captured them, so no zones can steal and hoard them.
 
-use std::{hash::Hash, sync::Arc};
+use std::{
+	hash::Hash,
+	sync::Arc,
+	time::{Duration, Instant},
+};
 
 use crate::{
 	client::Client,
 	fields::FieldAspect,
 	impl_aspects,
 	node::{NodeResult, NodeType, OwnedAspect},
+	root::FrameInfo,
 };
+use glam::{Quat, Vec3};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
 use stardust_xr::values::*;
+use thiserror::Error;
 
 stardust_xr_fusion_codegen::codegen_spatial_protocol!();
+
+/// Transforms fetched by `Spatial::get_transform_cached`, by the IDs of the spatial and the spatial they're relative to.
+#[derive(Default)]
+pub(crate) struct TransformCache(Mutex<FxHashMap<(u64, u64), (Instant, Transform)>>);
+impl TransformCache {
+	fn get(&self, key: (u64, u64), max_age: Duration) -> Option<Transform> {
+		let cache = self.0.lock();
+		let (fetched, transform) = cache.get(&key)?;
+		(fetched.elapsed() <= max_age).then_some(*transform)
+	}
+	fn insert(&self, key: (u64, u64), fetched: Instant, transform: Transform) {
+		self.0.lock().insert(key, (fetched, transform));
+	}
+	/// Forget the transforms involving node `id` if `signal` moves it.
+	pub(crate) fn invalidate(&self, id: u64, signal: u64) {
+		if matches!(
+			signal,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE
+				| SPATIAL_SET_RELATIVE_TRANSFORM_SERVER_OPCODE
+				| SPATIAL_SET_SPATIAL_PARENT_SERVER_OPCODE
+				| SPATIAL_SET_SPATIAL_PARENT_IN_PLACE_SERVER_OPCODE
+		) {
+			self.remove_node(id);
+		}
+	}
+	pub(crate) fn remove_node(&self, id: u64) {
+		self.0
+			.lock()
+			.retain(|(spatial, relative_to), _| *spatial != id && *relative_to != id);
+	}
+}
 impl Transform {
+	/// Every component unset, so setting it changes nothing and creating a node with it uses the identity.
 	pub const fn none() -> Self {
 		Transform {
 			translation: None,
@@ -33,6 +74,7 @@ impl Transform {
 			scale: None,
 		}
 	}
+	/// Every component explicitly set to the identity, so setting it resets the transform.
 	pub const fn identity() -> Self {
 		Transform {
 			translation: Some(Vector3 {
@@ -121,6 +163,16 @@ impl Transform {
 			scale: Some(scale.into()),
 		}
 	}
+
+	/// Overlay the components that are set in `over` on this transform, keeping this transform's components where `over` leaves them unset.
+	/// Useful to compose partial updates before sending them.
+	pub fn merge(self, over: &Transform) -> Self {
+		Transform {
+			translation: over.translation.or(self.translation),
+			rotation: over.rotation.or(self.rotation),
+			scale: over.scale.or(self.scale),
+		}
+	}
 }
 impl Copy for Transform {}
 impl Hash for Transform {
@@ -144,6 +196,108 @@ impl Hash for Transform {
 	}
 }
 
+/// A pose sets both the translation and rotation, leaving the scale unset.
+impl From<Pose> for Transform {
+	fn from(pose: Pose) -> Self {
+		Transform::from_translation_rotation(pose.translation, pose.rotation)
+	}
+}
+/// Why a [`Transform`] couldn't be converted into a [`Pose`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Transform has no {0} set, so it isn't a complete pose")]
+pub struct IncompletePose(pub &'static str);
+/// Needs both the translation and rotation set, any scale is dropped.
+impl TryFrom<Transform> for Pose {
+	type Error = IncompletePose;
+	fn try_from(transform: Transform) -> Result<Self, Self::Error> {
+		Ok(Pose::new(
+			transform.translation.ok_or(IncompletePose("translation"))?,
+			transform.rotation.ok_or(IncompletePose("rotation"))?,
+		))
+	}
+}
+
+/// Tweens a transform toward a target over several frames, such as a panel sliding into place.
+/// Feed it every frame's `FrameInfo` with `update` and send the transform it returns with `set_local_transform`.
+pub struct Animator {
+	from: (Vec3, Quat, Vec3),
+	to: (Vec3, Quat, Vec3),
+	duration: f32,
+	elapsed: f32,
+	easing: fn(f32) -> f32,
+}
+impl Animator {
+	/// Start at rest at `transform`, unset components being the identity.
+	/// Every tween takes `duration`, with `easing` mapping linear progress from 0 to 1 onto the blend like `Field::animate_shape`.
+	pub fn new(transform: Transform, duration: Duration, easing: fn(f32) -> f32) -> Self {
+		let components = transform_components(&Transform::identity().merge(&transform));
+		let duration = duration.as_secs_f32();
+		Animator {
+			from: components,
+			to: components,
+			duration,
+			elapsed: duration,
+			easing,
+		}
+	}
+	/// Tween from wherever the animation is now to `target`, starting over. Components unset in `target` keep heading to their previous target.
+	pub fn set_target(&mut self, target: Transform) {
+		let (translation, rotation, scale) = self.to;
+		let current = transform_components(&self.current());
+		self.to = (
+			target.translation.map_or(translation, Vec3::from),
+			target.rotation.map_or(rotation, Quat::from),
+			target.scale.map_or(scale, Vec3::from),
+		);
+		self.from = current;
+		self.elapsed = 0.0;
+	}
+	/// Advance by the frame's `delta` and get the transform to apply for it.
+	pub fn update(&mut self, info: &FrameInfo) -> Transform {
+		self.elapsed += info.delta;
+		self.current()
+	}
+	/// The transform at this point of the tween, with every component set.
+	pub fn current(&self) -> Transform {
+		let t = (self.easing)(self.progress());
+		Transform::from_translation_rotation_scale(
+			self.from.0.lerp(self.to.0, t),
+			self.from.1.slerp(self.to.1, t),
+			self.from.2.lerp(self.to.2, t),
+		)
+	}
+	/// If the current tween has reached its target.
+	pub fn is_finished(&self) -> bool {
+		self.progress() >= 1.0
+	}
+	fn progress(&self) -> f32 {
+		if self.duration > 0.0 {
+			(self.elapsed / self.duration).clamp(0.0, 1.0)
+		} else {
+			1.0
+		}
+	}
+}
+/// Only call with every component set.
+fn transform_components(transform: &Transform) -> (Vec3, Quat, Vec3) {
+	(
+		transform.translation.unwrap().into(),
+		transform.rotation.unwrap().into(),
+		transform.scale.unwrap().into(),
+	)
+}
+
+/// Conveniences on top of the protocol's `SpatialRefAspect` methods, for any spatial ref.
+#[allow(async_fn_in_trait)]
+pub trait SpatialRefAspectExt: SpatialRefAspect {
+	/// Get the transform relative to this client's root, same as `get_transform(client.get_root())`.
+	async fn world_transform(&self) -> NodeResult<Transform> {
+		let client = self.client()?;
+		self.get_transform(client.get_root()).await
+	}
+}
+impl<T: SpatialRefAspect> SpatialRefAspectExt for T {}
+
 impl SpatialRef {
 	pub async fn import(client: &Arc<Client>, uid: u64) -> NodeResult<Self> {
 		import_spatial_ref(client, uid).await
@@ -157,15 +311,61 @@ impl Spatial {
 		transform: Transform,
 		zoneable: bool,
 	) -> NodeResult<Self> {
-		let client = spatial_parent.client()?;
+		let id = spatial_parent.client()?.generate_id();
+		Self::create_with_id(spatial_parent, id, transform, zoneable)
+	}
+	/// Like `create`, but with an explicit node id instead of a generated one, see `Client::generate_id` for which ids are safe to use.
+	pub fn create_with_id(
+		spatial_parent: &impl SpatialRefAspect,
+		id: u64,
+		transform: Transform,
+		zoneable: bool,
+	) -> NodeResult<Self> {
 		create_spatial(
-			&client,
-			client.generate_id(),
+			&spatial_parent.client()?,
+			id,
 			spatial_parent,
 			transform,
 			zoneable,
 		)
 	}
+	/// Get the transform relative to `relative_to` like `get_transform`, but reuse the last one fetched if it's no older than `max_age`.
+	///
+	/// The cache is cleared whenever this client moves or reparents this spatial or `relative_to` (such as with `set_local_transform`).
+	/// It can be stale if anything else moves them, like another client, a zone, or moving a parent, so keep `max_age` short for anything that isn't static.
+	pub async fn get_transform_cached(
+		&self,
+		relative_to: &impl SpatialRefAspect,
+		max_age: Duration,
+	) -> NodeResult<Transform> {
+		let client = self.node().client()?;
+		let key = (self.node().get_id()?, relative_to.node().get_id()?);
+		if let Some(transform) = client.transform_cache.get(key, max_age) {
+			return Ok(transform);
+		}
+		let fetched = Instant::now();
+		let transform = self.get_transform(relative_to).await?;
+		client.transform_cache.insert(key, fetched, transform);
+		Ok(transform)
+	}
+	/// Take ownership of a spatial another client exported with `export_spatial_transferable`, so it can be destroyed or modified like one this client created.
+	pub async fn import_owned(client: &Arc<Client>, uid: u64) -> NodeResult<Self> {
+		let id = import_spatial_owned(client, uid).await?;
+		Ok(Spatial::from_id(client, id, true))
+	}
+}
+
+impl_aspects!(TransformSubscription: OwnedAspect);
+impl TransformSubscription {
+	/// Get `spatial`'s transform relative to `relative_to` pushed whenever it changes (at most once a frame), wrap this with a `TransformSubscriptionHandler` to receive it.
+	/// Dropping the subscription unsubscribes.
+	pub fn create(
+		spatial: &impl SpatialRefAspect,
+		relative_to: &impl SpatialRefAspect,
+	) -> NodeResult<Self> {
+		let client = spatial.client()?;
+		subscribe_transform(&client, client.generate_id(), spatial, relative_to)
+	}
 }
 
 impl_aspects!(Zone: OwnedAspect, SpatialRefAspect, SpatialAspect);
@@ -175,10 +375,19 @@ impl Zone {
 		transform: Transform,
 		field: &impl FieldAspect,
 	) -> NodeResult<Self> {
-		let client = spatial_parent.client()?;
+		let id = spatial_parent.client()?.generate_id();
+		Self::create_with_id(spatial_parent, id, transform, field)
+	}
+	/// Like `create`, but with an explicit node id instead of a generated one, see `Client::generate_id` for which ids are safe to use.
+	pub fn create_with_id(
+		spatial_parent: &impl SpatialRefAspect,
+		id: u64,
+		transform: Transform,
+		field: &impl FieldAspect,
+	) -> NodeResult<Self> {
 		create_zone(
-			&client,
-			client.generate_id(),
+			&spatial_parent.client()?,
+			id,
 			spatial_parent,
 			transform,
 			field,
@@ -208,6 +417,176 @@ async fn fusion_spatial() {
 	assert_eq!(bounding_box.size, [0.0; 3].into());
 }
 
+#[tokio::test]
+async fn fusion_spatial_set_enabled_recursive() {
+	use super::client::Client;
+	let (client, _) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	let parent = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	let child = Spatial::create(&parent, Transform::identity(), false).unwrap();
+	let grandchild = Spatial::create(&child, Transform::identity(), false).unwrap();
+
+	parent.set_enabled_recursive(false).unwrap();
+	for spatial in [&parent, &child, &grandchild] {
+		assert!(!spatial.get_enabled().await.unwrap());
+	}
+	parent.set_enabled_recursive(true).unwrap();
+	for spatial in [&parent, &child, &grandchild] {
+		assert!(spatial.get_enabled().await.unwrap());
+	}
+}
+
+#[tokio::test]
+async fn fusion_spatial_mock_get_transform() {
+	use super::client::Client;
+	use stardust_xr::{mock::MockServer, schemas::flex::deserialize};
+
+	let server = MockServer::new();
+	let transform = Transform::from_translation([0.0, 1.5, -0.5]);
+	server.respond(SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE, &transform);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let spatial = SpatialRef::from_id(&client, 5, false);
+	assert_eq!(
+		spatial.get_transform(client.get_root()).await.unwrap(),
+		transform
+	);
+
+	let call = server
+		.method_calls()
+		.into_iter()
+		.find(|call| call.method == SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE)
+		.unwrap();
+	assert_eq!(call.node, 5);
+	let relative_to: u64 = deserialize(&call.data).unwrap();
+	assert_eq!(relative_to, client.get_root().node().get_id().unwrap());
+}
+
+#[tokio::test]
+async fn fusion_spatial_parent_cycle() {
+	use super::client::Client;
+	use crate::node::NodeError;
+	use stardust_xr::mock::MockServer;
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let grandparent = Spatial::create(client.get_root(), Transform::none(), false).unwrap();
+	let parent = Spatial::create(&grandparent, Transform::none(), false).unwrap();
+	let child = Spatial::create(&parent, Transform::none(), false).unwrap();
+	assert!(matches!(
+		grandparent.set_spatial_parent(&child),
+		Err(NodeError::WouldCreateCycle)
+	));
+	assert!(matches!(
+		parent.set_spatial_parent_in_place(&parent),
+		Err(NodeError::WouldCreateCycle)
+	));
+
+	// Once the child is moved out, the old ancestors can go under it
+	child.set_spatial_parent(client.get_root()).unwrap();
+	grandparent.set_spatial_parent(&child).unwrap();
+	assert!(matches!(
+		child.set_spatial_parent(&parent),
+		Err(NodeError::WouldCreateCycle)
+	));
+}
+
+#[test]
+fn transform_merge() {
+	let full =
+		Transform::from_translation_rotation_scale([1.0, 2.0, 3.0], glam::Quat::IDENTITY, [2.0; 3]);
+	let rotation = glam::Quat::from_rotation_y(1.0);
+	let merged = full.merge(&Transform::from_rotation(rotation));
+	assert_eq!(merged.translation, full.translation);
+	assert_eq!(merged.rotation, Some(rotation.into()));
+	assert_eq!(merged.scale, full.scale);
+
+	assert_eq!(full.merge(&Transform::none()), full);
+	assert_eq!(Transform::none().merge(&full), full);
+}
+
+#[test]
+fn transform_json_round_trip() {
+	let transform = Transform::from_translation_rotation([1.0, 2.0, 3.0], glam::Quat::IDENTITY);
+	let json = serde_json::to_value(transform).unwrap();
+	assert_eq!(
+		json,
+		serde_json::json!({
+			"translation": [1.0, 2.0, 3.0],
+			"rotation": [0.0, 0.0, 0.0, 1.0],
+			"scale": null,
+		})
+	);
+	assert_eq!(
+		serde_json::from_value::<Transform>(json).unwrap(),
+		transform
+	);
+}
+
+#[tokio::test]
+async fn fusion_spatial_get_transform_cached() {
+	use super::client::Client;
+	use stardust_xr::mock::MockServer;
+
+	let server = MockServer::new();
+	server.respond(
+		SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE,
+		&Transform::from_translation([0.0, 1.0, 0.0]),
+	);
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+	let fetches = || {
+		server
+			.method_calls()
+			.iter()
+			.filter(|call| call.method == SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE)
+			.count()
+	};
+
+	let spatial = Spatial::from_id(&client, 5, false);
+	let max_age = Duration::from_secs(60);
+	spatial
+		.get_transform_cached(client.get_root(), max_age)
+		.await
+		.unwrap();
+	let cached = spatial
+		.get_transform_cached(client.get_root(), max_age)
+		.await
+		.unwrap();
+	assert_eq!(cached, Transform::from_translation([0.0, 1.0, 0.0]));
+	assert_eq!(fetches(), 1);
+
+	// Too old
+	spatial
+		.get_transform_cached(client.get_root(), Duration::ZERO)
+		.await
+		.unwrap();
+	assert_eq!(fetches(), 2);
+
+	// Signals that don't move it keep the cache
+	spatial.set_zoneable(true).unwrap();
+	spatial
+		.get_transform_cached(client.get_root(), max_age)
+		.await
+		.unwrap();
+	assert_eq!(fetches(), 2);
+
+	// Moved by this client
+	spatial.set_local_transform(Transform::identity()).unwrap();
+	spatial
+		.get_transform_cached(client.get_root(), max_age)
+		.await
+		.unwrap();
+	assert_eq!(fetches(), 3);
+}
+
 #[tokio::test]
 async fn fusion_spatial_import_export() {
 	use super::client::Client;
@@ -226,6 +605,50 @@ async fn fusion_spatial_import_export() {
 	assert_eq!(relative_transform, Transform::identity());
 }
 
+#[tokio::test]
+async fn fusion_spatial_import_owned() {
+	use super::client::Client;
+	let (client, _) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	let exported = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+
+	// Only exported as a reference, so ownership can't be taken
+	let ref_uid = exported.export_spatial().await.unwrap();
+	assert!(Spatial::import_owned(&client, ref_uid).await.is_err());
+
+	let uid = exported.export_spatial_transferable().await.unwrap();
+	let imported = Spatial::import_owned(&client, uid).await.unwrap();
+	assert!(imported.is_same(&exported).await.unwrap());
+	imported
+		.set_local_transform(Transform::from_translation([0.0, 1.0, 0.0]))
+		.unwrap();
+	let transform = exported.get_transform(client.get_root()).await.unwrap();
+	assert_eq!(transform.translation, Some([0.0, 1.0, 0.0].into()));
+
+	// Already transferred
+	assert!(Spatial::import_owned(&client, uid).await.is_err());
+}
+
+#[tokio::test]
+async fn fusion_spatial_import_is_same() {
+	use super::client::Client;
+	let (client, _) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	let exported = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	let other = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	let uid = exported.export_spatial().await.unwrap();
+	let imported = SpatialRef::import(&client, uid).await.unwrap();
+
+	assert_ne!(
+		imported.node().get_id().unwrap(),
+		exported.node().get_id().unwrap()
+	);
+	assert!(imported.is_same(&exported).await.unwrap());
+	assert!(!imported.is_same(&other).await.unwrap());
+}
+
 #[tokio::test]
 async fn fusion_zone() {
 	let (client, event_loop) = crate::client::Client::connect_with_async_loop()
@@ -292,6 +715,332 @@ async fn fusion_zone() {
 	tokio::select! {
 		biased;
 		_ = tokio::signal::ctrl_c() => (),
-		e = event_loop => e.unwrap().unwrap(),
+		e = event_loop => e.unwrap(),
+	}
+}
+
+#[tokio::test]
+async fn fusion_zone_zoneable_toggle() {
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	let field = crate::fields::Field::create(
+		client.get_root(),
+		Transform::identity(),
+		crate::fields::Shape::Sphere(0.1),
+	)
+	.unwrap();
+	let spatial = Spatial::create(client.get_root(), Transform::none(), false).unwrap();
+	let spatial_id = spatial.node().get_id().unwrap();
+
+	#[derive(Debug, PartialEq)]
+	enum ZoneEvent {
+		Enter(u64),
+		Capture(u64),
+		Release(u64),
+	}
+	struct ZoneableTest {
+		zone: Zone,
+		events: tokio::sync::mpsc::UnboundedSender<ZoneEvent>,
+	}
+	impl ZoneHandler for ZoneableTest {
+		fn enter(&mut self, spatial: SpatialRef) {
+			self.zone.capture(&spatial).unwrap();
+			let _ = self
+				.events
+				.send(ZoneEvent::Enter(spatial.node().get_id().unwrap()));
+		}
+		fn capture(&mut self, spatial: Spatial) {
+			let _ = self
+				.events
+				.send(ZoneEvent::Capture(spatial.node().get_id().unwrap()));
+		}
+		fn release(&mut self, id: u64) {
+			let _ = self.events.send(ZoneEvent::Release(id));
+		}
+		fn leave(&mut self, _id: u64) {}
+	}
+	let (events_tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+	let zone = Zone::create(client.get_root(), Transform::none(), &field).unwrap();
+	let zone = zone
+		.alias()
+		.wrap(ZoneableTest {
+			zone,
+			events: events_tx,
+		})
+		.unwrap();
+	async fn next_event(
+		events: &mut tokio::sync::mpsc::UnboundedReceiver<ZoneEvent>,
+	) -> Option<ZoneEvent> {
+		tokio::time::timeout(std::time::Duration::from_millis(500), events.recv())
+			.await
+			.ok()
+			.flatten()
+	}
+
+	// not zoneable, so the zone shouldn't see it
+	zone.node().update().unwrap();
+	assert_eq!(next_event(&mut events).await, None);
+
+	spatial.set_zoneable(true).unwrap();
+	zone.node().update().unwrap();
+	assert_eq!(
+		next_event(&mut events).await,
+		Some(ZoneEvent::Enter(spatial_id))
+	);
+	assert_eq!(
+		next_event(&mut events).await,
+		Some(ZoneEvent::Capture(spatial_id))
+	);
+
+	// turning zoneable off should release it from the zone
+	spatial.set_zoneable(false).unwrap();
+	assert_eq!(
+		next_event(&mut events).await,
+		Some(ZoneEvent::Release(spatial_id))
+	);
+}
+
+#[test]
+fn spatial_members_table() {
+	use stardust_xr::schemas::protocol::{MemberType, Protocol, Side, SPATIAL_PROTOCOL};
+
+	let protocol = Protocol::parse(SPATIAL_PROTOCOL).unwrap();
+	let aspect = protocol
+		.aspects
+		.iter()
+		.find(|a| a.name == "Spatial")
+		.unwrap();
+	assert_eq!(SPATIAL_MEMBERS.len(), aspect.members.len());
+	for member in &aspect.members {
+		assert!(SPATIAL_MEMBERS.contains(&(
+			member.opcode,
+			member.name.as_str(),
+			member.side,
+			member._type
+		)));
+	}
+	assert!(SPATIAL_MEMBERS
+		.iter()
+		.any(
+			|(opcode, _, side, _type)| *opcode == SPATIAL_SET_SPATIAL_PARENT_SERVER_OPCODE
+				&& *side == Side::Server
+				&& *_type == MemberType::Signal
+		));
+}
+
+#[tokio::test]
+async fn fusion_spatial_subscribe_transform() {
+	use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+	struct TransformUpdates(UnboundedSender<Transform>);
+	impl TransformSubscriptionHandler for TransformUpdates {
+		fn transform_updated(&mut self, transform: Transform) {
+			let _ = self.0.send(transform);
+		}
+	}
+
+	let (client, _event_loop) = crate::client::Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	let spatial = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	let (tx, mut rx) = unbounded_channel();
+	let _subscription = TransformSubscription::create(&spatial, client.get_root())
+		.unwrap()
+		.wrap(TransformUpdates(tx))
+		.unwrap();
+
+	let initial = rx.recv().await.unwrap();
+	assert_eq!(initial.translation, Some([0.0; 3].into()));
+
+	spatial
+		.set_local_transform(Transform::from_translation([0.0, 1.0, 0.0]))
+		.unwrap();
+	let moved = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+		loop {
+			let transform = rx.recv().await.unwrap();
+			if transform.translation != initial.translation {
+				break transform;
+			}
+		}
+	})
+	.await
+	.expect("Never got the moved transform");
+	assert_eq!(moved.translation, Some([0.0, 1.0, 0.0].into()));
+}
+
+#[test]
+fn fusion_transform_pose_conversion() {
+	let pose = Pose::new(vec3!(1.0, 2.0, 3.0), quat!(from_euler 0.0, 1.0, 0.0));
+	let transform = Transform::from(pose);
+	assert_eq!(transform.scale, None);
+	assert_eq!(Pose::try_from(transform), Ok(pose));
+
+	// scale is dropped going back to a pose
+	let scaled =
+		Transform::from_translation_rotation_scale(pose.translation, pose.rotation, [2.0; 3]);
+	assert_eq!(Pose::try_from(scaled), Ok(pose));
+
+	assert_eq!(
+		Pose::try_from(Transform::from_translation(pose.translation)),
+		Err(IncompletePose("rotation"))
+	);
+	assert_eq!(
+		Pose::try_from(Transform::none()),
+		Err(IncompletePose("translation"))
+	);
+}
+
+#[tokio::test]
+async fn fusion_spatial_get_spatial_children() {
+	let (client, _event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+	let parent = Spatial::create(client.get_root(), Transform::identity(), false).unwrap();
+	let child_a = Spatial::create(&parent, Transform::identity(), false).unwrap();
+	let child_b = Spatial::create(&parent, Transform::identity(), false).unwrap();
+	let _grandchild = Spatial::create(&child_a, Transform::identity(), false).unwrap();
+
+	let children = parent.get_spatial_children().await.unwrap();
+	assert_eq!(children.len(), 2);
+	for child in &children {
+		let is_a = child.is_same(&child_a).await.unwrap();
+		let is_b = child.is_same(&child_b).await.unwrap();
+		assert!(is_a != is_b);
+	}
+	assert!(child_b.get_spatial_children().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn fusion_spatial_create_with_id() {
+	use stardust_xr::{
+		mock::MockServer,
+		schemas::flex::{deserialize, serialize},
+	};
+
+	let server = MockServer::new();
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let spatial =
+		Spatial::create_with_id(client.get_root(), 42, Transform::identity(), false).unwrap();
+	assert_eq!(spatial.node().get_id().unwrap(), 42);
+	let transform = Transform::from_translation([0.0, 1.0, 0.0]);
+	spatial.set_local_transform(transform).unwrap();
+	// a message for the fixed id reaches the node like any other
+	client
+		.send_raw_signal(
+			42,
+			SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE,
+			&serialize(transform).unwrap(),
+			Vec::new(),
+		)
+		.unwrap();
+	client.flush().await.unwrap();
+
+	let set_transforms = || {
+		server
+			.signals()
+			.into_iter()
+			.filter(|s| s.method == SPATIAL_SET_LOCAL_TRANSFORM_SERVER_OPCODE)
+			.collect::<Vec<_>>()
+	};
+	tokio::time::timeout(Duration::from_secs(1), async {
+		while set_transforms().len() < 2 {
+			tokio::task::yield_now().await;
+		}
+	})
+	.await
+	.unwrap();
+	for signal in set_transforms() {
+		assert_eq!(signal.node, 42);
+		assert_eq!(deserialize::<Transform>(&signal.data).unwrap(), transform);
+	}
+	let create = server
+		.signals()
+		.into_iter()
+		.find(|s| s.method == INTERFACE_CREATE_SPATIAL_SERVER_OPCODE)
+		.unwrap();
+	let (id, ..): (u64, u64, Transform, bool) = deserialize(&create.data).unwrap();
+	assert_eq!(id, 42);
+}
+
+#[tokio::test]
+async fn fusion_spatial_world_transform() {
+	use super::client::Client;
+	use stardust_xr::{
+		mock::MockServer,
+		schemas::flex::{deserialize, serialize},
+	};
+
+	let server = MockServer::new();
+	// The transform depends on what it's relative to, so using the wrong node shows up
+	server.on_method(SPATIAL_REF_GET_TRANSFORM_SERVER_OPCODE, |_, data| {
+		let relative_to: u64 = deserialize(data).unwrap();
+		Ok(serialize(Transform::from_translation([relative_to as f32, 0.0, 0.0])).unwrap())
+	});
+	let (client, _event_loop) = Client::from_connection_with_async_loop(server.connect().unwrap())
+		.await
+		.unwrap();
+
+	let spatial = SpatialRef::from_id(&client, 5, false);
+	assert_eq!(
+		spatial.world_transform().await.unwrap(),
+		spatial.get_transform(client.get_root()).await.unwrap()
+	);
+	let other = SpatialRef::from_id(&client, 6, false);
+	assert_ne!(
+		spatial.world_transform().await.unwrap(),
+		spatial.get_transform(&other).await.unwrap()
+	);
+}
+
+#[test]
+fn spatial_animator_converges() {
+	let mut animator = Animator::new(Transform::none(), Duration::from_secs(1), |t| t);
+	assert!(animator.is_finished());
+	assert_eq!(animator.current(), Transform::identity());
+
+	let rotation = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+	animator.set_target(Transform::from_translation_rotation_scale(
+		[1.0, 0.0, 0.0],
+		rotation,
+		[2.0, 2.0, 2.0],
+	));
+	let frame = FrameInfo {
+		delta: 0.1,
+		elapsed: 0.0,
+	};
+	let mut last_distance = f32::INFINITY;
+	for _ in 0..5 {
+		let translation = Vec3::from(animator.update(&frame).translation.unwrap());
+		let distance = translation.distance(Vec3::X);
+		assert!(distance < last_distance);
+		last_distance = distance;
 	}
+	let halfway = animator.current();
+	assert!((halfway.translation.unwrap().x - 0.5).abs() < 0.001);
+	assert!((halfway.scale.unwrap().y - 1.5).abs() < 0.001);
+	assert!(!animator.is_finished());
+
+	for _ in 0..10 {
+		animator.update(&frame);
+	}
+	assert!(animator.is_finished());
+	let done = animator.current();
+	assert!(Vec3::from(done.translation.unwrap()).abs_diff_eq(Vec3::X, 0.0001));
+	assert!(Quat::from(done.rotation.unwrap()).abs_diff_eq(rotation, 0.0001));
+	assert!(Vec3::from(done.scale.unwrap()).abs_diff_eq(Vec3::splat(2.0), 0.0001));
+
+	// retargeting only one component keeps the rest where they are
+	animator.set_target(Transform::from_translation([0.0, 1.0, 0.0]));
+	animator.update(&FrameInfo {
+		delta: 1.0,
+		elapsed: 0.0,
+	});
+	let retargeted = animator.current();
+	assert!(Vec3::from(retargeted.translation.unwrap()).abs_diff_eq(Vec3::Y, 0.0001));
+	assert!(Vec3::from(retargeted.scale.unwrap()).abs_diff_eq(Vec3::splat(2.0), 0.0001));
 }
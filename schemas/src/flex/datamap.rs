@@ -1,10 +1,17 @@
-use flexbuffers::{DeserializationError, Reader, ReaderError};
+use flexbuffers::{DeserializationError, FlexBufferType, Reader, ReaderError};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// A map that contains non-spatial data in a map in flexbuffers format.
 #[derive(Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Datamap(Vec<u8>);
 impl Datamap {
+	/// Create a datamap with no keys, for things like input methods without any extra data.
+	pub fn empty() -> Self {
+		let mut fbb = flexbuffers::Builder::default();
+		fbb.start_map().end_map();
+		Datamap(fbb.view().to_vec())
+	}
 	/// Create a new datamap from a serialized flexbuffer map
 	pub fn from_raw(raw: Vec<u8>) -> Result<Self, ReaderError> {
 		flexbuffers::Reader::get_root(raw.as_slice())?.get_map()?;
@@ -20,6 +27,10 @@ impl Datamap {
 			.get_map()
 			.unwrap())
 	}
+	/// If the map has no keys.
+	pub fn is_empty(&self) -> bool {
+		self.with_data(|map| map.is_empty())
+	}
 	/// Get a reference to the raw binary data
 	pub fn raw(&self) -> &Vec<u8> {
 		&self.0
@@ -33,7 +44,139 @@ impl Datamap {
 	pub fn deserialize<'de, T: Deserialize<'de>>(&'de self) -> Result<T, DeserializationError> {
 		flexbuffers::from_slice(&self.0)
 	}
+
+	/// Compare the top-level keys of this map against a previous version of it, useful for reacting to edges such as a button just being pressed.
+	pub fn diff(&self, previous: &Datamap) -> DatamapDiff {
+		self.with_data(|current| {
+			previous.with_data(|previous| {
+				let mut diff = DatamapDiff::default();
+				for key in current.iter_keys() {
+					match (current.index(key), previous.index(key)) {
+						(Ok(current), Ok(previous)) => {
+							if current.to_string() != previous.to_string() {
+								diff.changed.push(key.to_string());
+							}
+						}
+						_ => diff.added.push(key.to_string()),
+					}
+				}
+				for key in previous.iter_keys() {
+					if current.index(key).is_err() {
+						diff.removed.push(key.to_string());
+					}
+				}
+				diff
+			})
+		})
+	}
+
+	/// Check that every key in `schema` is here with the right kind of value, so a handler can trust them without risking a panic.
+	/// Keys not in the schema are ignored.
+	pub fn validate(&self, schema: &DatamapSchema) -> Result<(), DatamapValidationError> {
+		self.with_data(|map| {
+			for (key, (kind, required)) in &schema.keys {
+				let Ok(value) = map.index(key.as_str()) else {
+					if *required {
+						return Err(DatamapValidationError::MissingKey(key.clone()));
+					}
+					continue;
+				};
+				let found = value.flexbuffer_type();
+				if !kind.matches(found) {
+					return Err(DatamapValidationError::WrongKind {
+						key: key.clone(),
+						expected: *kind,
+						found,
+					});
+				}
+			}
+			Ok(())
+		})
+	}
+}
+
+/// The kind of value a key in a `DatamapSchema` should have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DatamapValueKind {
+	Bool,
+	/// Signed or unsigned integers.
+	Int,
+	/// Any number, as integers are often sent where a float is expected.
+	Float,
+	String,
+	Blob,
+	/// Any vector, typed or not.
+	Vector,
+	Map,
+}
+impl DatamapValueKind {
+	fn matches(self, flex_type: FlexBufferType) -> bool {
+		let flex_type = flex_type.to_direct().unwrap_or(flex_type);
+		match self {
+			DatamapValueKind::Bool => flex_type == FlexBufferType::Bool,
+			DatamapValueKind::Int => flex_type.is_int() || flex_type.is_uint(),
+			DatamapValueKind::Float => {
+				flex_type.is_int() || flex_type.is_uint() || flex_type.is_float()
+			}
+			DatamapValueKind::String => flex_type == FlexBufferType::String,
+			DatamapValueKind::Blob => flex_type == FlexBufferType::Blob,
+			DatamapValueKind::Vector => flex_type.is_vector() && flex_type != FlexBufferType::Map,
+			DatamapValueKind::Map => flex_type == FlexBufferType::Map,
+		}
+	}
+}
+
+/// The keys a `Datamap` is expected to have, for `Datamap::validate`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DatamapSchema {
+	keys: BTreeMap<String, (DatamapValueKind, bool)>,
+}
+impl DatamapSchema {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	/// The key must be present with this kind of value.
+	pub fn required(mut self, key: impl ToString, kind: DatamapValueKind) -> Self {
+		self.keys.insert(key.to_string(), (kind, true));
+		self
+	}
+	/// The key may be missing, but if present must have this kind of value.
+	pub fn optional(mut self, key: impl ToString, kind: DatamapValueKind) -> Self {
+		self.keys.insert(key.to_string(), (kind, false));
+		self
+	}
+}
+
+/// Why a `Datamap` didn't match a `DatamapSchema`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DatamapValidationError {
+	#[error("Required key \"{0}\" is missing")]
+	MissingKey(String),
+	#[error("Key \"{key}\" should be {expected:?} but is {found:?}")]
+	WrongKind {
+		key: String,
+		expected: DatamapValueKind,
+		found: FlexBufferType,
+	},
+}
+
+/// Which top-level keys differ between two versions of a `Datamap`, from `Datamap::diff`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DatamapDiff {
+	/// Keys only in the current map.
+	pub added: Vec<String>,
+	/// Keys only in the previous map.
+	pub removed: Vec<String>,
+	/// Keys in both maps whose values differ.
+	pub changed: Vec<String>,
+}
+impl DatamapDiff {
+	/// If both maps had the same keys and values.
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
 }
+impl crate::Schema for Datamap {}
 impl core::fmt::Debug for Datamap {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		let mut f = f.debug_struct("Datamap");
@@ -45,3 +188,97 @@ impl core::fmt::Debug for Datamap {
 		.finish_non_exhaustive()
 	}
 }
+
+#[test]
+fn datamap_diff() {
+	#[derive(Serialize)]
+	struct Buttons {
+		grab: bool,
+		select: f32,
+	}
+	let previous = Datamap::from_typed(Buttons {
+		grab: false,
+		select: 0.5,
+	})
+	.unwrap();
+	let current = Datamap::from_typed(Buttons {
+		grab: true,
+		select: 0.5,
+	})
+	.unwrap();
+
+	assert!(current.diff(&current).is_empty());
+	assert_eq!(
+		current.diff(&previous),
+		DatamapDiff {
+			added: vec![],
+			removed: vec![],
+			changed: vec!["grab".to_string()],
+		}
+	);
+
+	let empty = Datamap::from_typed(std::collections::HashMap::<String, bool>::new()).unwrap();
+	let diff = current.diff(&empty);
+	assert_eq!(diff.added, vec!["grab".to_string(), "select".to_string()]);
+	assert_eq!(empty.diff(&current).removed, diff.added);
+}
+
+#[test]
+fn datamap_empty() {
+	let empty = Datamap::empty();
+	assert!(empty.is_empty());
+	let round_tripped = Datamap::from_raw(empty.raw().clone()).unwrap();
+	assert_eq!(round_tripped, empty);
+	assert!(round_tripped.is_empty());
+	assert_eq!(
+		empty
+			.deserialize::<std::collections::HashMap<String, f32>>()
+			.unwrap(),
+		std::collections::HashMap::new()
+	);
+
+	#[derive(Serialize)]
+	struct Buttons {
+		grab: bool,
+	}
+	assert!(!Datamap::from_typed(Buttons { grab: true })
+		.unwrap()
+		.is_empty());
+}
+
+#[test]
+fn datamap_validate() {
+	#[derive(Serialize)]
+	struct Buttons {
+		grab: bool,
+		select: f32,
+		name: String,
+	}
+	let datamap = Datamap::from_typed(Buttons {
+		grab: true,
+		select: 0.5,
+		name: "controller".to_string(),
+	})
+	.unwrap();
+
+	let schema = DatamapSchema::new()
+		.required("grab", DatamapValueKind::Bool)
+		.required("select", DatamapValueKind::Float)
+		.optional("context", DatamapValueKind::Map);
+	assert_eq!(datamap.validate(&schema), Ok(()));
+
+	let wrong_kind = schema.clone().required("name", DatamapValueKind::Float);
+	assert_eq!(
+		datamap.validate(&wrong_kind),
+		Err(DatamapValidationError::WrongKind {
+			key: "name".to_string(),
+			expected: DatamapValueKind::Float,
+			found: FlexBufferType::String,
+		})
+	);
+	let missing = schema.required("scroll", DatamapValueKind::Vector);
+	assert_eq!(
+		datamap.validate(&missing),
+		Err(DatamapValidationError::MissingKey("scroll".to_string()))
+	);
+}
@@ -12,8 +12,12 @@ use serde::{
 use std::{borrow::BorrowMut, cell::RefCell, f32::consts::PI, fmt::Display, marker::PhantomData};
 
 mod datamap;
+mod pool;
+mod value;
 pub use datamap::*;
 pub use flexbuffers;
+pub use pool::*;
+pub use value::*;
 
 #[derive(Debug, thiserror::Error)]
 pub enum FlexSerializeError {
@@ -33,12 +37,32 @@ impl serde::ser::Error for FlexSerializeError {
 
 /// Serialize the given data into flexbuffers, stripping struct field names off
 /// and putting structs into vectors to save space and computation.
+///
+/// Flexbuffers are always little-endian no matter the host, so the bytes (and `deserialize`'s reading of them) are the same on every architecture.
 pub fn serialize<S: Serialize>(to_serialize: S) -> Result<Vec<u8>, FlexSerializeError> {
 	let mut fbb = flexbuffers::Builder::default();
-	let fs = FlexSerializer { fbb: &mut fbb };
-	to_serialize.serialize(fs)?;
+	serialize_into(&mut fbb, to_serialize)?;
 	Ok(fbb.take_buffer())
 }
+/// Like `serialize`, but reuses an existing builder's allocations. The result is in `fbb.view()`.
+pub fn serialize_into<S: Serialize>(
+	fbb: &mut flexbuffers::Builder,
+	to_serialize: S,
+) -> Result<(), FlexSerializeError> {
+	fbb.reset();
+	let fs = FlexSerializer { fbb };
+	to_serialize.serialize(fs)
+}
+
+/// Name of the newtype struct `FlexValue::Bytes` wraps its bytes in, so they're written as a blob instead of a vector of `u8` like any other byte slice.
+pub(crate) const BLOB_NEWTYPE: &str = "$flex::Blob";
+/// The bytes inside a `BLOB_NEWTYPE`, which serialize as a plain vector of `u8`.
+fn blob_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FlexSerializeError> {
+	let data = serialize(value)?;
+	flexbuffers::Reader::get_root(data.as_slice())
+		.map(|bytes| bytes.as_vector().iter().map(|b| b.as_u8()).collect())
+		.map_err(|e| FlexSerializeError::Serde(e.to_string()))
+}
 
 struct FlexSerializer<'b> {
 	fbb: &'b mut flexbuffers::Builder,
@@ -156,9 +180,14 @@ impl<'b> Serializer for FlexSerializer<'b> {
 
 	fn serialize_newtype_struct<T: Serialize + ?Sized>(
 		self,
-		_name: &'static str,
+		name: &'static str,
 		value: &T,
 	) -> Result<Self::Ok, FlexSerializeError> {
+		if name == BLOB_NEWTYPE {
+			self.fbb
+				.build_singleton(flexbuffers::Blob(blob_bytes(value)?.as_slice()));
+			return Ok(());
+		}
 		value.serialize(self)
 	}
 
@@ -361,9 +390,14 @@ impl<'parent, 'fvb> Serializer for FlexVecSerializerWrapper<'parent, 'fvb> {
 
 	fn serialize_newtype_struct<T: Serialize + ?Sized>(
 		self,
-		_name: &'static str,
+		name: &'static str,
 		value: &T,
 	) -> Result<Self::Ok, FlexSerializeError> {
+		if name == BLOB_NEWTYPE {
+			self.0
+				.push(flexbuffers::Blob(blob_bytes(value)?.as_slice()));
+			return Ok(());
+		}
 		value.serialize(self)
 	}
 
@@ -832,9 +866,16 @@ impl<'parent, 'fvb> Serializer for FlexMapSerializerWrapper<'parent, 'fvb> {
 
 	fn serialize_newtype_struct<T: Serialize + ?Sized>(
 		self,
-		_name: &'static str,
+		name: &'static str,
 		value: &T,
 	) -> Result<Self::Ok, FlexSerializeError> {
+		if name == BLOB_NEWTYPE {
+			self.1.push(
+				&self.0.key,
+				flexbuffers::Blob(blob_bytes(value)?.as_slice()),
+			);
+			return Ok(());
+		}
 		value.serialize(self)
 	}
 
@@ -949,6 +990,28 @@ pub fn deserialize<'a, T: Deserialize<'a>>(data: &'a [u8]) -> Result<T, Deserial
 	T::deserialize(deserializer)
 }
 
+/// Deserialize like `deserialize`, but error if a struct, tuple or vector has more elements than the type reads.
+///
+/// Since struct fields are read by position, `deserialize` silently ignores any extra trailing fields,
+/// which is what lets older clients understand newer servers. That also hides protocol drift,
+/// so this is meant for tests and debugging tools, not the hot path.
+/// The check applies to structs, tuples and vectors nested in each other at any depth,
+/// but not to anything inside a map (including the values of a `HashMap` field) or an enum variant.
+pub fn deserialize_strict<'a, T: Deserialize<'a>>(
+	data: &'a [u8],
+) -> Result<T, DeserializationError> {
+	let root = flexbuffers::Reader::get_root(data)?;
+	T::deserialize(StrictDeserializer(root))
+}
+
+/// Get the raw flexbuffers reader for serialized data, to look at its structure directly instead of deserializing it into a type.
+///
+/// Useful for debugging datamaps and unknown signals, or peeking at fields the protocol doesn't model.
+/// Structs are vectors of their fields in declaration order (see `deserialize`), so a field is read with `reader(data)?.as_vector().idx(i)`.
+pub fn reader(data: &[u8]) -> Result<flexbuffers::Reader<&[u8]>, ReaderError> {
+	flexbuffers::Reader::get_root(data)
+}
+
 struct ReaderIteratorWrapper<'d>(ReaderIterator<&'d [u8]>);
 
 impl<'de> SeqAccess<'de> for ReaderIteratorWrapper<'de> {
@@ -973,6 +1036,24 @@ impl<'de> SeqAccess<'de> for ReaderIteratorWrapper<'de> {
 	}
 }
 
+/// Look up the name of the variant with the integer index in `tag`.
+fn variant_by_index<'de>(
+	variants: &'static [&'static str],
+	tag: &flexbuffers::Reader<&'de [u8]>,
+) -> Result<&'de str, DeserializationError> {
+	let index = tag.as_i64();
+	usize::try_from(index)
+		.ok()
+		.and_then(|index| variants.get(index))
+		.copied()
+		.ok_or_else(|| {
+			serde::de::Error::invalid_value(
+				serde::de::Unexpected::Signed(index),
+				&format!("a variant index below {}", variants.len()).as_str(),
+			)
+		})
+}
+
 struct EnumReader<'de> {
 	variant: &'de str,
 	value: Option<flexbuffers::Reader<&'de [u8]>>,
@@ -1138,10 +1219,17 @@ impl<'de> Deserializer<'de> for FlexbuffersDeserializer<'de> {
 		visitor.visit_newtype_struct(self)
 	}
 
+	/// Enums are accepted in any of these encodings:
+	/// - The variant name as a string, for unit variants.
+	/// - The variant index as an integer, for unit variants (what `serialize` writes).
+	/// - A map with the variant name as its only key and the content as its value.
+	/// - A vector of the variant index followed by the content, so newer variants can be sent without relying on names.
+	///
+	/// Variant indices follow the order the variants are declared in.
 	fn deserialize_enum<V>(
 		self,
 		_name: &'static str,
-		_variants: &'static [&'static str],
+		variants: &'static [&'static str],
 		visitor: V,
 	) -> Result<V::Value, Self::Error>
 	where
@@ -1149,11 +1237,23 @@ impl<'de> Deserializer<'de> for FlexbuffersDeserializer<'de> {
 	{
 		let (variant, value) = match self.0.flexbuffer_type() {
 			FlexBufferType::String => (self.0.as_str(), None),
+			FlexBufferType::UInt | FlexBufferType::Int => {
+				(variant_by_index(variants, &self.0)?, None)
+			}
 			FlexBufferType::Vector => {
 				let m = self.0.get_vector()?;
-				let variant = "t";
-				let value = Some(m.idx(0));
-				(variant, value)
+				let tag = m.idx(0);
+				if matches!(
+					tag.flexbuffer_type(),
+					FlexBufferType::UInt | FlexBufferType::Int
+				) {
+					let value = (m.len() > 1).then(|| m.idx(1));
+					(variant_by_index(variants, &tag)?, value)
+				} else {
+					let variant = "t";
+					let value = Some(tag);
+					(variant, value)
+				}
 			}
 			FlexBufferType::Map => {
 				let m = self.0.get_map()?;
@@ -1173,6 +1273,105 @@ impl<'de> Deserializer<'de> for FlexbuffersDeserializer<'de> {
 	}
 }
 
+struct StrictDeserializer<'de>(flexbuffers::Reader<&'de [u8]>);
+struct StrictSeqAccess<'de>(ReaderIterator<&'de [u8]>);
+
+impl<'de> SeqAccess<'de> for &mut StrictSeqAccess<'de> {
+	type Error = DeserializationError;
+
+	fn next_element_seed<T>(
+		&mut self,
+		seed: T,
+	) -> Result<Option<<T as DeserializeSeed<'de>>::Value>, Self::Error>
+	where
+		T: DeserializeSeed<'de>,
+	{
+		self.0
+			.next()
+			.map(|elem| seed.deserialize(StrictDeserializer(elem)))
+			.transpose()
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.0.len())
+	}
+}
+
+impl<'de> Deserializer<'de> for StrictDeserializer<'de> {
+	type Error = DeserializationError;
+
+	fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		if !self.0.flexbuffer_type().is_vector() {
+			return FlexbuffersDeserializer(self.0).deserialize_any(visitor);
+		}
+		let vector = self.0.get_vector()?;
+		let mut seq = StrictSeqAccess(vector.iter());
+		let value = visitor.visit_seq(&mut seq)?;
+		let unread = seq.0.len();
+		if unread > 0 {
+			return Err(serde::de::Error::custom(format!(
+				"{unread} unexpected trailing elements in a vector of {}",
+				vector.len()
+			)));
+		}
+		Ok(value)
+	}
+	serde::forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 str unit unit_struct bytes
+		ignored_any map identifier struct tuple tuple_struct seq string
+	}
+	fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		FlexbuffersDeserializer(self.0).deserialize_char(visitor)
+	}
+
+	fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		FlexbuffersDeserializer(self.0).deserialize_byte_buf(visitor)
+	}
+
+	fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		if self.0.flexbuffer_type() == FlexBufferType::Null {
+			visitor.visit_none()
+		} else {
+			visitor.visit_some(self)
+		}
+	}
+
+	fn deserialize_newtype_struct<V>(
+		self,
+		_name: &'static str,
+		visitor: V,
+	) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_enum<V>(
+		self,
+		name: &'static str,
+		variants: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		FlexbuffersDeserializer(self.0).deserialize_enum(name, variants, visitor)
+	}
+}
+
 #[test]
 fn round_trip_flex_serialize() {
 	use mint::{Quaternion, Vector2, Vector3};
@@ -1234,3 +1433,158 @@ fn round_trip_flex_serialize() {
 	let deserialized: TestStruct = deserialize(&serialized).unwrap();
 	assert_eq!(test_struct, deserialized, "Round trip lost data");
 }
+
+#[test]
+fn deserialize_enum_tags() {
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	enum Shape {
+		Point,
+		Sphere(f32),
+	}
+
+	// Map and name tagged, as written by plain flexbuffers
+	let named = flexbuffers::to_vec(Shape::Sphere(0.5)).unwrap();
+	assert_eq!(deserialize::<Shape>(&named).unwrap(), Shape::Sphere(0.5));
+	let named = flexbuffers::to_vec(Shape::Point).unwrap();
+	assert_eq!(deserialize::<Shape>(&named).unwrap(), Shape::Point);
+
+	// Index tagged
+	let mut fbb = flexbuffers::Builder::default();
+	let mut vector = fbb.start_vector();
+	vector.push(1_u32);
+	vector.push(0.5_f32);
+	vector.end_vector();
+	assert_eq!(
+		deserialize::<Shape>(fbb.view()).unwrap(),
+		Shape::Sphere(0.5)
+	);
+	let indexed = serialize(Shape::Point).unwrap();
+	assert_eq!(deserialize::<Shape>(&indexed).unwrap(), Shape::Point);
+
+	let unknown = serialize(7_u32).unwrap();
+	assert!(deserialize::<Shape>(&unknown).is_err());
+}
+
+#[test]
+fn deserialize_strict_trailing_elements() {
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Old {
+		a: u32,
+		b: String,
+	}
+	#[derive(Serialize)]
+	struct New {
+		a: u32,
+		b: String,
+		c: f32,
+	}
+	#[derive(Debug, PartialEq, Deserialize)]
+	struct Outer {
+		inner: Old,
+		list: Vec<Old>,
+	}
+	#[derive(Serialize)]
+	struct NewOuter {
+		inner: New,
+		list: Vec<New>,
+	}
+
+	let old = Old {
+		a: 1,
+		b: "b".to_string(),
+	};
+	let new = || New {
+		a: 1,
+		b: "b".to_string(),
+		c: 0.5,
+	};
+	let data = serialize(old).unwrap();
+	assert!(deserialize_strict::<Old>(&data).is_ok());
+
+	let data = serialize(new()).unwrap();
+	assert!(deserialize::<Old>(&data).is_ok());
+	let error = deserialize_strict::<Old>(&data).unwrap_err();
+	assert!(error.to_string().contains("trailing"), "{error}");
+
+	// Nested structs are checked too
+	let data = serialize(NewOuter {
+		inner: new(),
+		list: vec![new()],
+	})
+	.unwrap();
+	assert!(deserialize::<Outer>(&data).is_ok());
+	assert!(deserialize_strict::<Outer>(&data).is_err());
+}
+
+#[test]
+fn reader_walk_struct() {
+	#[derive(Serialize)]
+	struct Test {
+		id: u32,
+		name: String,
+		position: [f32; 2],
+	}
+
+	let data = serialize(Test {
+		id: 4,
+		name: "test".to_string(),
+		position: [1.0, 2.0],
+	})
+	.unwrap();
+	let root = reader(&data).unwrap();
+	assert!(root.flexbuffer_type().is_vector());
+	let fields = root.as_vector();
+	assert_eq!(fields.len(), 3);
+	assert_eq!(fields.idx(0).as_u32(), 4);
+	assert_eq!(fields.idx(1).as_str(), "test");
+	let position = fields
+		.idx(2)
+		.as_vector()
+		.iter()
+		.map(|c| c.as_f32())
+		.collect::<Vec<_>>();
+	assert_eq!(position, vec![1.0, 2.0]);
+
+	assert!(reader(&[]).is_err());
+}
+
+#[test]
+fn flex_wire_format_fixture() {
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Fixture {
+		id: u64,
+		offset: i16,
+		scale: f32,
+		time: f64,
+		name: String,
+		position: mint::Vector3<f32>,
+	}
+	let fixture = Fixture {
+		id: 0x0102030405060708,
+		offset: -2,
+		scale: 0.5,
+		time: 1.25,
+		name: "wire".to_string(),
+		position: [1.0, -2.0, 0.25].into(),
+	};
+	// Precomputed once, must come out byte for byte the same on every host (big-endian ones included) or the wire format broke
+	#[rustfmt::skip]
+	const BYTES: &[u8] = &[
+		// "wire"
+		4, 119, 105, 114, 101, 0, 0, 0,
+		// position as a fixed length vector of 3 f32s, then padding
+		0, 0, 128, 63, 0, 0, 0, 192, 0, 0, 128, 62, 0, 0, 0, 0,
+		// the struct as a vector of 6, least significant bytes first
+		6, 0, 0, 0, 0, 0, 0, 0,
+		8, 7, 6, 5, 4, 3, 2, 1,
+		254, 255, 255, 255, 255, 255, 255, 255,
+		0, 0, 0, 0, 0, 0, 224, 63,
+		0, 0, 0, 0, 0, 0, 244, 63,
+		63, 0, 0, 0, 0, 0, 0, 0,
+		64, 0, 0, 0, 0, 0, 0, 0,
+		// element types, then the root
+		11, 7, 15, 15, 20, 86, 54, 43, 1,
+	];
+	assert_eq!(serialize(&fixture).unwrap(), BYTES);
+	assert_eq!(deserialize::<Fixture>(BYTES).unwrap(), fixture);
+}
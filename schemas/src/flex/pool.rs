@@ -0,0 +1,94 @@
+use super::{serialize_into, FlexSerializeError};
+use serde::Serialize;
+use std::{
+	ops::{Deref, DerefMut},
+	sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// A pool of flexbuffer builders that can be shared between threads, so anything serializing at a high rate doesn't allocate a new builder every time.
+#[derive(Default)]
+pub struct FlexBuilderPool {
+	builders: Mutex<Vec<flexbuffers::Builder>>,
+}
+impl FlexBuilderPool {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	/// Take a builder out of the pool, or make a new one if they're all in use. It goes back into the pool when dropped.
+	pub fn get(&self) -> PooledBuilder<'_> {
+		let builder = self.builders().pop().unwrap_or_default();
+		PooledBuilder {
+			pool: self,
+			builder: Some(builder),
+		}
+	}
+	/// A panic while the lock was held can't leave the list of spare builders invalid, so poisoning is ignored.
+	fn builders(&self) -> MutexGuard<'_, Vec<flexbuffers::Builder>> {
+		self.builders.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Serialize the given data the same way as `serialize`, using a builder from this pool.
+	pub fn serialize<S: Serialize>(&self, to_serialize: S) -> Result<Vec<u8>, FlexSerializeError> {
+		let mut builder = self.get();
+		serialize_into(&mut builder, to_serialize)?;
+		Ok(builder.view().to_vec())
+	}
+}
+
+/// A builder borrowed from a `FlexBuilderPool`, returned to it when dropped.
+pub struct PooledBuilder<'a> {
+	pool: &'a FlexBuilderPool,
+	builder: Option<flexbuffers::Builder>,
+}
+impl Deref for PooledBuilder<'_> {
+	type Target = flexbuffers::Builder;
+
+	fn deref(&self) -> &Self::Target {
+		self.builder.as_ref().unwrap()
+	}
+}
+impl DerefMut for PooledBuilder<'_> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.builder.as_mut().unwrap()
+	}
+}
+impl Drop for PooledBuilder<'_> {
+	fn drop(&mut self) {
+		if let Some(mut builder) = self.builder.take() {
+			builder.reset();
+			self.pool.builders().push(builder);
+		}
+	}
+}
+
+#[test]
+fn flex_builder_pool_threads() {
+	use super::deserialize;
+	use std::sync::Arc;
+
+	let pool = Arc::new(FlexBuilderPool::new());
+	let threads = (0..8_u32)
+		.map(|thread| {
+			let pool = pool.clone();
+			std::thread::spawn(move || {
+				for i in 0..1000_u32 {
+					let message = (
+						thread,
+						i,
+						format!("message {i}"),
+						vec![i; (i % 16) as usize],
+					);
+					let data = pool.serialize(&message).unwrap();
+					assert_eq!(data, super::serialize(&message).unwrap());
+					let round_trip: (u32, u32, String, Vec<u32>) = deserialize(&data).unwrap();
+					assert_eq!(round_trip, message);
+				}
+			})
+		})
+		.collect::<Vec<_>>();
+	for thread in threads {
+		thread.join().unwrap();
+	}
+	assert!(pool.builders.lock().unwrap().len() <= 8);
+}
@@ -0,0 +1,310 @@
+use serde::{
+	de::{MapAccess, SeqAccess, Visitor},
+	ser::{SerializeMap, SerializeSeq},
+	Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::BTreeMap, fmt};
+
+/// Any value the flex format can hold, for data without a static type (such as plugin payloads), like `serde_json::Value` but for flex.
+///
+/// Serialize it with `serialize`/`deserialize` like any other type, or convert from and to concrete types with `FlexValue::from_typed`/`FlexValue::to_typed`.
+/// Maps are sorted by key, same as flexbuffers stores them.
+/// `Bytes` reads and writes flexbuffers blobs, while other byte slices still serialize as a vector of `u8`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum FlexValue {
+	#[default]
+	Null,
+	Bool(bool),
+	Int(i64),
+	UInt(u64),
+	Float(f64),
+	String(String),
+	Bytes(Vec<u8>),
+	Vec(Vec<FlexValue>),
+	Map(BTreeMap<String, FlexValue>),
+}
+impl FlexValue {
+	/// Turn any serializable value into a dynamic one by going through the flex format.
+	pub fn from_typed<T: Serialize>(typed: &T) -> Result<Self, super::FlexSerializeError> {
+		let data = super::serialize(typed)?;
+		super::deserialize(&data).map_err(|e| super::FlexSerializeError::Serde(e.to_string()))
+	}
+	/// Turn this into a concrete type by going through the flex format.
+	pub fn to_typed<T: for<'de> Deserialize<'de>>(&self) -> Result<T, super::FlexSerializeError> {
+		let data = super::serialize(self)?;
+		super::deserialize(&data).map_err(|e| super::FlexSerializeError::Serde(e.to_string()))
+	}
+
+	pub fn is_null(&self) -> bool {
+		matches!(self, FlexValue::Null)
+	}
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			FlexValue::Bool(b) => Some(*b),
+			_ => None,
+		}
+	}
+	/// Signed or unsigned integers that fit in an `i64`.
+	pub fn as_i64(&self) -> Option<i64> {
+		match self {
+			FlexValue::Int(i) => Some(*i),
+			FlexValue::UInt(u) => (*u).try_into().ok(),
+			_ => None,
+		}
+	}
+	/// Signed or unsigned integers that fit in a `u64`.
+	pub fn as_u64(&self) -> Option<u64> {
+		match self {
+			FlexValue::Int(i) => (*i).try_into().ok(),
+			FlexValue::UInt(u) => Some(*u),
+			_ => None,
+		}
+	}
+	/// Any number, as integers are often sent where a float is expected.
+	pub fn as_f64(&self) -> Option<f64> {
+		match self {
+			FlexValue::Int(i) => Some(*i as f64),
+			FlexValue::UInt(u) => Some(*u as f64),
+			FlexValue::Float(f) => Some(*f),
+			_ => None,
+		}
+	}
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			FlexValue::String(s) => Some(s),
+			_ => None,
+		}
+	}
+	pub fn as_bytes(&self) -> Option<&[u8]> {
+		match self {
+			FlexValue::Bytes(b) => Some(b),
+			_ => None,
+		}
+	}
+	pub fn as_vec(&self) -> Option<&[FlexValue]> {
+		match self {
+			FlexValue::Vec(v) => Some(v),
+			_ => None,
+		}
+	}
+	pub fn as_map(&self) -> Option<&BTreeMap<String, FlexValue>> {
+		match self {
+			FlexValue::Map(m) => Some(m),
+			_ => None,
+		}
+	}
+	/// Get the value at `key` if this is a map containing it.
+	pub fn get(&self, key: &str) -> Option<&FlexValue> {
+		self.as_map()?.get(key)
+	}
+}
+
+impl From<bool> for FlexValue {
+	fn from(b: bool) -> Self {
+		FlexValue::Bool(b)
+	}
+}
+impl From<i64> for FlexValue {
+	fn from(i: i64) -> Self {
+		FlexValue::Int(i)
+	}
+}
+impl From<u64> for FlexValue {
+	fn from(u: u64) -> Self {
+		FlexValue::UInt(u)
+	}
+}
+impl From<f64> for FlexValue {
+	fn from(f: f64) -> Self {
+		FlexValue::Float(f)
+	}
+}
+impl From<&str> for FlexValue {
+	fn from(s: &str) -> Self {
+		FlexValue::String(s.to_string())
+	}
+}
+impl From<String> for FlexValue {
+	fn from(s: String) -> Self {
+		FlexValue::String(s)
+	}
+}
+impl From<Vec<FlexValue>> for FlexValue {
+	fn from(v: Vec<FlexValue>) -> Self {
+		FlexValue::Vec(v)
+	}
+}
+impl From<BTreeMap<String, FlexValue>> for FlexValue {
+	fn from(m: BTreeMap<String, FlexValue>) -> Self {
+		FlexValue::Map(m)
+	}
+}
+
+impl Serialize for FlexValue {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			FlexValue::Null => serializer.serialize_unit(),
+			FlexValue::Bool(b) => serializer.serialize_bool(*b),
+			FlexValue::Int(i) => serializer.serialize_i64(*i),
+			FlexValue::UInt(u) => serializer.serialize_u64(*u),
+			FlexValue::Float(f) => serializer.serialize_f64(*f),
+			FlexValue::String(s) => serializer.serialize_str(s),
+			FlexValue::Bytes(b) => {
+				serializer.serialize_newtype_struct(super::BLOB_NEWTYPE, &RawBytes(b))
+			}
+			FlexValue::Vec(v) => {
+				let mut seq = serializer.serialize_seq(Some(v.len()))?;
+				for element in v {
+					seq.serialize_element(element)?;
+				}
+				seq.end()
+			}
+			FlexValue::Map(m) => {
+				let mut map = serializer.serialize_map(Some(m.len()))?;
+				for (key, value) in m {
+					map.serialize_entry(key, value)?;
+				}
+				map.end()
+			}
+		}
+	}
+}
+/// The bytes of a `FlexValue::Bytes`, which the flex serializer pulls back out to write as a blob.
+struct RawBytes<'a>(&'a [u8]);
+impl Serialize for RawBytes<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_bytes(self.0)
+	}
+}
+impl<'de> Deserialize<'de> for FlexValue {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_any(FlexValueVisitor)
+	}
+}
+
+struct FlexValueVisitor;
+impl<'de> Visitor<'de> for FlexValueVisitor {
+	type Value = FlexValue;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("any flex value")
+	}
+
+	fn visit_bool<E>(self, v: bool) -> Result<FlexValue, E> {
+		Ok(FlexValue::Bool(v))
+	}
+	fn visit_i64<E>(self, v: i64) -> Result<FlexValue, E> {
+		Ok(FlexValue::Int(v))
+	}
+	fn visit_u64<E>(self, v: u64) -> Result<FlexValue, E> {
+		Ok(FlexValue::UInt(v))
+	}
+	fn visit_f64<E>(self, v: f64) -> Result<FlexValue, E> {
+		Ok(FlexValue::Float(v))
+	}
+	fn visit_str<E>(self, v: &str) -> Result<FlexValue, E> {
+		Ok(FlexValue::String(v.to_string()))
+	}
+	fn visit_string<E>(self, v: String) -> Result<FlexValue, E> {
+		Ok(FlexValue::String(v))
+	}
+	fn visit_bytes<E>(self, v: &[u8]) -> Result<FlexValue, E> {
+		Ok(FlexValue::Bytes(v.to_vec()))
+	}
+	fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<FlexValue, E> {
+		Ok(FlexValue::Bytes(v))
+	}
+	fn visit_unit<E>(self) -> Result<FlexValue, E> {
+		Ok(FlexValue::Null)
+	}
+	fn visit_none<E>(self) -> Result<FlexValue, E> {
+		Ok(FlexValue::Null)
+	}
+	fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<FlexValue, D::Error> {
+		FlexValue::deserialize(deserializer)
+	}
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<FlexValue, A::Error> {
+		let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+		while let Some(element) = seq.next_element()? {
+			elements.push(element);
+		}
+		Ok(FlexValue::Vec(elements))
+	}
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<FlexValue, A::Error> {
+		let mut entries = BTreeMap::new();
+		while let Some((key, value)) = map.next_entry()? {
+			entries.insert(key, value);
+		}
+		Ok(FlexValue::Map(entries))
+	}
+}
+
+#[test]
+fn flex_value_round_trip() {
+	let mut inner = BTreeMap::new();
+	inner.insert("enabled".to_string(), true.into());
+	inner.insert("offset".to_string(), (-3_i64).into());
+	inner.insert(
+		"bytes".to_string(),
+		vec![FlexValue::UInt(1), FlexValue::UInt(2)].into(),
+	);
+	let mut root = BTreeMap::new();
+	root.insert("name".to_string(), "plugin".into());
+	root.insert("version".to_string(), 2_u64.into());
+	root.insert("scale".to_string(), 0.5.into());
+	root.insert(
+		"items".to_string(),
+		vec![FlexValue::Null, "a".into(), FlexValue::Map(inner)].into(),
+	);
+	let value = FlexValue::Map(root);
+
+	let data = super::serialize(&value).unwrap();
+	let round_tripped: FlexValue = super::deserialize(&data).unwrap();
+	assert_eq!(round_tripped, value);
+	assert_eq!(
+		round_tripped.get("name").and_then(FlexValue::as_str),
+		Some("plugin")
+	);
+	let items = round_tripped
+		.get("items")
+		.and_then(FlexValue::as_vec)
+		.unwrap();
+	assert!(items[0].is_null());
+	assert_eq!(items[2].get("offset").and_then(FlexValue::as_i64), Some(-3));
+
+	// Blobs read as bytes and are written back as blobs
+	let mut fbb = flexbuffers::Builder::default();
+	let mut map = fbb.start_map();
+	map.push("blob", flexbuffers::Blob(&[1_u8, 2, 3][..]));
+	map.end_map();
+	let blob: FlexValue = super::deserialize(fbb.view()).unwrap();
+	assert_eq!(
+		blob.get("blob").and_then(FlexValue::as_bytes),
+		Some(&[1, 2, 3][..])
+	);
+	let written_back: FlexValue = super::deserialize(&super::serialize(&blob).unwrap()).unwrap();
+	assert_eq!(written_back, blob);
+	let root_blob: FlexValue =
+		super::deserialize(&super::serialize(FlexValue::Bytes(vec![4, 5])).unwrap()).unwrap();
+	assert_eq!(root_blob, FlexValue::Bytes(vec![4, 5]));
+	let in_vec = FlexValue::Vec(vec![FlexValue::Bytes(vec![6])]);
+	let in_vec_back: FlexValue = super::deserialize(&super::serialize(&in_vec).unwrap()).unwrap();
+	assert_eq!(in_vec_back, in_vec);
+	// while plain byte slices stay vectors of u8
+	let slice: FlexValue = super::deserialize(&super::serialize(&[1_u8, 2][..]).unwrap()).unwrap();
+	assert_eq!(slice, vec![FlexValue::UInt(1), FlexValue::UInt(2)].into());
+
+	// and to and from concrete types
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Typed {
+		name: String,
+		values: Vec<u32>,
+	}
+	let typed = Typed {
+		name: "typed".to_string(),
+		values: vec![1, 2],
+	};
+	let dynamic = FlexValue::from_typed(&typed).unwrap();
+	assert_eq!(dynamic.as_vec().unwrap()[0].as_str(), Some("typed"));
+	assert_eq!(dynamic.to_typed::<Typed>().unwrap(), typed);
+}
@@ -17,3 +17,5 @@ pub mod flex;
 pub use zbus;
 pub mod dbus;
 pub mod protocol;
+pub mod schema;
+pub use schema::{assert_schema, Schema};
@@ -1,4 +1,5 @@
 use self::parser::convert;
+use convert_case::{Case, Casing};
 use kdl::{KdlDocument, KdlError};
 use thiserror::Error;
 
@@ -16,6 +17,25 @@ pub const ITEM_PROTOCOL: &str = include_str!("item.kdl");
 pub const ITEM_CAMERA_PROTOCOL: &str = include_str!("item_camera.kdl");
 pub const ITEM_PANEL_PROTOCOL: &str = include_str!("item_panel.kdl");
 
+/// The opcode of the member named `member` of aspect `aspect`, the same as its `Member::opcode` but without parsing the protocol,
+/// so it can be used in consts like `const SAVE_STATE: u64 = aspect_member_opcode("Root", "save_state");`.
+pub const fn aspect_member_opcode(aspect: &str, member: &str) -> u64 {
+	fnv_hash_str(fnv_hash_str(FNV_OFFSET_BASIS, aspect), member)
+}
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+/// FNV-1a of `s` the way `str`'s `Hash` impl feeds it to `FnvHasher`, with a trailing 0xff.
+const fn fnv_hash_str(mut hash: u64, s: &str) -> u64 {
+	let bytes = s.as_bytes();
+	let mut i = 0;
+	while i <= bytes.len() {
+		let byte = if i < bytes.len() { bytes[i] } else { 0xff };
+		hash ^= byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+		i += 1;
+	}
+	hash
+}
+
 #[derive(Debug)]
 pub struct Protocol {
 	pub version: u32,
@@ -31,6 +51,144 @@ impl Protocol {
 		let parsed: KdlDocument = sbs.parse().map_err(|p: KdlError| ParseError::Kdl(p))?;
 		convert(parsed)
 	}
+
+	/// Custom structs, enums, and unions that no member or other custom type in this protocol refers to.
+	///
+	/// This is a lint rather than a parse error, and types only used by other protocols show up here too.
+	pub fn unused_custom_types(&self) -> Vec<UnusedCustomType> {
+		let member_types = self
+			.aspects
+			.iter()
+			.flat_map(|a| &a.members)
+			.chain(self.interface.iter().flat_map(|i| &i.members))
+			.flat_map(|m| m.arguments.iter().map(|a| &a._type).chain(&m.return_type));
+		let custom_types = self
+			.custom_structs
+			.iter()
+			.flat_map(|s| s.fields.iter().map(|f| &f._type))
+			.chain(
+				self.custom_unions
+					.iter()
+					.flat_map(|u| u.options.iter().map(|o| &o._type)),
+			);
+		let mut used = Vec::new();
+		for argument_type in member_types.chain(custom_types) {
+			argument_type.custom_type_names(&mut used);
+		}
+		let is_used = |name: &str| used.iter().any(|u| u.eq_ignore_ascii_case(name));
+
+		let structs = self
+			.custom_structs
+			.iter()
+			.filter(|s| !is_used(&s.name))
+			.map(|s| UnusedCustomType::Struct(s.name.clone()));
+		let enums = self
+			.custom_enums
+			.iter()
+			.filter(|e| !is_used(&e.name))
+			.map(|e| UnusedCustomType::Enum(e.name.clone()));
+		let unions = self
+			.custom_unions
+			.iter()
+			.filter(|u| !is_used(&u.name))
+			.map(|u| UnusedCustomType::Union(u.name.clone()));
+		structs.chain(enums).chain(unions).collect()
+	}
+
+	/// Names that codegen's case conversion would change or merge together.
+	///
+	/// Types, aspects, enum variants and union options are converted to PascalCase, members, fields and arguments to snake_case.
+	/// A name that isn't already in that case generates code that doesn't match the protocol,
+	/// and two names in the same scope that convert to the same thing (such as `set_x` and `setX`) generate colliding items.
+	pub fn lint(&self) -> Vec<LintWarning> {
+		let mut warnings = Vec::new();
+
+		let type_names = self
+			.custom_structs
+			.iter()
+			.map(|s| &s.name)
+			.chain(self.custom_enums.iter().map(|e| &e.name))
+			.chain(self.custom_unions.iter().map(|u| &u.name))
+			.chain(self.aspects.iter().map(|a| &a.name));
+		lint_names(type_names, Case::Pascal, &mut warnings);
+
+		let member_scopes = self
+			.aspects
+			.iter()
+			.map(|a| &a.members)
+			.chain(self.interface.iter().map(|i| &i.members));
+		for members in member_scopes {
+			// Server side members go on the aspect trait and client side ones on the handler, so they can share names
+			for side in [Side::Server, Side::Client] {
+				let names = members.iter().filter(|m| m.side == side).map(|m| &m.name);
+				lint_names(names, Case::Snake, &mut warnings);
+			}
+			for member in members {
+				let arguments = member.arguments.iter().map(|a| &a.name);
+				lint_names(arguments, Case::Snake, &mut warnings);
+			}
+		}
+		for custom_struct in &self.custom_structs {
+			let fields = custom_struct.fields.iter().map(|f| &f.name);
+			lint_names(fields, Case::Snake, &mut warnings);
+		}
+		for custom_enum in &self.custom_enums {
+			lint_names(custom_enum.variants.iter(), Case::Pascal, &mut warnings);
+		}
+		for custom_union in &self.custom_unions {
+			let options = custom_union.options.iter().filter_map(|o| o.name.as_ref());
+			lint_names(options, Case::Pascal, &mut warnings);
+		}
+
+		warnings
+	}
+}
+
+/// Check that every name in one scope is already in `case` and that no 2 of them convert to the same name.
+fn lint_names<'a>(
+	names: impl Iterator<Item = &'a String>,
+	case: Case,
+	warnings: &mut Vec<LintWarning>,
+) {
+	let mut converted_names: Vec<(String, Vec<String>)> = Vec::new();
+	for name in names {
+		let converted = name.to_case(case);
+		if &converted != name {
+			warnings.push(LintWarning::Case {
+				name: name.clone(),
+				converted: converted.clone(),
+			});
+		}
+		match converted_names.iter_mut().find(|(c, _)| *c == converted) {
+			Some((_, names)) => names.push(name.clone()),
+			None => converted_names.push((converted, vec![name.clone()])),
+		}
+	}
+	for (converted, names) in converted_names {
+		if names.len() > 1 {
+			warnings.push(LintWarning::Collision { names, converted });
+		}
+	}
+}
+
+/// A naming problem found by `Protocol::lint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintWarning {
+	/// `name` isn't in the case codegen converts it to, so the generated name is `converted` instead.
+	Case { name: String, converted: String },
+	/// All of `names` are in the same scope and become `converted` in generated code, so they collide.
+	Collision {
+		names: Vec<String>,
+		converted: String,
+	},
+}
+
+/// A custom type declared in a protocol but never referred to, see `Protocol::unused_custom_types`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnusedCustomType {
+	Struct(String),
+	Enum(String),
+	Union(String),
 }
 
 #[derive(Debug)]
@@ -70,6 +228,7 @@ pub struct UnionOption {
 #[derive(Debug)]
 pub struct Aspect {
 	pub name: String,
+	pub id: u64, // FNV hash of the aspect name, what its member opcodes are hashed on top of
 	pub description: String,
 	pub inherits: Vec<String>,
 	pub members: Vec<Member>,
@@ -84,6 +243,21 @@ pub struct Member {
 	pub _type: MemberType,
 	pub arguments: Vec<Argument>,
 	pub return_type: Option<ArgumentType>,
+	/// If the return value may be absent, with `null` on the wire.
+	pub return_optional: bool,
+	/// A server-side signal the server confirms once it's processed it (`acked=true`), so the client can await it for sequencing.
+	/// On the wire it's a method call whose response carries no data.
+	pub acked: bool,
+}
+impl Member {
+	/// How this member is sent over the wire, acked signals are sent as methods to get their confirmation back.
+	pub fn wire_type(&self) -> MemberType {
+		if self.acked {
+			MemberType::Method
+		} else {
+			self._type
+		}
+	}
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -112,6 +286,8 @@ pub enum ArgumentType {
 	Color,
 	String,
 	Bytes,
+	/// A file descriptor, sent alongside the message with its index in the message's file descriptors on the wire.
+	Fd,
 	Vec(Box<ArgumentType>),
 	Map(Box<ArgumentType>),
 	NodeID,
@@ -126,12 +302,30 @@ pub enum ArgumentType {
 	},
 }
 
+impl ArgumentType {
+	fn custom_type_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+		match self {
+			ArgumentType::Enum(name) | ArgumentType::Union(name) | ArgumentType::Struct(name) => {
+				names.push(name)
+			}
+			ArgumentType::Vec2(t)
+			| ArgumentType::Vec3(t)
+			| ArgumentType::Vec(t)
+			| ArgumentType::Map(t) => t.custom_type_names(names),
+			_ => (),
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct Argument {
 	pub name: String,
 	pub description: Option<String>,
 	pub _type: ArgumentType,
 	pub optional: bool,
+	/// This node argument becomes the spatial parent of the created node (or of the node the member is called on), `spatial_parent=true`.
+	/// Lets clients track the spatial tree, such as to reject reparenting under a descendant right away.
+	pub spatial_parent: bool,
 }
 
 #[derive(Debug, Error)]
@@ -153,4 +347,227 @@ pub enum ParseError {
 		field_name: String,
 		field_type: String,
 	},
+	#[error("Only server-side signals without a return value can be acked, {0} is not")]
+	InvalidAcked(String),
+	#[error("Only required node arguments can be spatial parents, {0} is not")]
+	InvalidSpatialParent(String),
+}
+
+#[test]
+fn protocol_unused_custom_types() {
+	let protocol = Protocol::parse(
+		r#"
+		version 1
+		description ""
+		interface 0
+
+		struct "Used" {
+			description ""
+			field "nested" type="enum" enum="Nested"
+		}
+		enum "Nested" {
+			description ""
+			variant "A"
+		}
+		struct "Orphaned" {
+			description ""
+			field "value" type="float"
+		}
+
+		signal "set" side="server" {
+			description ""
+			argument "values" type="vec" member_type="struct" struct="used"
+		}
+	"#,
+	)
+	.unwrap();
+	assert_eq!(
+		protocol.unused_custom_types(),
+		vec![UnusedCustomType::Struct("Orphaned".to_string())]
+	);
+}
+
+#[test]
+fn protocol_bundled_no_unused_custom_types() {
+	for protocol in [
+		ROOT_PROTOCOL,
+		NODE_PROTOCOL,
+		SPATIAL_PROTOCOL,
+		FIELD_PROTOCOL,
+		DATA_PROTOCOL,
+		AUDIO_PROTOCOL,
+		DRAWABLE_PROTOCOL,
+		INPUT_PROTOCOL,
+		ITEM_PROTOCOL,
+		ITEM_CAMERA_PROTOCOL,
+		ITEM_PANEL_PROTOCOL,
+	] {
+		let protocol = Protocol::parse(protocol).unwrap();
+		assert_eq!(protocol.unused_custom_types(), vec![]);
+	}
+}
+
+#[test]
+fn protocol_aspect_member_opcode() {
+	for protocol in [
+		ROOT_PROTOCOL,
+		NODE_PROTOCOL,
+		SPATIAL_PROTOCOL,
+		FIELD_PROTOCOL,
+		DATA_PROTOCOL,
+		AUDIO_PROTOCOL,
+		DRAWABLE_PROTOCOL,
+		INPUT_PROTOCOL,
+		ITEM_PROTOCOL,
+		ITEM_CAMERA_PROTOCOL,
+		ITEM_PANEL_PROTOCOL,
+	] {
+		let protocol = Protocol::parse(protocol).unwrap();
+		for aspect in &protocol.aspects {
+			for member in &aspect.members {
+				assert_eq!(
+					aspect_member_opcode(&aspect.name, &member.name),
+					member.opcode,
+					"{}::{}",
+					aspect.name,
+					member.name
+				);
+			}
+		}
+	}
+}
+
+#[test]
+fn protocol_lint_collision() {
+	let protocol = Protocol::parse(
+		r#"
+		version 1
+		description ""
+
+		aspect "Thing" {
+			description ""
+
+			signal "set_x" side="server" {
+				description ""
+				argument "x" type="float"
+			}
+			signal "setX" side="server" {
+				description ""
+				argument "x" type="float"
+			}
+		}
+	"#,
+	)
+	.unwrap();
+	assert_eq!(
+		protocol.lint(),
+		vec![
+			LintWarning::Case {
+				name: "setX".to_string(),
+				converted: "set_x".to_string(),
+			},
+			LintWarning::Collision {
+				names: vec!["set_x".to_string(), "setX".to_string()],
+				converted: "set_x".to_string(),
+			},
+		]
+	);
+}
+
+#[test]
+fn protocol_bundled_lint_clean() {
+	for protocol in [
+		ROOT_PROTOCOL,
+		NODE_PROTOCOL,
+		SPATIAL_PROTOCOL,
+		FIELD_PROTOCOL,
+		DATA_PROTOCOL,
+		AUDIO_PROTOCOL,
+		DRAWABLE_PROTOCOL,
+		INPUT_PROTOCOL,
+		ITEM_PROTOCOL,
+		ITEM_CAMERA_PROTOCOL,
+		ITEM_PANEL_PROTOCOL,
+	] {
+		let protocol = Protocol::parse(protocol).unwrap();
+		assert_eq!(protocol.lint(), vec![]);
+	}
+}
+
+#[test]
+fn protocol_acked_signals() {
+	let protocol = Protocol::parse(
+		r#"
+		version 1
+		description ""
+		interface 0
+
+		signal "sync" side="server" acked=true {
+			description ""
+		}
+		signal "set" side="server" {
+			description ""
+		}
+	"#,
+	)
+	.unwrap();
+	let members = &protocol.interface.as_ref().unwrap().members;
+	assert!(members[0].acked);
+	assert_eq!(members[0].wire_type(), MemberType::Method);
+	assert!(!members[1].acked);
+	assert_eq!(members[1].wire_type(), MemberType::Signal);
+
+	let acked_client_signal = Protocol::parse(
+		r#"
+		version 1
+		description ""
+		interface 0
+
+		signal "notify" side="client" acked=true {
+			description ""
+		}
+	"#,
+	);
+	assert!(matches!(
+		acked_client_signal,
+		Err(ParseError::InvalidAcked(name)) if name == "notify"
+	));
+}
+
+#[test]
+fn protocol_spatial_parent_arguments() {
+	let protocol = Protocol::parse(
+		r#"
+		version 1
+		description ""
+		interface 0
+
+		signal "attach" side="server" {
+			description ""
+			argument "parent" type="node" aspect="SpatialRef" spatial_parent=true
+			argument "other" type="node" aspect="SpatialRef"
+		}
+	"#,
+	)
+	.unwrap();
+	let arguments = &protocol.interface.as_ref().unwrap().members[0].arguments;
+	assert!(arguments[0].spatial_parent);
+	assert!(!arguments[1].spatial_parent);
+
+	let not_a_node = Protocol::parse(
+		r#"
+		version 1
+		description ""
+		interface 0
+
+		signal "attach" side="server" {
+			description ""
+			argument "parent" type="string" spatial_parent=true
+		}
+	"#,
+	);
+	assert!(matches!(
+		not_a_node,
+		Err(ParseError::InvalidSpatialParent(name)) if name == "parent"
+	));
 }
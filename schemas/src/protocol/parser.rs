@@ -154,8 +154,14 @@ fn convert_aspect(aspect: &KdlNode) -> Result<Aspect, ParseError> {
 			})
 		})
 		.collect::<Result<Vec<_>, ParseError>>()?;
+	let id = {
+		let mut hasher = FnvHasher::default();
+		name.hash(&mut hasher);
+		hasher.finish()
+	};
 	Ok(Aspect {
 		name,
+		id,
 		description,
 		inherits,
 		members,
@@ -200,14 +206,22 @@ fn convert_member(member: &KdlNode, mut hasher: FnvHasher) -> Result<Member, Par
 		.map(convert_argument)
 		.collect::<Result<Vec<_>, ParseError>>()?;
 
-	let return_type = member
+	let return_node = member
 		.children()
 		.unwrap()
 		.nodes()
 		.iter()
-		.find(|n| n.name().value() == "return")
+		.find(|n| n.name().value() == "return");
+	let return_type = return_node
 		.map(|return_node| convert_argument_type(return_node, "type"))
 		.transpose()?;
+	let return_optional = return_node
+		.and_then(|return_node| get_bool_property(return_node, "optional").ok())
+		.unwrap_or(false);
+	let acked = get_bool_property(member, "acked").ok().unwrap_or(false);
+	if acked && (_type != MemberType::Signal || side != Side::Server || return_type.is_some()) {
+		return Err(ParseError::InvalidAcked(name));
+	}
 	Ok(Member {
 		name,
 		opcode: hasher.finish(),
@@ -216,6 +230,8 @@ fn convert_member(member: &KdlNode, mut hasher: FnvHasher) -> Result<Member, Par
 		_type,
 		arguments,
 		return_type,
+		return_optional,
+		acked,
 	})
 }
 fn convert_argument(argument: &KdlNode) -> Result<Argument, ParseError> {
@@ -227,11 +243,18 @@ fn convert_argument(argument: &KdlNode) -> Result<Argument, ParseError> {
 	let optional = get_bool_property(argument, "optional")
 		.ok()
 		.unwrap_or(false);
+	let spatial_parent = get_bool_property(argument, "spatial_parent")
+		.ok()
+		.unwrap_or(false);
+	if spatial_parent && (optional || !matches!(_type, ArgumentType::Node { .. })) {
+		return Err(ParseError::InvalidSpatialParent(name));
+	}
 	Ok(Argument {
 		name,
 		description,
 		_type,
 		optional,
+		spatial_parent,
 	})
 }
 fn convert_argument_type(argument: &KdlNode, key: &str) -> Result<ArgumentType, ParseError> {
@@ -252,6 +275,7 @@ fn convert_argument_type(argument: &KdlNode, key: &str) -> Result<ArgumentType,
 		"string" => ArgumentType::String,
 		"color" => ArgumentType::Color,
 		"bytes" => ArgumentType::Bytes,
+		"fd" => ArgumentType::Fd,
 		"vec" => ArgumentType::Vec(Box::new(convert_argument_type(argument, "member_type")?)),
 		"map" => ArgumentType::Map(Box::new(convert_argument_type(argument, "value_type")?)),
 		"id" => ArgumentType::NodeID,
@@ -0,0 +1,81 @@
+//! Marker trait for everything that's allowed over IPC.
+//!
+//! All types in the protocol are generated from the `.kdl` files in [`crate::protocol`], and the generated code implements [`Schema`] for each of them.
+//! Every message the generated code sends is checked with [`assert_schema`], so a type that isn't part of the protocol can't end up on the wire.
+//!
+//! Hand-written types that are serialized over IPC (such as [`crate::flex::Datamap`]) opt in by implementing the trait themselves,
+//! after making sure their serialized form matches what the protocol describes:
+//! ```
+//! use stardust_xr_schemas::Schema;
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Handwritten(u32);
+//! impl Schema for Handwritten {}
+//!
+//! stardust_xr_schemas::assert_schema::<(Handwritten, Vec<Option<Handwritten>>)>();
+//! ```
+
+use std::collections::HashMap;
+
+/// A type whose serialized form is part of the stardust protocol.
+pub trait Schema {}
+
+/// Fails to compile if `T` isn't part of the protocol. Costs nothing at runtime.
+///
+/// ```compile_fail
+/// struct NotSchema;
+/// stardust_xr_schemas::assert_schema::<(u32, NotSchema)>();
+/// ```
+pub const fn assert_schema<T: Schema + ?Sized>() {}
+
+macro_rules! impl_schema {
+	($($t:ty),*) => {
+		$(impl Schema for $t {})*
+	};
+}
+impl_schema!(
+	(),
+	bool,
+	u8,
+	u16,
+	u32,
+	u64,
+	i8,
+	i16,
+	i32,
+	i64,
+	f32,
+	f64,
+	str,
+	String
+);
+
+impl<T: Schema + ?Sized> Schema for &T {}
+impl<T: Schema> Schema for [T] {}
+impl<T: Schema, const N: usize> Schema for [T; N] {}
+impl<T: Schema> Schema for Vec<T> {}
+impl<T: Schema> Schema for Option<T> {}
+impl<K: Schema, V: Schema, S> Schema for HashMap<K, V, S> {}
+
+macro_rules! impl_schema_tuple {
+	($($t:ident),*) => {
+		impl<$($t: Schema),*> Schema for ($($t,)*) {}
+	};
+}
+impl_schema_tuple!(A);
+impl_schema_tuple!(A, B);
+impl_schema_tuple!(A, B, C);
+impl_schema_tuple!(A, B, C, D);
+impl_schema_tuple!(A, B, C, D, E);
+impl_schema_tuple!(A, B, C, D, E, F);
+impl_schema_tuple!(A, B, C, D, E, F, G);
+impl_schema_tuple!(A, B, C, D, E, F, G, H);
+impl_schema_tuple!(A, B, C, D, E, F, G, H, I);
+impl_schema_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_schema_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_schema_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+impl<T: Schema> Schema for mint::Vector2<T> {}
+impl<T: Schema> Schema for mint::Vector3<T> {}
+impl<T: Schema> Schema for mint::Quaternion<T> {}
+impl<T: Schema> Schema for mint::ColumnMatrix4<T> {}